@@ -0,0 +1,458 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! ACPI device description generation.
+//!
+//! On x86 guests discover platform devices through the Differentiated System Description
+//! Table (DSDT), whose body is a list of AML objects. Devices implementing [`DeviceAml`]
+//! provide their identity, and [`device_aml`] encodes them as `Device` objects whose `_CRS`
+//! (current resource settings) is synthesized by [`resource_template`] from their
+//! `Resource::PioAddressRange`, `Resource::MmioAddressRange` and `Resource::LegacyIrq`
+//! entries.
+//!
+//! Devices registered with
+//! [`IoManager::register_pio_aml`](../device_manager/struct.IoManager.html#method.register_pio_aml)
+//! or
+//! [`IoManager::register_mmio_aml`](../device_manager/struct.IoManager.html#method.register_mmio_aml)
+//! are all described by
+//! [`IoManager::generate_dsdt_body`](../device_manager/struct.IoManager.html#method.generate_dsdt_body).
+//!
+//! # Example
+//!
+//! ```
+//! # use vm_device::acpi::{device_aml, DeviceAml};
+//! # use vm_device::resources::Resource;
+//! struct Serial;
+//!
+//! impl DeviceAml for Serial {
+//!     fn aml_name(&self) -> &str {
+//!         "COM1"
+//!     }
+//!     fn aml_hid(&self) -> &str {
+//!         "PNP0501"
+//!     }
+//! }
+//!
+//! let resources = [
+//!     Resource::PioAddressRange { base: 0x3f8, size: 8 },
+//!     Resource::LegacyIrq(4),
+//! ];
+//! let aml = device_aml(&Serial, &resources).unwrap();
+//! // DeviceOp
+//! assert_eq!(&aml[..2], &[0x5b, 0x82]);
+//! ```
+
+use std::fmt::{Display, Formatter};
+
+use crate::resources::Resource;
+
+const ZERO_OP: u8 = 0x00;
+const ONE_OP: u8 = 0x01;
+const NAME_OP: u8 = 0x08;
+const BYTE_PREFIX: u8 = 0x0a;
+const WORD_PREFIX: u8 = 0x0b;
+const DWORD_PREFIX: u8 = 0x0c;
+const STRING_PREFIX: u8 = 0x0d;
+const QWORD_PREFIX: u8 = 0x0e;
+const SCOPE_OP: u8 = 0x10;
+const BUFFER_OP: u8 = 0x11;
+const EXT_OP_PREFIX: u8 = 0x5b;
+const DEVICE_OP: u8 = 0x82;
+const ROOT_CHAR: u8 = b'\\';
+
+// Resource descriptors.
+const IO_PORT_DESC: u8 = 0x47;
+const END_TAG_DESC: u8 = 0x79;
+const MEMORY32_FIXED_DESC: u8 = 0x86;
+const EXTENDED_IRQ_DESC: u8 = 0x89;
+const QWORD_ADDRESS_DESC: u8 = 0x8a;
+
+/// Errors encountered while generating AML.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The name is not a valid AML name segment of up to four characters.
+    InvalidName(String),
+    /// A string contains a NUL or non-ASCII character.
+    InvalidString(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidName(name) => write!(f, "invalid AML name: {:?}", name),
+            Error::InvalidString(s) => write!(f, "invalid AML string: {:?}", s),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Description of the ACPI `Device` object of a device.
+///
+/// The `_CRS` object is generated from the resources of the device, the device only needs
+/// to provide its identity and any additional object.
+pub trait DeviceAml {
+    /// Return the name of the object, a name segment of up to four characters (e.g.
+    /// `"COM1"`), padded with underscores when shorter.
+    fn aml_name(&self) -> &str;
+
+    /// Return the hardware ID of the device: either an EISA ID such as `"PNP0501"`, encoded
+    /// as an integer, or an ACPI ID such as `"ACPI0010"`, encoded as a string.
+    fn aml_hid(&self) -> &str;
+
+    /// Return the unique ID telling apart devices with the same hardware ID, if any.
+    fn aml_uid(&self) -> Option<u32> {
+        None
+    }
+
+    /// Return the encoded AML objects appended to the `Device` object, after `_HID`, `_UID`
+    /// and `_CRS`.
+    fn aml_objects(&self) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+/// Encode the `Device` object describing `device`, with `_CRS` built from `resources`.
+pub fn device_aml(device: &dyn DeviceAml, resources: &[Resource]) -> Result<Vec<u8>, Error> {
+    let mut body = name_seg(device.aml_name())?;
+    body.extend(name("_HID", hid(device.aml_hid())?));
+    if let Some(uid) = device.aml_uid() {
+        body.extend(name("_UID", integer(uid.into())));
+    }
+    body.extend(name("_CRS", resource_template(resources)));
+    body.extend(device.aml_objects());
+
+    let mut aml = vec![EXT_OP_PREFIX, DEVICE_OP];
+    aml.extend(package_length(body.len()));
+    aml.extend(body);
+    Ok(aml)
+}
+
+/// Encode a `ResourceTemplate` buffer listing the port I/O ranges, memory-mapped I/O ranges
+/// and legacy IRQs in `resources`, as used for `_CRS` objects. Other resources are ignored.
+///
+/// Port I/O ranges are described with 16-bit decoding, memory ranges as fixed read/write
+/// ranges and IRQs as exclusive, edge triggered and active high, like ISA interrupts.
+pub fn resource_template(resources: &[Resource]) -> Vec<u8> {
+    let mut descriptors = Vec::new();
+    for res in resources.iter() {
+        match *res {
+            Resource::PioAddressRange { base, size } => {
+                // The length of an I/O port descriptor is a single byte.
+                let mut offset = 0u32;
+                while offset < u32::from(size) {
+                    let min = (u32::from(base) + offset) as u16;
+                    let len = (u32::from(size) - offset).min(0xff);
+                    descriptors.push(IO_PORT_DESC);
+                    // Decodes 16-bit addresses.
+                    descriptors.push(0x01);
+                    descriptors.extend_from_slice(&min.to_le_bytes());
+                    descriptors.extend_from_slice(&min.to_le_bytes());
+                    // Alignment.
+                    descriptors.push(0x01);
+                    descriptors.push(len as u8);
+                    offset += len;
+                }
+            }
+            Resource::MmioAddressRange { base, size } => {
+                let end = base.checked_add(size);
+                if end.is_some_and(|end| end <= 1 << 32) {
+                    descriptors.push(MEMORY32_FIXED_DESC);
+                    descriptors.extend_from_slice(&9u16.to_le_bytes());
+                    // Read/write.
+                    descriptors.push(0x01);
+                    descriptors.extend_from_slice(&(base as u32).to_le_bytes());
+                    descriptors.extend_from_slice(&(size as u32).to_le_bytes());
+                } else {
+                    descriptors.push(QWORD_ADDRESS_DESC);
+                    descriptors.extend_from_slice(&43u16.to_le_bytes());
+                    // Memory range, with fixed minimum and maximum addresses.
+                    descriptors.push(0x00);
+                    descriptors.push(0x0c);
+                    // Non-cacheable, read/write.
+                    descriptors.push(0x01);
+                    for field in &[
+                        // Granularity.
+                        0,
+                        base,
+                        base.wrapping_add(size).wrapping_sub(1),
+                        // Translation offset.
+                        0,
+                        size,
+                    ] {
+                        descriptors.extend_from_slice(&field.to_le_bytes());
+                    }
+                }
+            }
+            Resource::LegacyIrq(irq) => {
+                descriptors.push(EXTENDED_IRQ_DESC);
+                descriptors.extend_from_slice(&6u16.to_le_bytes());
+                // Consumer, edge triggered, active high, exclusive.
+                descriptors.push(0x03);
+                // Number of interrupts.
+                descriptors.push(0x01);
+                descriptors.extend_from_slice(&irq.to_le_bytes());
+            }
+            _ => continue,
+        }
+    }
+    // A zero checksum means the template is valid.
+    descriptors.extend_from_slice(&[END_TAG_DESC, 0x00]);
+    buffer(&descriptors)
+}
+
+/// Encode a `Scope` object named by the absolute `path` (e.g. `"_SB_"` for `\_SB_`), holding
+/// the already encoded objects in `body`.
+pub fn scope(path: &str, body: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut content = vec![ROOT_CHAR];
+    content.extend(name_seg(path)?);
+    content.extend_from_slice(body);
+
+    let mut aml = vec![SCOPE_OP];
+    aml.extend(package_length(content.len()));
+    aml.extend(content);
+    Ok(aml)
+}
+
+// Encode a name segment, padded with underscores to four characters.
+fn name_seg(name: &str) -> Result<Vec<u8>, Error> {
+    let bytes = name.as_bytes();
+    let valid = !bytes.is_empty()
+        && bytes.len() <= 4
+        && (bytes[0].is_ascii_uppercase() || bytes[0] == b'_')
+        && bytes
+            .iter()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || *b == b'_');
+    if !valid {
+        return Err(Error::InvalidName(name.to_string()));
+    }
+    let mut seg = bytes.to_vec();
+    seg.resize(4, b'_');
+    Ok(seg)
+}
+
+// Encode a `Name` object whose name is a valid name segment.
+fn name(seg: &str, data: Vec<u8>) -> Vec<u8> {
+    let mut aml = vec![NAME_OP];
+    aml.extend_from_slice(seg.as_bytes());
+    aml.extend(data);
+    aml
+}
+
+fn integer(value: u64) -> Vec<u8> {
+    let mut aml = Vec::new();
+    match value {
+        0 => aml.push(ZERO_OP),
+        1 => aml.push(ONE_OP),
+        2..=0xff => aml.extend_from_slice(&[BYTE_PREFIX, value as u8]),
+        0x100..=0xffff => {
+            aml.push(WORD_PREFIX);
+            aml.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            aml.push(DWORD_PREFIX);
+            aml.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        _ => {
+            aml.push(QWORD_PREFIX);
+            aml.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    aml
+}
+
+fn string(s: &str) -> Result<Vec<u8>, Error> {
+    if !s.bytes().all(|b| b.is_ascii() && b != 0) {
+        return Err(Error::InvalidString(s.to_string()));
+    }
+    let mut aml = vec![STRING_PREFIX];
+    aml.extend_from_slice(s.as_bytes());
+    aml.push(0);
+    Ok(aml)
+}
+
+fn buffer(data: &[u8]) -> Vec<u8> {
+    let mut content = integer(data.len() as u64);
+    content.extend_from_slice(data);
+
+    let mut aml = vec![BUFFER_OP];
+    aml.extend(package_length(content.len()));
+    aml.extend(content);
+    aml
+}
+
+// Encode a hardware ID as a compressed EISA ID when it has the "AAA####" format, or as a
+// string otherwise.
+fn hid(id: &str) -> Result<Vec<u8>, Error> {
+    match eisa_id(id) {
+        Some(value) => Ok(integer(value.into())),
+        None => string(id),
+    }
+}
+
+fn eisa_id(id: &str) -> Option<u32> {
+    let bytes = id.as_bytes();
+    if bytes.len() != 7
+        || !bytes[..3].iter().all(u8::is_ascii_uppercase)
+        || !bytes[3..].iter().all(u8::is_ascii_hexdigit)
+    {
+        return None;
+    }
+    let vendor = bytes[..3]
+        .iter()
+        .fold(0, |acc, b| (acc << 5) | u32::from(b - 0x40));
+    let product = u32::from_str_radix(&id[3..], 16).ok()?;
+    // The ID is stored in big endian order.
+    Some(((vendor << 16) | product).swap_bytes())
+}
+
+// Encode the length of a package whose content is `len` bytes long. The encoded length
+// includes the bytes of the encoding itself.
+fn package_length(len: usize) -> Vec<u8> {
+    if len < 0x3f {
+        return vec![(len + 1) as u8];
+    }
+    let count = if len + 2 <= 0xfff {
+        2
+    } else if len + 3 <= 0xf_ffff {
+        3
+    } else {
+        4
+    };
+    let total = len + count;
+    let mut encoded = vec![(((count - 1) << 6) | (total & 0xf)) as u8];
+    for i in 1..count {
+        encoded.push((total >> (4 + 8 * (i - 1))) as u8);
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Serial;
+
+    impl DeviceAml for Serial {
+        fn aml_name(&self) -> &str {
+            "COM1"
+        }
+
+        fn aml_hid(&self) -> &str {
+            "PNP0501"
+        }
+
+        fn aml_uid(&self) -> Option<u32> {
+            Some(1)
+        }
+
+        fn aml_objects(&self) -> Vec<u8> {
+            name("_STA", integer(0xf))
+        }
+    }
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(eisa_id("PNP0501"), Some(0x0105_d041));
+        assert_eq!(eisa_id("PNP0A03"), Some(0x030a_d041));
+        assert_eq!(eisa_id("ACPI0010"), None);
+        assert_eq!(eisa_id("pnp0501"), None);
+        assert_eq!(hid("PNP0501").unwrap(), vec![0x0c, 0x41, 0xd0, 0x05, 0x01]);
+        assert_eq!(hid("ACPI0010").unwrap(), b"\x0dACPI0010\0".to_vec());
+        assert_eq!(
+            string("caf\u{e9}"),
+            Err(Error::InvalidString("caf\u{e9}".to_string()))
+        );
+
+        assert_eq!(integer(0), vec![0x00]);
+        assert_eq!(integer(1), vec![0x01]);
+        assert_eq!(integer(0xff), vec![0x0a, 0xff]);
+        assert_eq!(integer(0x100), vec![0x0b, 0x00, 0x01]);
+        assert_eq!(integer(0x1_0000), vec![0x0c, 0x00, 0x00, 0x01, 0x00]);
+        assert_eq!(
+            integer(1 << 32),
+            vec![0x0e, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]
+        );
+
+        assert_eq!(package_length(0x3e), vec![0x3f]);
+        assert_eq!(package_length(0x3f), vec![0x41, 0x04]);
+        assert_eq!(package_length(0xffd), vec![0x4f, 0xff]);
+        assert_eq!(package_length(0xffe), vec![0x81, 0x00, 0x01]);
+        assert_eq!(package_length(0x10_0000), vec![0xc4, 0x00, 0x00, 0x01]);
+
+        assert_eq!(name_seg("_SB").unwrap(), b"_SB_".to_vec());
+        for invalid in &["", "COM10", "1COM", "com1"] {
+            assert_eq!(
+                name_seg(invalid),
+                Err(Error::InvalidName(invalid.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_resource_template() {
+        let resources = [
+            Resource::PioAddressRange {
+                base: 0x3f8,
+                size: 8,
+            },
+            Resource::MmioAddressRange {
+                base: 0xfee0_0000,
+                size: 0x1000,
+            },
+            Resource::LegacyIrq(4),
+            Resource::KvmMemSlot(0),
+        ];
+        let mut expected = vec![0x11, 0x22, 0x0a, 0x1f];
+        expected.extend_from_slice(&[0x47, 0x01, 0xf8, 0x03, 0xf8, 0x03, 0x01, 0x08]);
+        expected.extend_from_slice(&[0x86, 0x09, 0x00, 0x01]);
+        expected.extend_from_slice(&[0x00, 0x00, 0xe0, 0xfe, 0x00, 0x10, 0x00, 0x00]);
+        expected.extend_from_slice(&[0x89, 0x06, 0x00, 0x03, 0x01, 0x04, 0x00, 0x00, 0x00]);
+        expected.extend_from_slice(&[0x79, 0x00]);
+        assert_eq!(resource_template(&resources), expected);
+
+        // Port ranges longer than 255 bytes are split.
+        let template = resource_template(&[Resource::PioAddressRange {
+            base: 0x1000,
+            size: 0x100,
+        }]);
+        assert_eq!(
+            template[4..],
+            [
+                0x47, 0x01, 0x00, 0x10, 0x00, 0x10, 0x01, 0xff, 0x47, 0x01, 0xff, 0x10, 0xff, 0x10,
+                0x01, 0x01, 0x79, 0x00
+            ]
+        );
+
+        // Memory ranges above 4 GiB need a QWord descriptor.
+        let template = resource_template(&[Resource::MmioAddressRange {
+            base: 0x1_0000_0000,
+            size: 0x2000,
+        }]);
+        assert_eq!(template.len(), 4 + 46 + 2);
+        assert_eq!(template[4..10], [0x8a, 0x2b, 0x00, 0x00, 0x0c, 0x01]);
+        assert_eq!(template[18..26], 0x1_0000_0000u64.to_le_bytes());
+        assert_eq!(template[26..34], 0x1_0000_1fffu64.to_le_bytes());
+        assert_eq!(template[42..50], 0x2000u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_device_aml() {
+        let resources = [Resource::LegacyIrq(4)];
+        let aml = device_aml(&Serial, &resources).unwrap();
+
+        let mut body = b"COM1".to_vec();
+        body.extend_from_slice(&[0x08, b'_', b'H', b'I', b'D', 0x0c, 0x41, 0xd0, 0x05, 0x01]);
+        body.extend_from_slice(&[0x08, b'_', b'U', b'I', b'D', 0x01]);
+        body.extend_from_slice(&[0x08, b'_', b'C', b'R', b'S']);
+        body.extend(resource_template(&resources));
+        body.extend_from_slice(&[0x08, b'_', b'S', b'T', b'A', 0x0a, 0x0f]);
+        let mut expected = vec![0x5b, 0x82, body.len() as u8 + 1];
+        expected.extend(body);
+        assert_eq!(aml, expected);
+
+        let mut expected = vec![0x10, aml.len() as u8 + 6, b'\\', b'_', b'S', b'B', b'_'];
+        expected.extend_from_slice(&aml);
+        assert_eq!(scope("_SB", &aml).unwrap(), expected);
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! Asynchronous device traits.
+//!
+//! [`AsyncDevicePio`] and [`AsyncDeviceMmio`] are the counterparts of [`DevicePio`] and
+//! [`DeviceMmio`] for devices which cannot complete an access right away, for example
+//! because they forward it to a backend over a socket. Their methods return an [`IoFuture`]
+//! instead of blocking the calling thread, which keeps the traits usable as trait objects
+//! and independent of any particular executor.
+//!
+//! Asynchronous devices are registered with
+//! [`IoManager::register_async_pio`](../device_manager/struct.IoManager.html#method.register_async_pio)
+//! and [`IoManager::register_async_mmio`](../device_manager/struct.IoManager.html#method.register_async_mmio),
+//! and accesses to them are dispatched by the `*_async` methods of `IoManager`.
+//!
+//! # Example
+//!
+//! ```
+//! # use std::sync::Mutex;
+//! # use vm_device::async_io::{AsyncDeviceMmio, IoFuture};
+//! # use vm_device::bus::{MmioAddress, MmioAddressOffset};
+//! struct RemoteDevice {
+//!     config: Mutex<u8>,
+//! }
+//!
+//! impl AsyncDeviceMmio for RemoteDevice {
+//!     fn mmio_read<'a>(
+//!         &'a self,
+//!         _base: MmioAddress,
+//!         _offset: MmioAddressOffset,
+//!         data: &'a mut [u8],
+//!     ) -> IoFuture<'a> {
+//!         // A real device would await the reply of its backend here.
+//!         Box::pin(async move { data[0] = *self.config.lock().unwrap() })
+//!     }
+//!
+//!     fn mmio_write<'a>(
+//!         &'a self,
+//!         _base: MmioAddress,
+//!         _offset: MmioAddressOffset,
+//!         data: &'a [u8],
+//!     ) -> IoFuture<'a> {
+//!         Box::pin(async move { *self.config.lock().unwrap() = data[0] })
+//!     }
+//! }
+//! ```
+//!
+//! [`DevicePio`]: ../trait.DevicePio.html
+//! [`DeviceMmio`]: ../trait.DeviceMmio.html
+
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::bus::{MmioAddress, MmioAddressOffset, PioAddress, PioAddressOffset};
+
+/// Future returned by asynchronous devices, which resolves once the access completes.
+pub type IoFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// Same as [`DevicePio`](../trait.DevicePio.html), but accesses complete asynchronously.
+pub trait AsyncDevicePio {
+    /// Handle a read operation on the device.
+    ///
+    /// # Arguments
+    ///
+    /// * `base`:   base address on a PIO bus
+    /// * `offset`: base address' offset
+    /// * `data`:   a buffer provided by the caller to store the read data
+    fn pio_read<'a>(
+        &'a self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        data: &'a mut [u8],
+    ) -> IoFuture<'a>;
+
+    /// Handle a write operation to the device.
+    ///
+    /// # Arguments
+    ///
+    /// * `base`:   base address on a PIO bus
+    /// * `offset`: base address' offset
+    /// * `data`:   a buffer provided by the caller holding the data to write
+    fn pio_write<'a>(
+        &'a self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        data: &'a [u8],
+    ) -> IoFuture<'a>;
+}
+
+/// Same as [`DeviceMmio`](../trait.DeviceMmio.html), but accesses complete asynchronously.
+pub trait AsyncDeviceMmio {
+    /// Handle a read operation on the device.
+    ///
+    /// # Arguments
+    ///
+    /// * `base`:   base address on a MMIO bus
+    /// * `offset`: base address' offset
+    /// * `data`:   a buffer provided by the caller to store the read data
+    fn mmio_read<'a>(
+        &'a self,
+        base: MmioAddress,
+        offset: MmioAddressOffset,
+        data: &'a mut [u8],
+    ) -> IoFuture<'a>;
+
+    /// Handle a write operation to the device.
+    ///
+    /// # Arguments
+    ///
+    /// * `base`:   base address on a MMIO bus
+    /// * `offset`: base address' offset
+    /// * `data`:   a buffer provided by the caller holding the data to write
+    fn mmio_write<'a>(
+        &'a self,
+        base: MmioAddress,
+        offset: MmioAddressOffset,
+        data: &'a [u8],
+    ) -> IoFuture<'a>;
+}
+
+// Blanket implementations for Arc<T>.
+
+impl<T: AsyncDevicePio + ?Sized> AsyncDevicePio for Arc<T> {
+    fn pio_read<'a>(
+        &'a self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        data: &'a mut [u8],
+    ) -> IoFuture<'a> {
+        self.deref().pio_read(base, offset, data)
+    }
+
+    fn pio_write<'a>(
+        &'a self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        data: &'a [u8],
+    ) -> IoFuture<'a> {
+        self.deref().pio_write(base, offset, data)
+    }
+}
+
+impl<T: AsyncDeviceMmio + ?Sized> AsyncDeviceMmio for Arc<T> {
+    fn mmio_read<'a>(
+        &'a self,
+        base: MmioAddress,
+        offset: MmioAddressOffset,
+        data: &'a mut [u8],
+    ) -> IoFuture<'a> {
+        self.deref().mmio_read(base, offset, data)
+    }
+
+    fn mmio_write<'a>(
+        &'a self,
+        base: MmioAddress,
+        offset: MmioAddressOffset,
+        data: &'a [u8],
+    ) -> IoFuture<'a> {
+        self.deref().mmio_write(base, offset, data)
+    }
+}
@@ -0,0 +1,384 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! Bridges forwarding accesses to child buses.
+//!
+//! A [`Bridge`] owns a child [`Bus`] and is registered on its parent bus (or with an
+//! [`IoManager`](../device_manager/struct.IoManager.html)) as a regular device covering the
+//! window it forwards. An access at `offset` within the window is translated to the child
+//! address `child_base + offset` and dispatched to the device registered there, which can
+//! itself be another bridge, so buses can be nested into a tree.
+//!
+//! Devices can be added to and removed from the child bus while the bridge is registered,
+//! as happens with hotplug behind PCI bridges. Reads of child addresses without a device
+//! return all ones, like a master abort on PCI, and writes to them are ignored. The bridge
+//! is poisoned as soon as any device of its child bus is, so the poison policy of the
+//! parent bus also covers the devices behind it.
+//!
+//! # Example
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use vm_device::bridge::MmioBridge;
+//! # use vm_device::bus::{MmioAddress, MmioAddressOffset, MmioRange};
+//! # use vm_device::device_manager::{IoManager, MmioManager};
+//! # use vm_device::DeviceMmio;
+//! struct Register;
+//!
+//! impl DeviceMmio for Register {
+//!     fn mmio_read(&self, base: MmioAddress, _offset: MmioAddressOffset, data: &mut [u8]) {
+//!         data[0] = base.0 as u8;
+//!     }
+//!     fn mmio_write(&self, _base: MmioAddress, _offset: MmioAddressOffset, _data: &[u8]) {}
+//! }
+//!
+//! // Forward the window starting at 0xc000_0000 to child addresses starting at 0x40.
+//! let bridge = Arc::new(MmioBridge::<Arc<dyn DeviceMmio + Send + Sync>>::new(MmioAddress(0x40)));
+//! let mut manager = IoManager::new();
+//! let window = MmioRange::new(MmioAddress(0xc000_0000), 0x1000).unwrap();
+//! manager.register_mmio(window, bridge.clone()).unwrap();
+//!
+//! let range = MmioRange::new(MmioAddress(0x44), 4).unwrap();
+//! bridge.register(range, Arc::new(Register)).unwrap();
+//!
+//! let mut data = [0; 1];
+//! manager.mmio_read(MmioAddress(0xc000_0004), &mut data).unwrap();
+//! assert_eq!(data, [0x44]);
+//! ```
+
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+
+use crate::bus::{
+    self, Bus, BusAddress, BusRange, MmioAddress, MmioAddressOffset, PioAddress, PioAddressOffset,
+};
+use crate::{DeviceMmio, DevicePio};
+
+/// A device forwarding the accesses to its window to a child bus.
+pub struct Bridge<A: BusAddress, D> {
+    child_base: A,
+    child: RwLock<Bus<A, D>>,
+}
+
+/// A bridge between MMIO buses.
+pub type MmioBridge<D> = Bridge<MmioAddress, D>;
+/// A bridge between PIO buses.
+pub type PioBridge<D> = Bridge<PioAddress, D>;
+
+impl<A: BusAddress, D> Bridge<A, D> {
+    /// Create a bridge with an empty child bus, which translates the start of its window to
+    /// the child address `child_base`.
+    pub fn new(child_base: A) -> Self {
+        Bridge {
+            child_base,
+            child: RwLock::new(Bus::new()),
+        }
+    }
+
+    /// Return the child address the start of the window is translated to.
+    pub fn child_base(&self) -> A {
+        self.child_base
+    }
+
+    /// Register a device with the provided range of child addresses.
+    pub fn register(&self, range: BusRange<A>, device: D) -> Result<(), bus::Error> {
+        self.child
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .register(range, device)
+    }
+
+    /// Deregister the device registered at the child address `addr`.
+    pub fn deregister(&self, addr: A) -> Option<(BusRange<A>, D)> {
+        self.child
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .deregister(addr)
+    }
+
+    /// Return a read guard of the child bus, for looking up its devices.
+    pub fn child(&self) -> RwLockReadGuard<'_, Bus<A, D>> {
+        self.child.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Translate `offset` within the window into a child address, if it does not overflow.
+    pub fn translate(&self, offset: A::V) -> Option<A> {
+        self.child_base.checked_add(offset)
+    }
+
+    // Resolve an access of `len` bytes at `offset` within the window, then run `access` on
+    // the device with the base of its range and the offset within it. Returns `false` when
+    // no device handles the access.
+    fn forward<F>(&self, offset: A::V, len: usize, access: F) -> bool
+    where
+        F: FnOnce(&D, A, A::V),
+    {
+        let addr = match self.translate(offset) {
+            Some(addr) => addr,
+            None => return false,
+        };
+        let child = self.child();
+        match child.check_access(addr, len) {
+            Ok((range, device)) => {
+                access(device, range.base(), addr - range.base());
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+impl<D: DeviceMmio> DeviceMmio for Bridge<MmioAddress, D> {
+    fn mmio_read(&self, _base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+        let len = data.len();
+        if !self.forward(offset, len, |device, base, offset| {
+            device.mmio_read(base, offset, data)
+        }) {
+            data.fill(0xff);
+        }
+    }
+
+    fn mmio_write(&self, _base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+        self.forward(offset, data.len(), |device, base, offset| {
+            device.mmio_write(base, offset, data)
+        });
+    }
+
+    fn mmio_poisoned(&self) -> bool {
+        self.child()
+            .devices()
+            .any(|(_, device)| device.mmio_poisoned())
+    }
+}
+
+impl<D: DevicePio> DevicePio for Bridge<PioAddress, D> {
+    fn pio_read(&self, _base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
+        let len = data.len();
+        if !self.forward(offset, len, |device, base, offset| {
+            device.pio_read(base, offset, data)
+        }) {
+            data.fill(0xff);
+        }
+    }
+
+    fn pio_write(&self, _base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
+        self.forward(offset, data.len(), |device, base, offset| {
+            device.pio_write(base, offset, data)
+        });
+    }
+
+    fn pio_read_string(
+        &self,
+        _base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &mut [u8],
+    ) {
+        if !self.forward(offset, size, |device, base, offset| {
+            device.pio_read_string(base, offset, size, data)
+        }) {
+            data.fill(0xff);
+        }
+    }
+
+    fn pio_write_string(
+        &self,
+        _base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &[u8],
+    ) {
+        self.forward(offset, size, |device, base, offset| {
+            device.pio_write_string(base, offset, size, data)
+        });
+    }
+
+    fn pio_poisoned(&self) -> bool {
+        self.child()
+            .devices()
+            .any(|(_, device)| device.pio_poisoned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use crate::bus::{MmioRange, PioRange, PoisonPolicy};
+    use crate::device_manager::{IoManager, MmioManager};
+
+    // Logs the accesses it receives, and reads back the low byte of its base.
+    #[derive(Default)]
+    struct LogDevice {
+        writes: Mutex<Vec<(u64, u64, Vec<u8>)>>,
+        poisoned: bool,
+    }
+
+    impl DeviceMmio for LogDevice {
+        fn mmio_read(&self, base: MmioAddress, _offset: MmioAddressOffset, data: &mut [u8]) {
+            data.fill(base.0 as u8);
+        }
+
+        fn mmio_write(&self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+            self.writes
+                .lock()
+                .unwrap()
+                .push((base.0, offset, data.to_vec()));
+        }
+
+        fn mmio_poisoned(&self) -> bool {
+            self.poisoned
+        }
+    }
+
+    impl DevicePio for LogDevice {
+        fn pio_read(&self, base: PioAddress, _offset: PioAddressOffset, data: &mut [u8]) {
+            data.fill(base.0 as u8);
+        }
+
+        fn pio_write(&self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
+            self.writes
+                .lock()
+                .unwrap()
+                .push((base.0.into(), offset.into(), data.to_vec()));
+        }
+
+        fn pio_poisoned(&self) -> bool {
+            self.poisoned
+        }
+    }
+
+    #[test]
+    fn test_mmio_bridge_tree() {
+        let mut io_mgr = IoManager::new();
+        let root: Arc<MmioBridge<Arc<dyn DeviceMmio + Send + Sync>>> =
+            Arc::new(Bridge::new(MmioAddress(0x1000)));
+        let leaf: Arc<MmioBridge<Arc<dyn DeviceMmio + Send + Sync>>> =
+            Arc::new(Bridge::new(MmioAddress(0)));
+        let device = Arc::new(LogDevice::default());
+        assert_eq!(root.child_base(), MmioAddress(0x1000));
+        assert_eq!(root.translate(0x10), Some(MmioAddress(0x1010)));
+        assert_eq!(
+            Bridge::<_, LogDevice>::new(MmioAddress(u64::MAX)).translate(1),
+            None
+        );
+
+        io_mgr
+            .register_mmio(
+                MmioRange::new(MmioAddress(0xd000_0000), 0x1_0000).unwrap(),
+                root.clone(),
+            )
+            .unwrap();
+        root.register(
+            MmioRange::new(MmioAddress(0x2000), 0x100).unwrap(),
+            leaf.clone(),
+        )
+        .unwrap();
+        // Devices can be added behind a bridge which is already registered.
+        leaf.register(
+            MmioRange::new(MmioAddress(0x20), 0x10).unwrap(),
+            device.clone(),
+        )
+        .unwrap();
+        assert!(root.child().device(MmioAddress(0x2000)).is_some());
+
+        // 0xd000_1024 -> 0x2024 on the root's child bus -> 0x24 on the leaf's child bus.
+        let mut data = [0; 2];
+        io_mgr
+            .mmio_read(MmioAddress(0xd000_1024), &mut data)
+            .unwrap();
+        assert_eq!(data, [0x20, 0x20]);
+        io_mgr.mmio_write(MmioAddress(0xd000_1028), &[1]).unwrap();
+        assert_eq!(*device.writes.lock().unwrap(), vec![(0x20, 8, vec![1])]);
+
+        // Master aborts.
+        io_mgr
+            .mmio_read(MmioAddress(0xd000_1000), &mut data)
+            .unwrap();
+        assert_eq!(data, [0xff, 0xff]);
+        io_mgr
+            .mmio_read(MmioAddress(0xd000_102f), &mut data)
+            .unwrap();
+        assert_eq!(data, [0xff, 0xff]);
+        io_mgr.mmio_write(MmioAddress(0xd000_1030), &[1]).unwrap();
+        assert_eq!(device.writes.lock().unwrap().len(), 1);
+
+        assert!(leaf.deregister(MmioAddress(0x20)).is_some());
+        io_mgr
+            .mmio_read(MmioAddress(0xd000_1024), &mut data)
+            .unwrap();
+        assert_eq!(data, [0xff, 0xff]);
+
+        // A poisoned device poisons the bridges above it.
+        assert!(!root.mmio_poisoned());
+        let poisoned = Arc::new(LogDevice {
+            poisoned: true,
+            ..Default::default()
+        });
+        leaf.register(MmioRange::new(MmioAddress(0x20), 0x10).unwrap(), poisoned)
+            .unwrap();
+        assert!(root.mmio_poisoned());
+        io_mgr.set_poison_policy(PoisonPolicy::Fail);
+        assert_eq!(
+            io_mgr.mmio_read(MmioAddress(0xd000_1024), &mut data),
+            Err(bus::Error::DevicePoisoned)
+        );
+    }
+
+    #[test]
+    fn test_pio_bridge() {
+        let bridge = Bridge::new(PioAddress(0x100));
+        bridge
+            .register(
+                PioRange::new(PioAddress(0x104), 4).unwrap(),
+                LogDevice::default(),
+            )
+            .unwrap();
+        assert_eq!(
+            bridge.register(
+                PioRange::new(PioAddress(0x107), 4).unwrap(),
+                LogDevice::default()
+            ),
+            Err(bus::Error::DeviceOverlap)
+        );
+
+        let base = PioAddress(0x3f8);
+        let mut data = [0; 4];
+        bridge.pio_read(base, 5, &mut data[..1]);
+        assert_eq!(data[0], 0x04);
+        bridge.pio_read_string(base, 6, 2, &mut data);
+        assert_eq!(data, [0x04; 4]);
+        bridge.pio_read_string(base, 8, 2, &mut data);
+        assert_eq!(data, [0xff; 4]);
+
+        bridge.pio_write(base, 4, &[1]);
+        bridge.pio_write_string(base, 7, 1, &[2, 3]);
+        bridge.pio_write(base, 3, &[4]);
+        assert_eq!(
+            *bridge
+                .child()
+                .device(PioAddress(0x104))
+                .unwrap()
+                .1
+                .writes
+                .lock()
+                .unwrap(),
+            vec![
+                (0x104, 0, vec![1]),
+                (0x104, 3, vec![2]),
+                (0x104, 3, vec![3])
+            ]
+        );
+
+        assert!(!bridge.pio_poisoned());
+        let poisoned = LogDevice {
+            poisoned: true,
+            ..Default::default()
+        };
+        bridge
+            .register(PioRange::new(PioAddress(0x100), 4).unwrap(), poisoned)
+            .unwrap();
+        assert!(bridge.pio_poisoned());
+    }
+}
@@ -3,7 +3,7 @@
 
 use std::cmp::Ordering;
 use std::convert::TryFrom;
-use std::ops::{Add, Sub};
+use std::ops::{Add, BitAnd, Sub};
 
 /// This trait defines the operations we expect to apply to bus address values.
 pub trait BusAddress:
@@ -15,6 +15,7 @@ pub trait BusAddress:
 {
     /// Defines the underlying value type of the `BusAddress`.
     type V: Add<Output = Self::V>
+        + BitAnd<Output = Self::V>
         + Copy
         + From<u8>
         + PartialEq
@@ -35,6 +36,7 @@ pub type MmioAddressOffset = u64;
 
 /// Represents a MMIO address.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MmioAddress(pub MmioAddressOffset);
 
 /// Represents a PIO address offset.
@@ -42,6 +44,7 @@ pub type PioAddressOffset = u16;
 
 /// Represents a PIO address.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PioAddress(pub PioAddressOffset);
 
 // Implementing `BusAddress` and its prerequisites for `MmioAddress`.
@@ -56,7 +59,7 @@ impl Eq for MmioAddress {}
 
 impl PartialOrd for MmioAddress {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.0.partial_cmp(&other.0)
+        Some(self.cmp(other))
     }
 }
 
@@ -106,7 +109,7 @@ impl Eq for PioAddress {}
 
 impl PartialOrd for PioAddress {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.0.partial_cmp(&other.0)
+        Some(self.cmp(other))
     }
 }
 
@@ -172,7 +175,7 @@ mod tests {
 
     #[test]
     fn test_address_ops() {
-        check_bus_address_ops(MmioAddress(0), std::u64::MAX);
-        check_bus_address_ops(PioAddress(0), std::u16::MAX);
+        check_bus_address_ops(MmioAddress(0), u64::MAX);
+        check_bus_address_ops(PioAddress(0), u16::MAX);
     }
 }
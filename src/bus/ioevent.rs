@@ -0,0 +1,162 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+use std::sync::Arc;
+
+use crate::bus::{BusAddress, Error, MmioAddress, PioAddress};
+
+/// Object signaled when a write matches an [`IoEvent`] (for example an eventfd).
+pub trait Notifier {
+    /// Signal the notifier.
+    fn notify(&self);
+}
+
+impl<F: Fn()> Notifier for F {
+    fn notify(&self) {
+        self()
+    }
+}
+
+/// Defines what happens with a write that triggers a notifier.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NotifyMode {
+    /// The notifier is signaled, then the write is dispatched to the device as usual.
+    BeforeDispatch,
+    /// The notifier is signaled and the write is considered handled, so no device is
+    /// required at that address. The write is not dispatched to a device, so it does not
+    /// show up in the access statistics or traces of the range it falls in.
+    InsteadOfDispatch,
+}
+
+/// Selects the writes which trigger a notifier, similar to KVM ioeventfds.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct IoEvent<A: BusAddress> {
+    addr: A,
+    len: Option<usize>,
+    datamatch: Option<u64>,
+}
+
+impl<A: BusAddress> IoEvent<A> {
+    /// Create a new `IoEvent` matching writes to `addr`.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr`: address the write has to start at
+    /// * `len`: length the write has to have (1, 2, 4 or 8), or `None` to match any length
+    /// * `datamatch`: value the written data has to be equal to, interpreted as a little
+    ///   endian integer of `len` bytes; requires `len` to be specified
+    pub fn new(addr: A, len: Option<usize>, datamatch: Option<u64>) -> Result<Self, Error> {
+        match len {
+            Some(1) | Some(2) | Some(4) | Some(8) => {}
+            Some(_) => return Err(Error::InvalidIoEvent),
+            None if datamatch.is_some() => return Err(Error::InvalidIoEvent),
+            None => {}
+        }
+        // `datamatch` must fit within `len` bytes.
+        if let (Some(len), Some(value)) = (len, datamatch) {
+            if len < 8 && value >> (len * 8) != 0 {
+                return Err(Error::InvalidIoEvent);
+            }
+        }
+
+        Ok(IoEvent {
+            addr,
+            len,
+            datamatch,
+        })
+    }
+
+    /// Return the address matched by this event.
+    pub fn addr(&self) -> A {
+        self.addr
+    }
+
+    /// Return the write length matched by this event, if any.
+    pub fn access_len(&self) -> Option<usize> {
+        self.len
+    }
+
+    /// Return the value matched by this event, if any.
+    pub fn datamatch(&self) -> Option<u64> {
+        self.datamatch
+    }
+
+    /// Check whether a write of `data` at `addr` matches the event.
+    pub fn matches(&self, addr: A, data: &[u8]) -> bool {
+        if addr != self.addr {
+            return false;
+        }
+        if let Some(len) = self.len {
+            if data.len() != len {
+                return false;
+            }
+        }
+        match self.datamatch {
+            Some(value) => {
+                let mut bytes = [0u8; 8];
+                bytes[..data.len()].copy_from_slice(data);
+                u64::from_le_bytes(bytes) == value
+            }
+            None => true,
+        }
+    }
+}
+
+/// Represents an event on the MMIO bus.
+pub type MmioIoEvent = IoEvent<MmioAddress>;
+/// Represents an event on the PIO bus.
+pub type PioIoEvent = IoEvent<PioAddress>;
+
+// An event registered with a bus, together with the notifier it triggers.
+pub(crate) struct IoEventEntry<A: BusAddress> {
+    pub(crate) event: IoEvent<A>,
+    pub(crate) mode: NotifyMode,
+    pub(crate) notifier: Arc<dyn Notifier + Send + Sync>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ioevent() {
+        let addr = MmioAddress(0x1000);
+
+        assert_eq!(
+            IoEvent::new(addr, Some(3), None),
+            Err(Error::InvalidIoEvent)
+        );
+        assert_eq!(
+            IoEvent::new(addr, Some(16), None),
+            Err(Error::InvalidIoEvent)
+        );
+        assert_eq!(
+            IoEvent::new(addr, None, Some(1)),
+            Err(Error::InvalidIoEvent)
+        );
+        assert_eq!(
+            IoEvent::new(addr, Some(1), Some(0x100)),
+            Err(Error::InvalidIoEvent)
+        );
+
+        let any = IoEvent::new(addr, None, None).unwrap();
+        assert!(any.matches(addr, &[1]));
+        assert!(any.matches(addr, &[1, 2, 3, 4]));
+        assert!(!any.matches(MmioAddress(0x1001), &[1]));
+
+        let sized = IoEvent::new(addr, Some(2), None).unwrap();
+        assert_eq!(sized.access_len(), Some(2));
+        assert!(sized.matches(addr, &[1, 2]));
+        assert!(!sized.matches(addr, &[1]));
+
+        let value = IoEvent::new(addr, Some(4), Some(0x1234)).unwrap();
+        assert_eq!(value.addr(), addr);
+        assert_eq!(value.datamatch(), Some(0x1234));
+        assert!(value.matches(addr, &[0x34, 0x12, 0, 0]));
+        assert!(!value.matches(addr, &[0x12, 0x34, 0, 0]));
+        assert!(!value.matches(addr, &[0x34, 0x12]));
+
+        let full = IoEvent::new(addr, Some(8), Some(u64::MAX)).unwrap();
+        assert!(full.matches(addr, &[0xff; 8]));
+    }
+}
@@ -9,20 +9,31 @@
 //! regardless with their device associations.
 
 mod address;
+mod ioevent;
+mod permissions;
 mod range;
+mod stats;
 
-use std::collections::BTreeMap;
+use std::collections::{btree_map, BTreeMap};
 use std::convert::TryFrom;
 use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::ops::Bound;
 use std::result::Result;
+use std::sync::Arc;
 
-use address::BusAddress;
+pub(crate) use address::BusAddress;
+use ioevent::IoEventEntry;
+use stats::AccessCounters;
 
 pub use address::{MmioAddress, MmioAddressOffset, PioAddress, PioAddressOffset};
+pub use ioevent::{IoEvent, MmioIoEvent, Notifier, NotifyMode, PioIoEvent};
+pub use permissions::{DeniedAccess, Permissions};
 pub use range::{BusRange, MmioRange, PioRange};
+pub use stats::AccessStats;
 
 /// Errors encountered during bus operations.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Error {
     /// No device is associated with the specified address or range.
     DeviceNotFound,
@@ -32,6 +43,17 @@ pub enum Error {
     InvalidAccessLength(usize),
     /// Invalid range provided (either zero-sized, or last address overflows).
     InvalidRange,
+    /// Invalid combination of length and data match provided for an I/O event.
+    InvalidIoEvent,
+    /// An identical I/O event is already registered.
+    IoEventExists,
+    /// The device is poisoned and the bus is configured to fail accesses to it.
+    DevicePoisoned,
+    /// The permissions of the range forbid the access, and are configured to fail it with
+    /// [`DeniedAccess::Fail`].
+    AccessDenied,
+    /// Specified range overlaps a range reserved for the platform.
+    RangeReserved,
 }
 
 impl Display for Error {
@@ -41,21 +63,128 @@ impl Display for Error {
             Error::DeviceOverlap => write!(f, "range overlaps with existing device"),
             Error::InvalidAccessLength(len) => write!(f, "invalid access length ({})", len),
             Error::InvalidRange => write!(f, "invalid range provided"),
+            Error::InvalidIoEvent => write!(f, "invalid I/O event provided"),
+            Error::IoEventExists => write!(f, "I/O event already registered"),
+            Error::DevicePoisoned => write!(f, "device is poisoned"),
+            Error::AccessDenied => write!(f, "access denied by the range permissions"),
+            Error::RangeReserved => write!(f, "range overlaps with reserved range"),
         }
     }
 }
 
 impl std::error::Error for Error {}
 
+/// The direction of an access.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IoDirection {
+    /// The guest read from the device.
+    Read,
+    /// The guest wrote to the device.
+    Write,
+}
+
+/// Defines how accesses to a poisoned device are handled. A device is poisoned when a
+/// previous access panicked while holding its lock, for example on another vCPU thread.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PoisonPolicy {
+    /// Recover the device state and dispatch the access as usual.
+    #[default]
+    Recover,
+    /// Fail the access with [`Error::DevicePoisoned`] without reaching the device.
+    Fail,
+    /// Panic, which is the behavior of a plain `lock().unwrap()`.
+    Panic,
+}
+
+impl PoisonPolicy {
+    /// Apply the policy to an access targeting a device, where `poisoned` tells whether
+    /// the device is poisoned. Returns whether the access can proceed.
+    pub fn check(self, poisoned: bool) -> Result<(), Error> {
+        match self {
+            _ if !poisoned => Ok(()),
+            PoisonPolicy::Recover => Ok(()),
+            PoisonPolicy::Fail => Err(Error::DevicePoisoned),
+            PoisonPolicy::Panic => panic!("access to a poisoned device"),
+        }
+    }
+}
+
+/// Where an access dispatched by [`Bus::dispatch_access`] goes.
+pub enum Dispatch<'a, A: BusAddress, D> {
+    /// The access must be handed to the device registered with the range.
+    Device(&'a BusRange<A>, &'a D),
+    /// The permissions of the range forbid the access, and are configured to complete it
+    /// with [`DeniedAccess::Ignore`]: writes are dropped, and reads return the value in
+    /// every byte.
+    Ignored(u8),
+}
+
+// Implemented by hand, as deriving would not bound the size type of the addresses.
+impl<A: BusAddress, D: std::fmt::Debug> std::fmt::Debug for Dispatch<'_, A, D>
+where
+    BusRange<A>: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Dispatch::Device(range, device) => {
+                f.debug_tuple("Device").field(range).field(device).finish()
+            }
+            Dispatch::Ignored(value) => f.debug_tuple("Ignored").field(value).finish(),
+        }
+    }
+}
+
+impl<A: BusAddress, D: PartialEq> PartialEq for Dispatch<'_, A, D> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Dispatch::Device(r1, d1), Dispatch::Device(r2, d2)) => r1 == r2 && d1 == d2,
+            (Dispatch::Ignored(v1), Dispatch::Ignored(v2)) => v1 == v2,
+            _ => false,
+        }
+    }
+}
+
+impl<A: BusAddress, D: Eq> Eq for Dispatch<'_, A, D> {}
+
+// An alias window, whose addresses are redirected to `target + ((addr - base) & mask)`,
+// where `base` is the start of the window. Without a mask the window mirrors `target`.
+struct Alias<A: BusAddress> {
+    target: A,
+    mask: Option<A::V>,
+    // The range of the device the window mirrors, when registered through
+    // `register_aliases`, so the window goes away together with the device.
+    owner: Option<BusRange<A>>,
+}
+
+// The state the bus keeps for each registered range.
+struct Entry<D> {
+    device: D,
+    // Only present while statistics are enabled for the bus.
+    stats: Option<AccessCounters>,
+    permissions: Permissions,
+}
+
 /// A bus that's agnostic to the range address type and device type.
 pub struct Bus<A: BusAddress, D> {
-    devices: BTreeMap<BusRange<A>, D>,
+    devices: BTreeMap<BusRange<A>, Entry<D>>,
+    stats_enabled: bool,
+    // Registered I/O events, indexed by the address they match.
+    ioevents: BTreeMap<A, Vec<IoEventEntry<A>>>,
+    poison_policy: PoisonPolicy,
+    aliases: BTreeMap<BusRange<A>, Alias<A>>,
+    // Ranges no device can be registered with.
+    reserved: Vec<BusRange<A>>,
 }
 
 impl<A: BusAddress, D> Default for Bus<A, D> {
     fn default() -> Self {
         Bus {
             devices: BTreeMap::new(),
+            stats_enabled: false,
+            ioevents: BTreeMap::new(),
+            poison_policy: PoisonPolicy::default(),
+            aliases: BTreeMap::new(),
+            reserved: Vec::new(),
         }
     }
 }
@@ -66,15 +195,20 @@ impl<A: BusAddress, D> Bus<A, D> {
         Self::default()
     }
 
+    fn entry(&self, addr: A) -> Option<(&BusRange<A>, &Entry<D>)> {
+        self.devices
+            .range(..=BusRange::unit(addr))
+            .nth_back(0)
+            .filter(|pair| pair.0.last() >= addr)
+    }
+
     /// Return the registered range and device associated with `addr`.
     pub fn device(&self, addr: A) -> Option<(&BusRange<A>, &D)> {
         // The range is returned as an optimization because the caller
         // might need both the device and its associated bus range.
         // The same goes for the device_mut() method.
-        self.devices
-            .range(..=BusRange::unit(addr))
-            .nth_back(0)
-            .filter(|pair| pair.0.last() >= addr)
+        self.entry(addr)
+            .map(|(range, entry)| (range, &entry.device))
     }
 
     /// Return the registered range and a mutable reference to the device
@@ -84,39 +218,450 @@ impl<A: BusAddress, D> Bus<A, D> {
             .range_mut(..=BusRange::unit(addr))
             .nth_back(0)
             .filter(|pair| pair.0.last() >= addr)
+            .map(|(range, entry)| (range, &mut entry.device))
+    }
+
+    /// Check whether `range` overlaps any of the registered ranges, including alias windows.
+    pub fn overlaps(&self, range: &BusRange<A>) -> bool {
+        self.devices
+            .keys()
+            .chain(self.aliases.keys())
+            .any(|r| range.overlaps(r))
+    }
+
+    /// Reserve `range` for the platform, so registering a device or an alias window
+    /// overlapping it fails with [`RangeReserved`](enum.Error.html#variant.RangeReserved).
+    ///
+    /// Devices already registered within the range are left in place, which allows the
+    /// platform to register the devices emulating the reserved blocks before reserving them.
+    pub fn reserve(&mut self, range: BusRange<A>) {
+        self.reserved.push(range);
+    }
+
+    /// Check whether `range` overlaps any of the reserved ranges.
+    pub fn is_reserved(&self, range: &BusRange<A>) -> bool {
+        self.reserved.iter().any(|r| range.overlaps(r))
+    }
+
+    // Check that `range` is free to be registered.
+    fn check_free(&self, range: &BusRange<A>) -> Result<(), Error> {
+        if self.overlaps(range) {
+            return Err(Error::DeviceOverlap);
+        }
+        if self.is_reserved(range) {
+            return Err(Error::RangeReserved);
+        }
+        Ok(())
+    }
+
+    /// Return an iterator over the registered ranges and their devices, in address order.
+    pub fn devices(&self) -> impl Iterator<Item = (&BusRange<A>, &D)> {
+        self.devices
+            .iter()
+            .map(|(range, entry)| (range, &entry.device))
+    }
+
+    /// Return every range the bus dispatches accesses for, which are the registered ranges
+    /// and alias windows, in address order.
+    pub fn ranges(&self) -> Vec<BusRange<A>> {
+        let mut ranges: Vec<BusRange<A>> = self
+            .devices
+            .keys()
+            .chain(self.aliases.keys())
+            .copied()
+            .collect();
+        ranges.sort();
+        ranges
     }
 
     /// Register a device with the provided range.
     pub fn register(&mut self, range: BusRange<A>, device: D) -> Result<(), Error> {
-        for r in self.devices.keys() {
-            if range.overlaps(r) {
+        self.register_with_permissions(range, device, Permissions::default())
+    }
+
+    /// Register a device with the provided range, only allowing the accesses permitted by
+    /// `permissions` to be dispatched to it.
+    pub fn register_with_permissions(
+        &mut self,
+        range: BusRange<A>,
+        device: D,
+        permissions: Permissions,
+    ) -> Result<(), Error> {
+        self.check_free(&range)?;
+
+        let stats = if self.stats_enabled {
+            Some(AccessCounters::default())
+        } else {
+            None
+        };
+        self.devices.insert(
+            range,
+            Entry {
+                device,
+                stats,
+                permissions,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Deregister the device associated with `addr`, together with the alias windows
+    /// registered for it by [`register_aliases`](#method.register_aliases). Windows registered
+    /// with [`register_alias`](#method.register_alias) are left in place, as they may
+    /// redirect to other devices as well.
+    pub fn deregister(&mut self, addr: A) -> Option<(BusRange<A>, D)> {
+        let range = self.device(addr).map(|(range, _)| *range)?;
+        self.aliases.retain(|_, alias| alias.owner != Some(range));
+        self.devices
+            .remove(&range)
+            .map(|entry| (range, entry.device))
+    }
+
+    /// Register `device` with the `canonical` range, mirrored at each of the `aliases` base
+    /// addresses with the same size. Accesses to a mirror are dispatched as if they targeted
+    /// the matching address of the canonical range, so the device sees canonical offsets.
+    ///
+    /// Nothing gets registered if any of the ranges is invalid or overlaps an existing one.
+    pub fn register_aliases(
+        &mut self,
+        canonical: BusRange<A>,
+        aliases: &[A],
+        device: D,
+    ) -> Result<(), Error> {
+        let mut windows = Vec::with_capacity(aliases.len());
+        for base in aliases {
+            let window = BusRange::new(*base, canonical.size())?;
+            if window.overlaps(&canonical) || windows.iter().any(|w| window.overlaps(w)) {
                 return Err(Error::DeviceOverlap);
             }
+            self.check_free(&window)?;
+            windows.push(window);
         }
 
-        self.devices.insert(range, device);
+        self.register(canonical, device)?;
+        for window in windows {
+            self.aliases.insert(
+                window,
+                Alias {
+                    target: canonical.base(),
+                    mask: None,
+                    owner: Some(canonical),
+                },
+            );
+        }
+        Ok(())
+    }
 
+    /// Register an alias window redirecting an access at `addr` within `window` to
+    /// `target + ((addr - window.base()) & mask)`, the way chipsets decoding only some of
+    /// the address bits mirror devices. Without a `mask`, `window` simply mirrors the range
+    /// of the same size starting at `target`.
+    ///
+    /// The window must not overlap any registered range or window; the addresses it
+    /// redirects to are resolved when accesses are dispatched.
+    pub fn register_alias(
+        &mut self,
+        window: BusRange<A>,
+        target: A,
+        mask: Option<A::V>,
+    ) -> Result<(), Error> {
+        self.check_free(&window)?;
+        self.aliases.insert(
+            window,
+            Alias {
+                target,
+                mask,
+                owner: None,
+            },
+        );
         Ok(())
     }
 
-    /// Deregister the device associated with `addr`.
-    pub fn deregister(&mut self, addr: A) -> Option<(BusRange<A>, D)> {
-        let range = self.device(addr).map(|(range, _)| *range)?;
-        self.devices.remove(&range).map(|device| (range, device))
+    /// Deregister the alias window containing `addr`, returning its range.
+    pub fn deregister_alias(&mut self, addr: A) -> Option<BusRange<A>> {
+        let window = *self
+            .aliases
+            .range(..=BusRange::unit(addr))
+            .nth_back(0)
+            .filter(|(window, _)| window.last() >= addr)?
+            .0;
+        self.aliases.remove(&window);
+        Some(window)
+    }
+
+    /// Return the address an access at `addr` is redirected to by the alias windows, or
+    /// `addr` itself when it isn't part of any window. The device managers resolve every
+    /// address with this before dispatching the access.
+    pub fn canonical_addr(&self, addr: A) -> A {
+        if self.aliases.is_empty() {
+            return addr;
+        }
+        self.aliases
+            .range(..=BusRange::unit(addr))
+            .nth_back(0)
+            .filter(|(window, _)| window.last() >= addr)
+            .and_then(|(window, alias)| {
+                let offset = addr - window.base();
+                let offset = alias.mask.map_or(offset, |mask| offset & mask);
+                alias.target.checked_add(offset)
+            })
+            .unwrap_or(addr)
     }
 
     /// Verify whether an access starting at `addr` with length `len` fits within any of
     /// the registered ranges. Return the range and a handle to the device when present.
     pub fn check_access(&self, addr: A, len: usize) -> Result<(&BusRange<A>, &D), Error> {
+        Self::fit_access(self.entry(addr), addr, len)
+    }
+
+    /// Same as [`check_access`](#method.check_access), but also checks the permissions of the
+    /// range for `direction` and accounts the access in the statistics of the range containing
+    /// `addr` when statistics are enabled. This is what the device managers use when
+    /// dispatching I/O.
+    ///
+    /// Accesses forbidden by the permissions of the range either fail with
+    /// [`AccessDenied`](enum.Error.html#variant.AccessDenied) or resolve to
+    /// [`Dispatch::Ignored`], as configured with the range. Ignored accesses are counted
+    /// separately from the dispatched and failed ones.
+    pub fn dispatch_access(
+        &self,
+        addr: A,
+        len: usize,
+        direction: IoDirection,
+    ) -> Result<Dispatch<'_, A, D>, Error> {
+        Self::account_access(self.entry(addr), addr, len, 1, direction)
+    }
+
+    /// Same as [`dispatch_access`](#method.dispatch_access), for `count` consecutive accesses
+    /// of `len` bytes each at `addr`, as issued by string I/O instructions.
+    pub fn dispatch_repeated_access(
+        &self,
+        addr: A,
+        len: usize,
+        count: usize,
+        direction: IoDirection,
+    ) -> Result<Dispatch<'_, A, D>, Error> {
+        Self::account_access(self.entry(addr), addr, len, count, direction)
+    }
+
+    /// Return a cursor for dispatching several accesses in a row.
+    pub fn cursor(&self) -> BusCursor<'_, A, D> {
+        BusCursor {
+            bus: self,
+            current: None,
+            next: self.devices.range(..).peekable(),
+            last_addr: None,
+        }
+    }
+
+    // Resolve `count` accesses of `len` bytes at `addr`, and account them in the statistics
+    // of `entry` when enabled.
+    fn account_access<'a>(
+        entry: Option<(&'a BusRange<A>, &'a Entry<D>)>,
+        addr: A,
+        len: usize,
+        count: usize,
+        direction: IoDirection,
+    ) -> Result<Dispatch<'a, A, D>, Error> {
+        let result = Self::fit_permitted_access(entry, addr, len, direction);
+        if let Some(stats) = entry.and_then(|(_, entry)| entry.stats.as_ref()) {
+            match result {
+                Ok(Dispatch::Device(..)) => stats.record_repeated(direction, len, count, true),
+                Ok(Dispatch::Ignored(_)) => stats.record_ignored(count),
+                Err(_) => stats.record_repeated(direction, len, count, false),
+            }
+        }
+        result
+    }
+
+    fn fit_permitted_access<'a>(
+        entry: Option<(&'a BusRange<A>, &'a Entry<D>)>,
+        addr: A,
+        len: usize,
+        direction: IoDirection,
+    ) -> Result<Dispatch<'a, A, D>, Error> {
+        let (range, device) = Self::fit_access(entry, addr, len)?;
+        match entry {
+            Some((_, entry)) if !entry.permissions.allows(direction) => {
+                match entry.permissions.denied() {
+                    DeniedAccess::Fail => Err(Error::AccessDenied),
+                    DeniedAccess::Ignore(value) => Ok(Dispatch::Ignored(value)),
+                }
+            }
+            _ => Ok(Dispatch::Device(range, device)),
+        }
+    }
+
+    fn fit_access<'a>(
+        entry: Option<(&'a BusRange<A>, &'a Entry<D>)>,
+        addr: A,
+        len: usize,
+    ) -> Result<(&'a BusRange<A>, &'a D), Error> {
         let access_range = BusRange::new(
             addr,
             A::V::try_from(len).map_err(|_| Error::InvalidAccessLength(len))?,
         )
         .map_err(|_| Error::InvalidRange)?;
-        self.device(addr)
+        entry
             .filter(|(range, _)| range.last() >= access_range.last())
+            .map(|(range, entry)| (range, &entry.device))
             .ok_or(Error::DeviceNotFound)
     }
+
+    /// Set how the device managers handle accesses to poisoned devices registered with
+    /// this bus. The default is [`PoisonPolicy::Recover`].
+    pub fn set_poison_policy(&mut self, policy: PoisonPolicy) {
+        self.poison_policy = policy;
+    }
+
+    /// Return the policy applied to accesses to poisoned devices.
+    pub fn poison_policy(&self) -> PoisonPolicy {
+        self.poison_policy
+    }
+
+    /// Enable or disable the collection of per-range access statistics.
+    ///
+    /// Enabling statistics starts the counters of all ranges from zero, while disabling them
+    /// discards the collected values.
+    pub fn set_stats_enabled(&mut self, enabled: bool) {
+        self.stats_enabled = enabled;
+        for entry in self.devices.values_mut() {
+            entry.stats = if enabled {
+                Some(AccessCounters::default())
+            } else {
+                None
+            };
+        }
+    }
+
+    /// Return a snapshot of the access statistics of each registered range, or an empty
+    /// vector when statistics are disabled.
+    pub fn stats(&self) -> Vec<(BusRange<A>, AccessStats)> {
+        self.devices
+            .iter()
+            .filter_map(|(range, entry)| entry.stats.as_ref().map(|s| (*range, s.snapshot())))
+            .collect()
+    }
+
+    /// Reset the access statistics of all registered ranges.
+    pub fn reset_stats(&self) {
+        for stats in self
+            .devices
+            .values()
+            .filter_map(|entry| entry.stats.as_ref())
+        {
+            stats.reset();
+        }
+    }
+
+    /// Register `notifier` to be signaled on writes matching `event`.
+    ///
+    /// I/O events are independent of the registered devices, so they can be placed inside
+    /// device ranges as well as at addresses where no device is present.
+    pub fn register_ioevent(
+        &mut self,
+        event: IoEvent<A>,
+        mode: NotifyMode,
+        notifier: Arc<dyn Notifier + Send + Sync>,
+    ) -> Result<(), Error> {
+        let entries = self.ioevents.entry(event.addr()).or_default();
+        if entries.iter().any(|entry| entry.event == event) {
+            return Err(Error::IoEventExists);
+        }
+        entries.push(IoEventEntry {
+            event,
+            mode,
+            notifier,
+        });
+        Ok(())
+    }
+
+    /// Deregister the notifier associated with `event`.
+    pub fn deregister_ioevent(
+        &mut self,
+        event: &IoEvent<A>,
+    ) -> Option<Arc<dyn Notifier + Send + Sync>> {
+        let entries = self.ioevents.get_mut(&event.addr())?;
+        let index = entries.iter().position(|entry| entry.event == *event)?;
+        let entry = entries.remove(index);
+        if entries.is_empty() {
+            self.ioevents.remove(&event.addr());
+        }
+        Some(entry.notifier)
+    }
+
+    /// Check whether any I/O event is registered at `addr`.
+    pub fn has_ioevents(&self, addr: A) -> bool {
+        self.ioevents.contains_key(&addr)
+    }
+
+    /// Signal the notifiers of all I/O events matching a write of `data` at `addr`.
+    ///
+    /// Returns `true` when one of the matching events was registered with
+    /// [`NotifyMode::InsteadOfDispatch`], in which case the write must not be dispatched
+    /// to the device.
+    pub fn notify_write(&self, addr: A, data: &[u8]) -> bool {
+        let mut handled = false;
+        if let Some(entries) = self.ioevents.get(&addr) {
+            for entry in entries
+                .iter()
+                .filter(|entry| entry.event.matches(addr, data))
+            {
+                entry.notifier.notify();
+                handled |= entry.mode == NotifyMode::InsteadOfDispatch;
+            }
+        }
+        handled
+    }
+}
+
+/// Dispatches consecutive accesses on a [`Bus`].
+///
+/// As long as the accesses come in ascending address order, the cursor resolves them with
+/// a single walk over the registered ranges instead of a full lookup per access. Accesses
+/// going backwards are still supported, at the cost of a new lookup.
+pub struct BusCursor<'a, A: BusAddress, D> {
+    bus: &'a Bus<A, D>,
+    // The last range starting at or below `last_addr`.
+    current: Option<(&'a BusRange<A>, &'a Entry<D>)>,
+    // The ranges following `current`.
+    next: Peekable<btree_map::Range<'a, BusRange<A>, Entry<D>>>,
+    last_addr: Option<A>,
+}
+
+impl<'a, A: BusAddress, D> BusCursor<'a, A, D> {
+    /// Same as [`Bus::dispatch_access`].
+    pub fn dispatch_access(
+        &mut self,
+        addr: A,
+        len: usize,
+        direction: IoDirection,
+    ) -> Result<Dispatch<'a, A, D>, Error> {
+        let entry = self.seek(addr);
+        Bus::account_access(entry, addr, len, 1, direction)
+    }
+
+    fn seek(&mut self, addr: A) -> Option<(&'a BusRange<A>, &'a Entry<D>)> {
+        if !matches!(self.last_addr, Some(last) if last <= addr) {
+            let unit = BusRange::unit(addr);
+            self.current = self.bus.devices.range(..=unit).nth_back(0);
+            self.next = self
+                .bus
+                .devices
+                .range((Bound::Excluded(unit), Bound::Unbounded))
+                .peekable();
+        } else {
+            while let Some((range, _)) = self.next.peek() {
+                if range.base() > addr {
+                    break;
+                }
+                self.current = self.next.next();
+            }
+        }
+        self.last_addr = Some(addr);
+        self.current.filter(|(range, _)| range.last() >= addr)
+    }
 }
 
 /// Represents an MMIO bus.
@@ -134,6 +679,34 @@ pub trait BusManager<A: BusAddress> {
 
     /// Return a mutable reference to the bus.
     fn bus_mut(&mut self) -> &mut Bus<A, Self::D>;
+
+    /// Check that `range` may be registered, besides being free on the bus, failing with the
+    /// error the registration should fail with otherwise. The registration methods of the
+    /// device manager traits call this for every new device; the default implementation
+    /// accepts any range.
+    fn check_range(&self, _range: &BusRange<A>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Release any state kept about `device`, which was just deregistered from `range`.
+    /// The deregistration methods of the device manager traits call this for every removed
+    /// device; the default implementation does nothing.
+    fn release_device(&mut self, _range: BusRange<A>, _device: &Self::D) {}
+
+    /// Observe an access once it completes, whether it reached a device or not. The dispatch
+    /// methods of the device manager traits call this for every access, with the address
+    /// `addr` used by the guest, the `offset` of the access within the range of the device
+    /// it reached, if any, the `data` written or read and the `result` of the access. String
+    /// accesses are observed one element at a time. The default implementation does nothing.
+    fn observe_access(
+        &self,
+        _addr: A,
+        _offset: Option<A::V>,
+        _direction: IoDirection,
+        _data: &[u8],
+        _result: &Result<(), Error>,
+    ) {
+    }
 }
 
 #[cfg(test)]
@@ -191,6 +764,8 @@ mod test {
 
         // Detect double registration with the same range.
         assert_eq!(bus.register(range, device), Err(Error::DeviceOverlap));
+        assert!(bus.overlaps(&range));
+        assert!(!bus.overlaps(&MmioRange::new(base_prev, 1).unwrap()));
 
         // We detect overlaps even if it's another range associated with the same device (we don't
         // implicitly merge ranges). `check_access` fails if the specified range does not fully
@@ -277,4 +852,364 @@ mod test {
             Err(Error::InvalidAccessLength(usize::MAX))
         );
     }
+
+    #[test]
+    fn test_bus_stats() {
+        let range = MmioRange::new(MmioAddress(0x1000), 0x10).unwrap();
+        let range2 = MmioRange::new(MmioAddress(0x2000), 0x10).unwrap();
+        let mut bus = Bus::new();
+        bus.register(range, 1u8).unwrap();
+
+        // Statistics are disabled by default.
+        assert!(bus
+            .dispatch_access(MmioAddress(0x1000), 4, IoDirection::Read)
+            .is_ok());
+        assert!(bus.stats().is_empty());
+
+        bus.set_stats_enabled(true);
+        bus.register(range2, 2u8).unwrap();
+
+        assert_eq!(
+            bus.dispatch_access(MmioAddress(0x1004), 4, IoDirection::Read),
+            Ok(Dispatch::Device(&range, &1))
+        );
+        bus.dispatch_access(MmioAddress(0x1008), 8, IoDirection::Write)
+            .unwrap();
+        bus.dispatch_access(MmioAddress(0x2000), 2, IoDirection::Write)
+            .unwrap();
+        // The access starts within `range` but doesn't fit.
+        assert_eq!(
+            bus.dispatch_access(MmioAddress(0x100c), 8, IoDirection::Read),
+            Err(Error::DeviceNotFound)
+        );
+        // Failures outside of any registered range are not accounted.
+        assert_eq!(
+            bus.dispatch_access(MmioAddress(0x3000), 1, IoDirection::Read),
+            Err(Error::DeviceNotFound)
+        );
+
+        // `check_access` never updates the statistics.
+        assert!(bus.check_access(MmioAddress(0x1000), 1).is_ok());
+
+        let stats = bus.stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].0, range);
+        assert_eq!(
+            stats[0].1,
+            AccessStats {
+                reads: 1,
+                writes: 1,
+                bytes_read: 4,
+                bytes_written: 8,
+                failed: 1,
+                ignored: 0,
+            }
+        );
+        assert_eq!(stats[1].0, range2);
+        assert_eq!(stats[1].1.writes, 1);
+        assert_eq!(stats[1].1.bytes_written, 2);
+
+        bus.dispatch_repeated_access(MmioAddress(0x2002), 2, 4, IoDirection::Read)
+            .unwrap();
+        assert_eq!(bus.stats()[1].1.reads, 4);
+        assert_eq!(bus.stats()[1].1.bytes_read, 8);
+        assert_eq!(
+            bus.dispatch_repeated_access(MmioAddress(0x200f), 2, 4, IoDirection::Read),
+            Err(Error::DeviceNotFound)
+        );
+        assert_eq!(bus.stats()[1].1.failed, 1);
+
+        bus.reset_stats();
+        assert!(bus
+            .stats()
+            .iter()
+            .all(|(_, s)| *s == AccessStats::default()));
+
+        // Statistics go away together with the range.
+        bus.deregister(range2.base()).unwrap();
+        assert_eq!(bus.stats().len(), 1);
+
+        bus.set_stats_enabled(false);
+        assert!(bus.stats().is_empty());
+    }
+
+    #[test]
+    fn test_bus_permissions() {
+        let mut bus = Bus::new();
+        bus.set_stats_enabled(true);
+        let ignored = MmioRange::new(MmioAddress(0x1000), 0x10).unwrap();
+        let failed = MmioRange::new(MmioAddress(0x2000), 0x10).unwrap();
+        bus.register_with_permissions(
+            ignored,
+            1u8,
+            Permissions::read_only(DeniedAccess::Ignore(0)),
+        )
+        .unwrap();
+        bus.register_with_permissions(failed, 2u8, Permissions::read_only(DeniedAccess::Fail))
+            .unwrap();
+
+        assert_eq!(
+            bus.dispatch_access(MmioAddress(0x1000), 4, IoDirection::Read),
+            Ok(Dispatch::Device(&ignored, &1))
+        );
+        assert_eq!(
+            bus.dispatch_access(MmioAddress(0x1000), 4, IoDirection::Write),
+            Ok(Dispatch::Ignored(0))
+        );
+        assert_eq!(
+            bus.dispatch_repeated_access(MmioAddress(0x1004), 2, 3, IoDirection::Write),
+            Ok(Dispatch::Ignored(0))
+        );
+        assert_eq!(
+            bus.dispatch_access(MmioAddress(0x2000), 4, IoDirection::Write),
+            Err(Error::AccessDenied)
+        );
+
+        // Ignored accesses are neither dispatched nor failed.
+        let stats = bus.stats();
+        assert_eq!(
+            stats[0].1,
+            AccessStats {
+                reads: 1,
+                bytes_read: 4,
+                ignored: 4,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            stats[1].1,
+            AccessStats {
+                failed: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_bus_aliases() {
+        let mut bus = Bus::new();
+        let canonical = PioRange::new(PioAddress(0x60), 4).unwrap();
+        let other = PioRange::new(PioAddress(0x80), 4).unwrap();
+        bus.register(other, 2u8).unwrap();
+
+        // Invalid mirrors leave the bus untouched.
+        assert_eq!(
+            bus.register_aliases(canonical, &[PioAddress(0x62)], 1u8),
+            Err(Error::DeviceOverlap)
+        );
+        assert_eq!(
+            bus.register_aliases(canonical, &[PioAddress(0x100), PioAddress(0x102)], 1u8),
+            Err(Error::DeviceOverlap)
+        );
+        assert_eq!(
+            bus.register_aliases(canonical, &[PioAddress(0x7e)], 1u8),
+            Err(Error::DeviceOverlap)
+        );
+        assert_eq!(
+            bus.register_aliases(canonical, &[PioAddress(u16::MAX)], 1u8),
+            Err(Error::InvalidRange)
+        );
+        assert!(bus.device(PioAddress(0x60)).is_none());
+
+        bus.register_aliases(canonical, &[PioAddress(0x160), PioAddress(0x260)], 1u8)
+            .unwrap();
+        assert_eq!(bus.canonical_addr(PioAddress(0x163)), PioAddress(0x63));
+        assert_eq!(bus.canonical_addr(PioAddress(0x261)), PioAddress(0x61));
+        assert_eq!(bus.canonical_addr(PioAddress(0x164)), PioAddress(0x164));
+        assert!(bus.overlaps(&PioRange::new(PioAddress(0x15f), 2).unwrap()));
+        assert_eq!(
+            bus.ranges(),
+            vec![
+                canonical,
+                other,
+                PioRange::new(PioAddress(0x160), 4).unwrap(),
+                PioRange::new(PioAddress(0x260), 4).unwrap()
+            ]
+        );
+        assert_eq!(
+            bus.register(PioRange::new(PioAddress(0x262), 1).unwrap(), 3u8),
+            Err(Error::DeviceOverlap)
+        );
+
+        // A chipset decoding 10 address bits mirrors the other device every 0x400 bytes.
+        let window = PioRange::new(PioAddress(0x200), 0x400).unwrap();
+        assert_eq!(
+            bus.register_alias(window, PioAddress(0x60), None),
+            Err(Error::DeviceOverlap)
+        );
+        let window = PioRange::new(PioAddress(0x400), 0x400).unwrap();
+        bus.register_alias(window, PioAddress(0), Some(0x3ff))
+            .unwrap();
+        assert_eq!(bus.canonical_addr(PioAddress(0x482)), PioAddress(0x82));
+        assert_eq!(
+            bus.dispatch_access(bus.canonical_addr(PioAddress(0x480)), 4, IoDirection::Read),
+            Ok(Dispatch::Device(&other, &2))
+        );
+        assert_eq!(bus.deregister_alias(PioAddress(0x7ff)), Some(window));
+        assert_eq!(bus.deregister_alias(PioAddress(0x7ff)), None);
+        assert_eq!(bus.canonical_addr(PioAddress(0x482)), PioAddress(0x482));
+
+        // Mirrors go away together with their device.
+        assert_eq!(bus.deregister(PioAddress(0x61)), Some((canonical, 1)));
+        assert_eq!(bus.canonical_addr(PioAddress(0x163)), PioAddress(0x163));
+        bus.register(PioRange::new(PioAddress(0x160), 1).unwrap(), 3u8)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_bus_alias_window_shared() {
+        let mut bus = Bus::new();
+        let first = PioRange::new(PioAddress(0), 4).unwrap();
+        let second = PioRange::new(PioAddress(0x80), 4).unwrap();
+        bus.register(first, 1u8).unwrap();
+        bus.register(second, 2u8).unwrap();
+        // A single window mirrors both devices.
+        let window = PioRange::new(PioAddress(0x400), 0x400).unwrap();
+        bus.register_alias(window, PioAddress(0), Some(0x3ff))
+            .unwrap();
+
+        // Deregistering the device at the target of the window keeps the window, which
+        // still mirrors the other device.
+        assert_eq!(bus.deregister(PioAddress(0)), Some((first, 1)));
+        assert_eq!(bus.ranges(), vec![second, window]);
+        assert_eq!(
+            bus.dispatch_access(bus.canonical_addr(PioAddress(0x481)), 1, IoDirection::Read),
+            Ok(Dispatch::Device(&second, &2))
+        );
+        assert_eq!(bus.deregister(PioAddress(0x80)), Some((second, 2)));
+        assert_eq!(bus.ranges(), vec![window]);
+    }
+
+    #[test]
+    fn test_bus_reserved() {
+        let mut bus = Bus::new();
+        let ioapic = MmioRange::new(MmioAddress(0xfec0_0000), 0x1000).unwrap();
+        bus.register(ioapic, 1u8).unwrap();
+        bus.reserve(MmioRange::new(MmioAddress(0xfec0_0000), 0x10_0000).unwrap());
+        bus.reserve(MmioRange::new(MmioAddress(0xfee0_0000), 0x10_0000).unwrap());
+
+        // Devices registered before the reservation are kept.
+        assert!(bus.device(MmioAddress(0xfec0_0000)).is_some());
+        assert!(bus.is_reserved(&MmioRange::new(MmioAddress(0xfeef_ffff), 1).unwrap()));
+        assert!(!bus.is_reserved(&MmioRange::new(MmioAddress(0xfef0_0000), 0x1000).unwrap()));
+
+        assert_eq!(bus.register(ioapic, 2u8), Err(Error::DeviceOverlap));
+        assert_eq!(
+            bus.register(
+                MmioRange::new(MmioAddress(0xfec0_1000), 0x1000).unwrap(),
+                2u8
+            ),
+            Err(Error::RangeReserved)
+        );
+        assert_eq!(
+            bus.register(
+                MmioRange::new(MmioAddress(0xfedf_f000), 0x2000).unwrap(),
+                2u8
+            ),
+            Err(Error::RangeReserved)
+        );
+        let canonical = MmioRange::new(MmioAddress(0xd000_0000), 0x1000).unwrap();
+        assert_eq!(
+            bus.register_aliases(canonical, &[MmioAddress(0xfee0_0000)], 2u8),
+            Err(Error::RangeReserved)
+        );
+        assert!(bus.device(MmioAddress(0xd000_0000)).is_none());
+        assert_eq!(
+            bus.register_alias(
+                MmioRange::new(MmioAddress(0xfee0_0000), 0x1000).unwrap(),
+                MmioAddress(0xd000_0000),
+                None
+            ),
+            Err(Error::RangeReserved)
+        );
+
+        bus.register(
+            MmioRange::new(MmioAddress(0xfef0_0000), 0x1000).unwrap(),
+            2u8,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_bus_ioevents() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut bus: PioBus<u8> = Bus::new();
+        let count = Arc::new(AtomicUsize::new(0));
+        let count2 = count.clone();
+        let notifier: Arc<dyn Notifier + Send + Sync> = Arc::new(move || {
+            count2.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let any = PioIoEvent::new(PioAddress(0x10), None, None).unwrap();
+        let value = PioIoEvent::new(PioAddress(0x10), Some(2), Some(0x1)).unwrap();
+
+        assert!(!bus.notify_write(PioAddress(0x10), &[0x1, 0x0]));
+
+        bus.register_ioevent(any, NotifyMode::BeforeDispatch, notifier.clone())
+            .unwrap();
+        assert_eq!(
+            bus.register_ioevent(any, NotifyMode::InsteadOfDispatch, notifier.clone()),
+            Err(Error::IoEventExists)
+        );
+        bus.register_ioevent(value, NotifyMode::InsteadOfDispatch, notifier)
+            .unwrap();
+
+        // Only `any` matches.
+        assert!(!bus.notify_write(PioAddress(0x10), &[0x2, 0x0]));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(!bus.notify_write(PioAddress(0x11), &[0x1, 0x0]));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        // Both events match, and `value` takes over the write.
+        assert!(bus.notify_write(PioAddress(0x10), &[0x1, 0x0]));
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        assert!(bus.has_ioevents(PioAddress(0x10)));
+        assert!(!bus.has_ioevents(PioAddress(0x11)));
+        assert!(bus.deregister_ioevent(&value).is_some());
+        assert!(bus.deregister_ioevent(&value).is_none());
+        assert!(!bus.notify_write(PioAddress(0x10), &[0x1, 0x0]));
+        assert!(bus.deregister_ioevent(&any).is_some());
+        assert!(bus.ioevents.is_empty());
+    }
+
+    #[test]
+    fn test_bus_cursor() {
+        let mut bus = Bus::new();
+        for (i, base) in [0x10u64, 0x20, 0x40].iter().enumerate() {
+            bus.register(MmioRange::new(MmioAddress(*base), 0x10).unwrap(), i)
+                .unwrap();
+        }
+        bus.set_stats_enabled(true);
+
+        let mut cursor = bus.cursor();
+        let mut dispatch = |addr, len| {
+            cursor
+                .dispatch_access(MmioAddress(addr), len, IoDirection::Read)
+                .map(|dispatch| match dispatch {
+                    Dispatch::Device(range, device) => (range.base().0, *device),
+                    Dispatch::Ignored(_) => unreachable!(),
+                })
+        };
+
+        assert_eq!(dispatch(0x0, 1), Err(Error::DeviceNotFound));
+        assert_eq!(dispatch(0x10, 4), Ok((0x10, 0)));
+        assert_eq!(dispatch(0x1c, 4), Ok((0x10, 0)));
+        assert_eq!(dispatch(0x1e, 4), Err(Error::DeviceNotFound));
+        assert_eq!(dispatch(0x2f, 1), Ok((0x20, 1)));
+        assert_eq!(dispatch(0x30, 1), Err(Error::DeviceNotFound));
+        assert_eq!(dispatch(0x48, 8), Ok((0x40, 2)));
+        // Going backwards.
+        assert_eq!(dispatch(0x24, 4), Ok((0x20, 1)));
+        assert_eq!(dispatch(0x14, 4), Ok((0x10, 0)));
+        assert_eq!(dispatch(0x4c, 4), Ok((0x40, 2)));
+        assert_eq!(dispatch(0x50, 4), Err(Error::DeviceNotFound));
+
+        let stats = bus.stats();
+        assert_eq!(stats[0].1.reads, 3);
+        assert_eq!(stats[0].1.failed, 1);
+        assert_eq!(stats[1].1.reads, 2);
+        assert_eq!(stats[2].1.reads, 2);
+    }
 }
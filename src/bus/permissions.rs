@@ -0,0 +1,95 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+use crate::bus::IoDirection;
+
+/// Defines how an access in a direction forbidden by the [`Permissions`] of a range is
+/// handled. In both cases the access never reaches the device.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DeniedAccess {
+    /// The access fails with [`Error::AccessDenied`](enum.Error.html#variant.AccessDenied).
+    Fail,
+    /// The access completes successfully: writes are dropped and reads return the provided
+    /// value in every byte (typically `0x00` or `0xff`). The bus resolves such accesses to
+    /// [`Dispatch::Ignored`](enum.Dispatch.html#variant.Ignored).
+    Ignore(u8),
+}
+
+/// Access permissions of a bus range, such as read-only for ROM-like regions or write-only
+/// for doorbells.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Permissions {
+    read: bool,
+    write: bool,
+    denied: DeniedAccess,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Permissions {
+            read: true,
+            write: true,
+            denied: DeniedAccess::Fail,
+        }
+    }
+}
+
+impl Permissions {
+    /// Allow both reads and writes, which is what ranges are registered with by default.
+    pub fn read_write() -> Self {
+        Self::default()
+    }
+
+    /// Only allow reads; writes are handled according to `denied`.
+    pub fn read_only(denied: DeniedAccess) -> Self {
+        Permissions {
+            read: true,
+            write: false,
+            denied,
+        }
+    }
+
+    /// Only allow writes; reads are handled according to `denied`.
+    pub fn write_only(denied: DeniedAccess) -> Self {
+        Permissions {
+            read: false,
+            write: true,
+            denied,
+        }
+    }
+
+    /// Check whether accesses in `direction` are allowed.
+    pub fn allows(&self, direction: IoDirection) -> bool {
+        match direction {
+            IoDirection::Read => self.read,
+            IoDirection::Write => self.write,
+        }
+    }
+
+    /// Return how forbidden accesses are handled.
+    pub fn denied(&self) -> DeniedAccess {
+        self.denied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permissions() {
+        let rw = Permissions::read_write();
+        assert!(rw.allows(IoDirection::Read));
+        assert!(rw.allows(IoDirection::Write));
+        assert_eq!(rw.denied(), DeniedAccess::Fail);
+
+        let ro = Permissions::read_only(DeniedAccess::Ignore(0));
+        assert!(ro.allows(IoDirection::Read));
+        assert!(!ro.allows(IoDirection::Write));
+        assert_eq!(ro.denied(), DeniedAccess::Ignore(0));
+
+        let wo = Permissions::write_only(DeniedAccess::Ignore(0xff));
+        assert!(!wo.allows(IoDirection::Read));
+        assert!(wo.allows(IoDirection::Write));
+    }
+}
@@ -72,7 +72,7 @@ impl<A: BusAddress> Eq for BusRange<A> {}
 
 impl<A: BusAddress> PartialOrd for BusRange<A> {
     fn partial_cmp(&self, other: &BusRange<A>) -> Option<Ordering> {
-        self.base.partial_cmp(&other.base)
+        Some(self.cmp(other))
     }
 }
 
@@ -87,6 +87,43 @@ pub type MmioRange = BusRange<MmioAddress>;
 /// Represents a PIO bus range.
 pub type PioRange = BusRange<PioAddress>;
 
+// Ranges are serialized as their base and size, and validated by `BusRange::new` when they
+// are deserialized.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "BusRange")]
+struct SerdeBusRange<A, V> {
+    base: A,
+    size: V,
+}
+
+#[cfg(feature = "serde")]
+impl<A> serde::Serialize for BusRange<A>
+where
+    A: BusAddress + serde::Serialize,
+    A::V: serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerdeBusRange {
+            base: self.base,
+            size: self.size,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, A> serde::Deserialize<'de> for BusRange<A>
+where
+    A: BusAddress + serde::Deserialize<'de>,
+    A::V: serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let range = SerdeBusRange::<A, A::V>::deserialize(deserializer)?;
+        BusRange::new(range.base, range.size).map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,10 +135,10 @@ mod tests {
 
         assert_eq!(BusRange::new(base_zero, 0), Err(Error::InvalidRange));
 
-        assert!(BusRange::new(base_zero, std::u64::MAX).is_ok());
-        assert!(BusRange::new(MmioAddress(1), std::u64::MAX).is_ok());
+        assert!(BusRange::new(base_zero, u64::MAX).is_ok());
+        assert!(BusRange::new(MmioAddress(1), u64::MAX).is_ok());
         assert_eq!(
-            BusRange::new(MmioAddress(2), std::u64::MAX),
+            BusRange::new(MmioAddress(2), u64::MAX),
             Err(Error::InvalidRange)
         );
 
@@ -175,4 +212,23 @@ mod tests {
             }
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let range = PioRange::new(PioAddress(0x3f8), 8).unwrap();
+        let json = serde_json::to_string(&range).unwrap();
+        assert_eq!(json, r#"{"base":1016,"size":8}"#);
+        let other: PioRange = serde_json::from_str(&json).unwrap();
+        assert_eq!((other.base(), other.size()), (range.base(), range.size()));
+
+        let range = MmioRange::new(MmioAddress(u64::MAX), 1).unwrap();
+        let other: MmioRange =
+            serde_json::from_str(&serde_json::to_string(&range).unwrap()).unwrap();
+        assert_eq!((other.base(), other.size()), (range.base(), range.size()));
+
+        // Invalid ranges are rejected.
+        assert!(serde_json::from_str::<PioRange>(r#"{"base":0,"size":0}"#).is_err());
+        assert!(serde_json::from_str::<PioRange>(r#"{"base":65535,"size":2}"#).is_err());
+    }
 }
@@ -0,0 +1,127 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::bus::IoDirection;
+
+/// Snapshot of the accesses dispatched to a bus range.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct AccessStats {
+    /// Number of successfully dispatched reads.
+    pub reads: u64,
+    /// Number of successfully dispatched writes.
+    pub writes: u64,
+    /// Number of bytes read by successful accesses.
+    pub bytes_read: u64,
+    /// Number of bytes written by successful accesses.
+    pub bytes_written: u64,
+    /// Number of accesses starting within the range which could not be dispatched.
+    pub failed: u64,
+    /// Number of accesses forbidden by the permissions of the range which completed without
+    /// reaching the device.
+    pub ignored: u64,
+}
+
+// Counters are only updated with relaxed atomic operations, so they can be shared by vCPU
+// threads without locking. A snapshot is therefore not guaranteed to be consistent across
+// fields while accesses are in flight.
+#[derive(Default)]
+pub(crate) struct AccessCounters {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    failed: AtomicU64,
+    ignored: AtomicU64,
+}
+
+impl AccessCounters {
+    // Record `count` accesses of `len` bytes each. A failure counts as a single failed access.
+    pub(crate) fn record_repeated(
+        &self,
+        direction: IoDirection,
+        len: usize,
+        count: usize,
+        success: bool,
+    ) {
+        if !success {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let count = u64::try_from(count).unwrap_or(u64::MAX);
+        let bytes = u64::try_from(len).unwrap_or(u64::MAX).saturating_mul(count);
+        match direction {
+            IoDirection::Read => {
+                self.reads.fetch_add(count, Ordering::Relaxed);
+                self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+            }
+            IoDirection::Write => {
+                self.writes.fetch_add(count, Ordering::Relaxed);
+                self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Record `count` accesses completed without reaching the device.
+    pub(crate) fn record_ignored(&self, count: usize) {
+        let count = u64::try_from(count).unwrap_or(u64::MAX);
+        self.ignored.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> AccessStats {
+        AccessStats {
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            ignored: self.ignored.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        self.reads.store(0, Ordering::Relaxed);
+        self.writes.store(0, Ordering::Relaxed);
+        self.bytes_read.store(0, Ordering::Relaxed);
+        self.bytes_written.store(0, Ordering::Relaxed);
+        self.failed.store(0, Ordering::Relaxed);
+        self.ignored.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_counters() {
+        let counters = AccessCounters::default();
+        assert_eq!(counters.snapshot(), AccessStats::default());
+
+        counters.record_repeated(IoDirection::Read, 4, 1, true);
+        counters.record_repeated(IoDirection::Read, 2, 1, true);
+        counters.record_repeated(IoDirection::Write, 1, 1, true);
+        counters.record_repeated(IoDirection::Write, 8, 1, false);
+        counters.record_repeated(IoDirection::Write, 2, 3, true);
+        counters.record_repeated(IoDirection::Read, 2, 3, false);
+        counters.record_ignored(2);
+
+        assert_eq!(
+            counters.snapshot(),
+            AccessStats {
+                reads: 2,
+                writes: 4,
+                bytes_read: 6,
+                bytes_written: 7,
+                failed: 2,
+                ignored: 2,
+            }
+        );
+
+        counters.reset();
+        assert_eq!(counters.snapshot(), AccessStats::default());
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! Kernel command line generation.
+//!
+//! Guests without device tree or ACPI support discover virtio-mmio devices through the
+//! kernel command line, with a `virtio_mmio.device=<size>@<base>:<irq>` parameter per
+//! device. [`virtio_mmio_device`] builds such a parameter from the resources assigned to a
+//! device, so it always matches the range the device is registered with.
+//!
+//! # Example
+//!
+//! ```
+//! # use vm_device::cmdline::virtio_mmio_devices;
+//! # use vm_device::resources::{DeviceResources, Resource};
+//! let mut block = DeviceResources::new();
+//! block.append(Resource::MmioAddressRange {
+//!     base: 0xd000_0000,
+//!     size: 0x1000,
+//! });
+//! block.append(Resource::LegacyIrq(5));
+//!
+//! assert_eq!(
+//!     virtio_mmio_devices(&[block]).unwrap(),
+//!     "virtio_mmio.device=4K@0xd0000000:5"
+//! );
+//! ```
+
+use std::fmt::{Display, Formatter};
+
+use crate::resources::{self, DeviceResources};
+
+/// Errors encountered while generating command line parameters.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The device has no memory-mapped I/O range.
+    MissingMmioRange,
+    /// The device has more than one memory-mapped I/O range.
+    MultipleMmioRanges,
+    /// The device has no legacy IRQ.
+    MissingIrq,
+    /// The device has more than one legacy IRQ.
+    MultipleIrqs,
+    /// The resources of the device are invalid.
+    Resources(resources::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::MissingMmioRange => write!(f, "device has no MMIO range"),
+            Error::MultipleMmioRanges => write!(f, "device has multiple MMIO ranges"),
+            Error::MissingIrq => write!(f, "device has no legacy IRQ"),
+            Error::MultipleIrqs => write!(f, "device has multiple legacy IRQs"),
+            Error::Resources(e) => write!(f, "invalid device resources: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Resources(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Build the `virtio_mmio.device` parameter describing a virtio-mmio device, which must
+/// have exactly one memory-mapped I/O range and one legacy IRQ among its `resources`.
+///
+/// The size is expressed with the largest `K`, `M` or `G` suffix dividing it, and the
+/// base address in hexadecimal.
+pub fn virtio_mmio_device(resources: &DeviceResources) -> Result<String, Error> {
+    let range = match resources.get_mmio_ranges().map_err(Error::Resources)?[..] {
+        [] => return Err(Error::MissingMmioRange),
+        [range] => range,
+        _ => return Err(Error::MultipleMmioRanges),
+    };
+    let irqs: Vec<u32> = resources.legacy_irqs().collect();
+    let irq = match irqs[..] {
+        [] => return Err(Error::MissingIrq),
+        [irq] => irq,
+        _ => return Err(Error::MultipleIrqs),
+    };
+    Ok(format!(
+        "virtio_mmio.device={}@{:#x}:{}",
+        format_size(range.size()),
+        range.base().0,
+        irq
+    ))
+}
+
+/// Build the space separated `virtio_mmio.device` parameters of several devices, failing
+/// if any of them cannot be described.
+pub fn virtio_mmio_devices(devices: &[DeviceResources]) -> Result<String, Error> {
+    let params = devices
+        .iter()
+        .map(virtio_mmio_device)
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(params.join(" "))
+}
+
+// Format `size` the way the kernel parses memory sizes.
+fn format_size(size: u64) -> String {
+    for (shift, suffix) in &[(30, "G"), (20, "M"), (10, "K")] {
+        if size != 0 && size.trailing_zeros() >= *shift {
+            return format!("{}{}", size >> shift, suffix);
+        }
+    }
+    size.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::resources::Resource;
+
+    fn device_resources(resources: &[Resource]) -> DeviceResources {
+        let mut device_resources = DeviceResources::new();
+        for res in resources {
+            device_resources.append(res.clone());
+        }
+        device_resources
+    }
+
+    #[test]
+    fn test_virtio_mmio_device() {
+        let mmio = Resource::MmioAddressRange {
+            base: 0xd000_0000,
+            size: 0x200,
+        };
+        let resources = device_resources(&[
+            Resource::PioAddressRange { base: 0, size: 1 },
+            mmio.clone(),
+            Resource::LegacyIrq(12),
+        ]);
+        assert_eq!(
+            virtio_mmio_device(&resources).unwrap(),
+            "virtio_mmio.device=512@0xd0000000:12"
+        );
+
+        assert_eq!(format_size(0x1000), "4K");
+        assert_eq!(format_size(0x30_0000), "3M");
+        assert_eq!(format_size(1 << 32), "4G");
+        assert_eq!(format_size(0x1001), "4097");
+
+        assert_eq!(
+            virtio_mmio_device(&device_resources(&[Resource::LegacyIrq(5)])),
+            Err(Error::MissingMmioRange)
+        );
+        assert_eq!(
+            virtio_mmio_device(&device_resources(std::slice::from_ref(&mmio))),
+            Err(Error::MissingIrq)
+        );
+        assert_eq!(
+            virtio_mmio_device(&device_resources(&[
+                mmio.clone(),
+                Resource::LegacyIrq(5),
+                Resource::MmioAddressRange {
+                    base: 0xd000_1000,
+                    size: 0x1000,
+                },
+            ])),
+            Err(Error::MultipleMmioRanges)
+        );
+        assert_eq!(
+            virtio_mmio_device(&device_resources(&[
+                mmio.clone(),
+                Resource::LegacyIrq(5),
+                Resource::LegacyIrq(6),
+            ])),
+            Err(Error::MultipleIrqs)
+        );
+        let empty = Resource::MmioAddressRange {
+            base: 0xd000_0000,
+            size: 0,
+        };
+        assert_eq!(
+            virtio_mmio_device(&device_resources(&[empty.clone(), Resource::LegacyIrq(5)])),
+            Err(Error::Resources(resources::Error::InvalidRange(empty)))
+        );
+    }
+
+    #[test]
+    fn test_virtio_mmio_devices() {
+        let devices = [
+            device_resources(&[
+                Resource::MmioAddressRange {
+                    base: 0xd000_0000,
+                    size: 0x1000,
+                },
+                Resource::LegacyIrq(5),
+            ]),
+            device_resources(&[
+                Resource::MmioAddressRange {
+                    base: 0xd000_1000,
+                    size: 0x1000,
+                },
+                Resource::LegacyIrq(6),
+            ]),
+        ];
+        assert_eq!(
+            virtio_mmio_devices(&devices).unwrap(),
+            "virtio_mmio.device=4K@0xd0000000:5 virtio_mmio.device=4K@0xd0001000:6"
+        );
+        assert_eq!(virtio_mmio_devices(&[]).unwrap(), "");
+        assert_eq!(
+            virtio_mmio_devices(&[devices[0].clone(), DeviceResources::new()]),
+            Err(Error::MissingMmioRange)
+        );
+    }
+}
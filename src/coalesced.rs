@@ -0,0 +1,226 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! Coalesced MMIO.
+//!
+//! Devices such as framebuffers tolerate having their writes delivered later than the guest
+//! issued them. [`CoalescedMmio`] wraps such a device and buffers writes into a ring instead
+//! of dispatching them immediately. Buffered writes are delivered in order when the ring is
+//! flushed, which happens explicitly through [`CoalescedMmio::flush`], when the ring is full,
+//! before every read so that reads always observe the preceding writes, and when the wrapper
+//! is dropped.
+//!
+//! Coalesced ranges are usually registered through
+//! [`IoManager::register_coalesced_mmio`](../device_manager/struct.IoManager.html#method.register_coalesced_mmio).
+
+use std::collections::VecDeque;
+use std::sync::{Mutex, PoisonError};
+
+use crate::bus::{MmioAddress, MmioAddressOffset};
+use crate::DeviceMmio;
+
+// A write waiting to be delivered to the inner device.
+struct PendingWrite {
+    base: MmioAddress,
+    offset: MmioAddressOffset,
+    data: Vec<u8>,
+}
+
+/// Wrapper which buffers the writes to `T` in a ring of bounded capacity.
+pub struct CoalescedMmio<T: DeviceMmio> {
+    inner: T,
+    capacity: usize,
+    ring: Mutex<VecDeque<PendingWrite>>,
+}
+
+impl<T: DeviceMmio> CoalescedMmio<T> {
+    /// Wrap `inner` so that up to `capacity` writes are buffered before being delivered.
+    /// A `capacity` of zero is treated as one.
+    pub fn new(inner: T, capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        CoalescedMmio {
+            inner,
+            capacity,
+            ring: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Return a reference to the wrapped device.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Return the number of writes waiting to be delivered.
+    pub fn pending(&self) -> usize {
+        self.ring
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Deliver all buffered writes to the inner device, in the order they were issued.
+    /// Returns the number of delivered writes.
+    pub fn flush(&self) -> usize {
+        let mut ring = self.ring.lock().unwrap_or_else(PoisonError::into_inner);
+        self.deliver(&mut ring)
+    }
+
+    // The ring lock is held while delivering, so writes issued concurrently cannot
+    // overtake the ones being flushed. If the inner device panics, the writes left in the
+    // ring are discarded by `drain`, so the ring stays usable once its lock is recovered.
+    fn deliver(&self, ring: &mut VecDeque<PendingWrite>) -> usize {
+        let count = ring.len();
+        for write in ring.drain(..) {
+            self.inner.mmio_write(write.base, write.offset, &write.data);
+        }
+        count
+    }
+}
+
+impl<T: DeviceMmio> DeviceMmio for CoalescedMmio<T> {
+    fn mmio_read(&self, base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+        let mut ring = self.ring.lock().unwrap_or_else(PoisonError::into_inner);
+        self.deliver(&mut ring);
+        self.inner.mmio_read(base, offset, data);
+    }
+
+    fn mmio_write(&self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+        let mut ring = self.ring.lock().unwrap_or_else(PoisonError::into_inner);
+        if ring.len() == self.capacity {
+            self.deliver(&mut ring);
+        }
+        ring.push_back(PendingWrite {
+            base,
+            offset,
+            data: data.to_vec(),
+        });
+    }
+
+    fn mmio_poisoned(&self) -> bool {
+        self.inner.mmio_poisoned()
+    }
+}
+
+impl<T: DeviceMmio> Drop for CoalescedMmio<T> {
+    fn drop(&mut self) {
+        let ring = self.ring.get_mut().unwrap_or_else(PoisonError::into_inner);
+        for write in ring.drain(..) {
+            self.inner.mmio_write(write.base, write.offset, &write.data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    // Logs the writes it receives, and returns the number of received writes on reads.
+    struct LogDevice {
+        writes: Mutex<Vec<(MmioAddressOffset, Vec<u8>)>>,
+    }
+
+    impl DeviceMmio for LogDevice {
+        fn mmio_read(&self, _base: MmioAddress, _offset: MmioAddressOffset, data: &mut [u8]) {
+            data[0] = self.writes.lock().unwrap().len() as u8;
+        }
+
+        fn mmio_write(&self, _base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+            self.writes.lock().unwrap().push((offset, data.to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_coalesced_mmio() {
+        let device = CoalescedMmio::new(
+            LogDevice {
+                writes: Mutex::new(Vec::new()),
+            },
+            3,
+        );
+        let base = MmioAddress(0x1000);
+
+        device.mmio_write(base, 0, &[1]);
+        device.mmio_write(base, 4, &[2, 2]);
+        assert_eq!(device.pending(), 2);
+        assert!(device.inner().writes.lock().unwrap().is_empty());
+
+        assert_eq!(device.flush(), 2);
+        assert_eq!(device.flush(), 0);
+        assert_eq!(
+            *device.inner().writes.lock().unwrap(),
+            vec![(0, vec![1]), (4, vec![2, 2])]
+        );
+
+        // Reads observe the buffered writes.
+        device.mmio_write(base, 8, &[3]);
+        let mut data = [0; 1];
+        device.mmio_read(base, 0, &mut data);
+        assert_eq!(data, [3]);
+        assert_eq!(device.pending(), 0);
+
+        // A full ring is delivered before accepting new writes.
+        for i in 0..4 {
+            device.mmio_write(base, i, &[i as u8]);
+        }
+        assert_eq!(device.pending(), 1);
+        let writes = device.inner().writes.lock().unwrap();
+        assert_eq!(writes.len(), 6);
+        assert_eq!(writes[5], (2, vec![2]));
+    }
+
+    #[test]
+    fn test_coalesced_mmio_drop() {
+        let log = Arc::new(LogDevice {
+            writes: Mutex::new(Vec::new()),
+        });
+        let device = CoalescedMmio::new(log.clone(), 4);
+        device.mmio_write(MmioAddress(0x1000), 0, &[1]);
+        assert!(log.writes.lock().unwrap().is_empty());
+
+        drop(device);
+        assert_eq!(*log.writes.lock().unwrap(), vec![(0, vec![1])]);
+    }
+
+    #[test]
+    fn test_coalesced_mmio_poisoned_ring() {
+        // Panics on writes to offset 0, and logs the other ones.
+        struct PanicDevice(LogDevice);
+
+        impl DeviceMmio for PanicDevice {
+            fn mmio_read(&self, base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+                self.0.mmio_read(base, offset, data);
+            }
+
+            fn mmio_write(&self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+                assert_ne!(offset, 0);
+                self.0.mmio_write(base, offset, data);
+            }
+        }
+
+        let device = Arc::new(CoalescedMmio::new(
+            PanicDevice(LogDevice {
+                writes: Mutex::new(Vec::new()),
+            }),
+            4,
+        ));
+        let base = MmioAddress(0x1000);
+        device.mmio_write(base, 0, &[1]);
+        device.mmio_write(base, 4, &[2]);
+
+        // The inner device panics while the ring lock is held, poisoning it.
+        let flushing = device.clone();
+        assert!(std::thread::spawn(move || flushing.flush()).join().is_err());
+        assert!(device.ring.is_poisoned());
+
+        // The wrapper keeps working with the writes issued afterwards.
+        assert_eq!(device.pending(), 0);
+        device.mmio_write(base, 8, &[3]);
+        assert_eq!(device.flush(), 1);
+        let mut data = [0; 1];
+        device.mmio_read(base, 0, &mut data);
+        assert_eq!(data, [1]);
+        assert_eq!(*device.inner().0.writes.lock().unwrap(), vec![(8, vec![3])]);
+    }
+}
@@ -0,0 +1,331 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! Devices assembled from independent blocks.
+//!
+//! Many devices expose several unrelated register blocks within a single range, such as the
+//! common configuration, ISR, notification and device specific areas of a virtio-pci BAR.
+//! A [`CompositeDevice`] holds such blocks on an internal [`Bus`] keyed by offset, so it can be
+//! registered as a single range while every block receives offsets relative to its own start.
+//!
+//! Reads of offsets which are not covered by any block return zeros, and writes to them are
+//! ignored, as are accesses crossing the end of a block. The composite device is poisoned as
+//! soon as any of its blocks is.
+//!
+//! # Example
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use vm_device::bus::{MmioAddress, MmioAddressOffset, MmioRange};
+//! # use vm_device::composite::CompositeMmio;
+//! # use vm_device::device_manager::{IoManager, MmioManager};
+//! # use vm_device::DeviceMmio;
+//! struct Block(u8);
+//!
+//! impl DeviceMmio for Block {
+//!     fn mmio_read(&self, _base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+//!         data[0] = self.0 + offset as u8;
+//!     }
+//!     fn mmio_write(&self, _base: MmioAddress, _offset: MmioAddressOffset, _data: &[u8]) {}
+//! }
+//!
+//! let mut device = CompositeMmio::<Box<dyn DeviceMmio + Send + Sync>>::new();
+//! device
+//!     .add_block(MmioRange::new(MmioAddress(0), 0x100).unwrap(), Box::new(Block(0x10)))
+//!     .unwrap();
+//! device
+//!     .add_block(MmioRange::new(MmioAddress(0x100), 0x100).unwrap(), Box::new(Block(0x20)))
+//!     .unwrap();
+//!
+//! let mut manager = IoManager::new();
+//! let range = MmioRange::new(MmioAddress(0x1000_0000), 0x1000).unwrap();
+//! manager.register_mmio(range, Arc::new(device)).unwrap();
+//!
+//! // The second block sees an offset of 1.
+//! let mut data = [0; 1];
+//! manager.mmio_read(MmioAddress(0x1000_0101), &mut data).unwrap();
+//! assert_eq!(data, [0x21]);
+//! ```
+
+use crate::bus::{
+    self, Bus, BusAddress, BusRange, MmioAddress, MmioAddressOffset, PioAddress, PioAddressOffset,
+};
+use crate::{DeviceMmio, DevicePio};
+
+/// A device made of blocks registered at offsets within its range.
+pub struct CompositeDevice<A: BusAddress, D> {
+    // Keyed by the offsets of the blocks, expressed as addresses starting from zero.
+    blocks: Bus<A, D>,
+}
+
+/// A composite device for the MMIO bus.
+pub type CompositeMmio<D> = CompositeDevice<MmioAddress, D>;
+/// A composite device for the PIO bus.
+pub type CompositePio<D> = CompositeDevice<PioAddress, D>;
+
+impl<A: BusAddress, D> Default for CompositeDevice<A, D> {
+    fn default() -> Self {
+        CompositeDevice { blocks: Bus::new() }
+    }
+}
+
+impl<A: BusAddress, D> CompositeDevice<A, D> {
+    /// Create a composite device without any block.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a block covering `range`, whose addresses are offsets within the composite device.
+    pub fn add_block(&mut self, range: BusRange<A>, device: D) -> Result<(), bus::Error> {
+        self.blocks.register(range, device)
+    }
+
+    /// Remove the block covering `offset`, returning its range and device.
+    pub fn remove_block(&mut self, offset: A) -> Option<(BusRange<A>, D)> {
+        self.blocks.deregister(offset)
+    }
+
+    /// Return the block covering `offset` together with its range, if available.
+    pub fn block(&self, offset: A) -> Option<(&BusRange<A>, &D)> {
+        self.blocks.device(offset)
+    }
+}
+
+impl<D: DeviceMmio> DeviceMmio for CompositeDevice<MmioAddress, D> {
+    fn mmio_read(&self, base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+        let addr = MmioAddress(offset);
+        match self.blocks.check_access(addr, data.len()) {
+            Ok((range, device)) => {
+                device.mmio_read(base + range.base().0, addr - range.base(), data)
+            }
+            Err(_) => data.fill(0),
+        }
+    }
+
+    fn mmio_write(&self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+        let addr = MmioAddress(offset);
+        if let Ok((range, device)) = self.blocks.check_access(addr, data.len()) {
+            device.mmio_write(base + range.base().0, addr - range.base(), data);
+        }
+    }
+
+    fn mmio_poisoned(&self) -> bool {
+        self.blocks
+            .devices()
+            .any(|(_, device)| device.mmio_poisoned())
+    }
+}
+
+impl<D: DevicePio> DevicePio for CompositeDevice<PioAddress, D> {
+    fn pio_read(&self, base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
+        let addr = PioAddress(offset);
+        match self.blocks.check_access(addr, data.len()) {
+            Ok((range, device)) => {
+                device.pio_read(base + range.base().0, addr - range.base(), data)
+            }
+            Err(_) => data.fill(0),
+        }
+    }
+
+    fn pio_write(&self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
+        let addr = PioAddress(offset);
+        if let Ok((range, device)) = self.blocks.check_access(addr, data.len()) {
+            device.pio_write(base + range.base().0, addr - range.base(), data);
+        }
+    }
+
+    fn pio_read_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &mut [u8],
+    ) {
+        let addr = PioAddress(offset);
+        match self.blocks.check_access(addr, size) {
+            Ok((range, device)) => {
+                device.pio_read_string(base + range.base().0, addr - range.base(), size, data)
+            }
+            Err(_) => data.fill(0),
+        }
+    }
+
+    fn pio_write_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &[u8],
+    ) {
+        let addr = PioAddress(offset);
+        if let Ok((range, device)) = self.blocks.check_access(addr, size) {
+            device.pio_write_string(base + range.base().0, addr - range.base(), size, data);
+        }
+    }
+
+    fn pio_poisoned(&self) -> bool {
+        self.blocks
+            .devices()
+            .any(|(_, device)| device.pio_poisoned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use crate::bus::{MmioRange, PioRange};
+
+    // Logs the accesses it receives, and reads back the low byte of the offset.
+    #[derive(Default)]
+    struct LogDevice {
+        writes: Mutex<Vec<(u64, u64, Vec<u8>)>>,
+        poisoned: bool,
+    }
+
+    impl DeviceMmio for LogDevice {
+        fn mmio_read(&self, _base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+            data.fill(offset as u8);
+        }
+
+        fn mmio_write(&self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+            self.writes
+                .lock()
+                .unwrap()
+                .push((base.0, offset, data.to_vec()));
+        }
+
+        fn mmio_poisoned(&self) -> bool {
+            self.poisoned
+        }
+    }
+
+    impl DevicePio for LogDevice {
+        fn pio_read(&self, _base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
+            data.fill(offset as u8);
+        }
+
+        fn pio_write(&self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
+            self.writes
+                .lock()
+                .unwrap()
+                .push((base.0.into(), offset.into(), data.to_vec()));
+        }
+
+        fn pio_poisoned(&self) -> bool {
+            self.poisoned
+        }
+    }
+
+    #[test]
+    fn test_composite_mmio() {
+        let mut device = CompositeMmio::new();
+        let common = MmioRange::new(MmioAddress(0), 0x10).unwrap();
+        let notify = MmioRange::new(MmioAddress(0x100), 0x10).unwrap();
+        device.add_block(common, LogDevice::default()).unwrap();
+        device.add_block(notify, LogDevice::default()).unwrap();
+        assert_eq!(
+            device.add_block(
+                MmioRange::new(MmioAddress(0xf), 2).unwrap(),
+                LogDevice::default()
+            ),
+            Err(bus::Error::DeviceOverlap)
+        );
+
+        let base = MmioAddress(0x1000);
+        let mut data = [0xff; 2];
+        device.mmio_read(base, 0x104, &mut data);
+        assert_eq!(data, [4, 4]);
+        device.mmio_write(base, 0x102, &[1, 2]);
+        assert_eq!(
+            *device
+                .block(MmioAddress(0x100))
+                .unwrap()
+                .1
+                .writes
+                .lock()
+                .unwrap(),
+            vec![(0x1100, 2, vec![1, 2])]
+        );
+
+        // Holes and accesses crossing the end of a block.
+        device.mmio_read(base, 0x50, &mut data);
+        assert_eq!(data, [0, 0]);
+        data = [0xff; 2];
+        device.mmio_read(base, 0xf, &mut data);
+        assert_eq!(data, [0, 0]);
+        device.mmio_write(base, 0x50, &[1]);
+        device.mmio_write(base, 0xf, &[1, 2]);
+        assert!(device
+            .block(MmioAddress(0))
+            .unwrap()
+            .1
+            .writes
+            .lock()
+            .unwrap()
+            .is_empty());
+
+        let (range, _) = device.remove_block(MmioAddress(0x10f)).unwrap();
+        assert_eq!(range, notify);
+        assert!(device.block(MmioAddress(0x100)).is_none());
+
+        // A single poisoned block poisons the whole device.
+        assert!(!device.mmio_poisoned());
+        let poisoned = LogDevice {
+            poisoned: true,
+            ..Default::default()
+        };
+        device.add_block(notify, poisoned).unwrap();
+        assert!(device.mmio_poisoned());
+    }
+
+    #[test]
+    fn test_composite_pio() {
+        let mut device = CompositePio::new();
+        device
+            .add_block(
+                PioRange::new(PioAddress(4), 4).unwrap(),
+                LogDevice::default(),
+            )
+            .unwrap();
+
+        let base = PioAddress(0x3f8);
+        let mut data = [0xff; 4];
+        device.pio_read(base, 6, &mut data[..1]);
+        assert_eq!(data[0], 2);
+        device.pio_read_string(base, 5, 1, &mut data);
+        assert_eq!(data, [1; 4]);
+        device.pio_read_string(base, 1, 1, &mut data);
+        assert_eq!(data, [0; 4]);
+
+        device.pio_write(base, 4, &[1]);
+        device.pio_write_string(base, 7, 1, &[2, 3]);
+        device.pio_write(base, 8, &[4]);
+        assert_eq!(
+            *device
+                .block(PioAddress(4))
+                .unwrap()
+                .1
+                .writes
+                .lock()
+                .unwrap(),
+            vec![
+                (0x3fc, 0, vec![1]),
+                (0x3fc, 3, vec![2]),
+                (0x3fc, 3, vec![3])
+            ]
+        );
+
+        assert!(!device.pio_poisoned());
+        let poisoned = LogDevice {
+            poisoned: true,
+            ..Default::default()
+        };
+        device
+            .add_block(PioRange::new(PioAddress(0), 4).unwrap(), poisoned)
+            .unwrap();
+        assert!(device.pio_poisoned());
+    }
+}
@@ -116,23 +116,40 @@
 
 use std::fmt::{Display, Formatter};
 use std::result::Result;
-use std::sync::Arc;
-
-use crate::bus::{self, BusManager, MmioAddress, MmioBus, MmioRange, PioAddress, PioBus, PioRange};
-use crate::resources::Resource;
+use std::sync::{Arc, Weak};
+use std::time::Instant;
+
+use crate::acpi::{self, DeviceAml};
+use crate::async_io::{AsyncDeviceMmio, AsyncDevicePio};
+use crate::bus::{
+    self, AccessStats, Bus, BusAddress, BusManager, BusRange, Dispatch, IoDirection, MmioAddress,
+    MmioAddressOffset, MmioBus, MmioIoEvent, MmioRange, Notifier, NotifyMode, Permissions,
+    PioAddress, PioAddressOffset, PioBus, PioIoEvent, PioRange, PoisonPolicy,
+};
+use crate::coalesced::CoalescedMmio;
+use crate::fdt::{self, DeviceFdt, FdtWriter};
+use crate::memory_map::{self, MemoryMap};
+use crate::resources::{self, DeviceResources, ReservedResources, Resource};
+use crate::trace::{IoAccess, IoBus, TraceSink};
 use crate::{DeviceMmio, DevicePio};
 
+// Coalescing wrapper of the devices registered through `register_coalesced_mmio`.
+type CoalescedDevice = CoalescedMmio<Arc<dyn DeviceMmio + Send + Sync>>;
+
 /// Error type for [IoManager] usage.
 #[derive(Debug)]
 pub enum Error {
     /// Error during bus operation.
     Bus(bus::Error),
+    /// The device claims an IRQ reserved for the platform.
+    ReservedIrq(u32),
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::Bus(_) => write!(f, "device_manager: bus error"),
+            Error::ReservedIrq(irq) => write!(f, "device_manager: IRQ {} is reserved", irq),
         }
     }
 }
@@ -141,10 +158,36 @@ impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Error::Bus(e) => Some(e),
+            Error::ReservedIrq(_) => None,
         }
     }
 }
 
+/// An operation part of a batch dispatched with [`PioManager::pio_batch`] or
+/// [`MmioManager::mmio_batch`].
+#[derive(Debug)]
+pub enum IoOp<'a, A> {
+    /// Read from `addr` into `data`.
+    Read {
+        /// Address to read from.
+        addr: A,
+        /// Buffer to store the read data.
+        data: &'a mut [u8],
+    },
+    /// Write `data` to `addr`.
+    Write {
+        /// Address to write to.
+        addr: A,
+        /// Data to write.
+        data: &'a [u8],
+    },
+}
+
+/// Represents an operation on the PIO bus.
+pub type PioOp<'a> = IoOp<'a, PioAddress>;
+/// Represents an operation on the MMIO bus.
+pub type MmioOp<'a> = IoOp<'a, MmioAddress>;
+
 /// Represents an object that provides PIO manager operations.
 pub trait PioManager {
     /// Type of the objects that can be registered with this `PioManager`.
@@ -158,6 +201,8 @@ pub trait PioManager {
     fn pio_read(&self, addr: PioAddress, data: &mut [u8]) -> Result<(), bus::Error>;
 
     /// Dispatch a write operation to the device registered at `addr`.
+    ///
+    /// I/O events registered with the bus are signaled before the write reaches the device.
     fn pio_write(&self, addr: PioAddress, data: &[u8]) -> Result<(), bus::Error>;
 
     /// Register the provided device with the specified range.
@@ -166,6 +211,143 @@ pub trait PioManager {
     /// Deregister the device currently registered at `addr` together with the
     /// associated range.
     fn deregister_pio(&mut self, addr: PioAddress) -> Option<(PioRange, Self::D)>;
+
+    /// Dispatch a string read (`rep ins`) of `count` elements of `size` bytes each, all
+    /// read from `addr`. `data` must be exactly `count * size` bytes long.
+    ///
+    /// The default implementation dispatches every element on its own.
+    fn pio_read_string(
+        &self,
+        addr: PioAddress,
+        size: usize,
+        count: usize,
+        data: &mut [u8],
+    ) -> Result<(), bus::Error> {
+        check_string_access(size, count, data.len())?;
+        for element in data.chunks_mut(size) {
+            self.pio_read(addr, element)?;
+        }
+        Ok(())
+    }
+
+    /// Dispatch a string write (`rep outs`) of `count` elements of `size` bytes each, all
+    /// written to `addr`. `data` must be exactly `count * size` bytes long.
+    ///
+    /// The default implementation dispatches every element on its own.
+    fn pio_write_string(
+        &self,
+        addr: PioAddress,
+        size: usize,
+        count: usize,
+        data: &[u8],
+    ) -> Result<(), bus::Error> {
+        check_string_access(size, count, data.len())?;
+        for element in data.chunks(size) {
+            self.pio_write(addr, element)?;
+        }
+        Ok(())
+    }
+
+    /// Dispatch a batch of operations in order, returning the result of each of them.
+    ///
+    /// The default implementation dispatches every operation on its own; implementations
+    /// may resolve the devices more efficiently when the addresses are sorted.
+    fn pio_batch(&self, ops: &mut [PioOp<'_>]) -> Vec<Result<(), bus::Error>> {
+        ops.iter_mut()
+            .map(|op| match op {
+                IoOp::Read { addr, data } => self.pio_read(*addr, data),
+                IoOp::Write { addr, data } => self.pio_write(*addr, data),
+            })
+            .collect()
+    }
+}
+
+// Device an access is dispatched to, along with the base of its range and the offset of the
+// access within it.
+type AccessTarget<'a, A, D> = (&'a D, A, <A as BusAddress>::V);
+
+// Resolve an access at `addr` to the device it must be dispatched to. This is shared by all
+// the dispatch paths of the device managers.
+//
+// The address is first translated through the aliases of `bus`. Writes are then handed to
+// `notify`, which signals the matching I/O events and returns whether one of them handles
+// the write instead of the device. `lookup` finds the device and accounts the access, as
+// `Bus::dispatch_access` does, and accesses to devices reported by `poisoned` fail if the
+// bus is configured to. Finally, accesses ignored by the permissions of their range are
+// completed through `ignore`. Accesses which must not reach the device resolve to `None`.
+fn resolve_access<'a, A: BusAddress + 'a, D: 'a>(
+    bus: &Bus<A, D>,
+    addr: A,
+    notify: impl FnOnce(A) -> bool,
+    lookup: impl FnOnce(A) -> Result<Dispatch<'a, A, D>, bus::Error>,
+    poisoned: impl FnOnce(&D) -> bool,
+    ignore: impl FnOnce(u8),
+) -> Result<Option<AccessTarget<'a, A, D>>, bus::Error> {
+    let addr = bus.canonical_addr(addr);
+    if notify(addr) {
+        return Ok(None);
+    }
+    match lookup(addr)? {
+        Dispatch::Device(range, device) => {
+            bus.poison_policy().check(poisoned(device))?;
+            Ok(Some((device, range.base(), addr - range.base())))
+        }
+        Dispatch::Ignored(value) => {
+            ignore(value);
+            Ok(None)
+        }
+    }
+}
+
+// Report an access completed with `result` to `manager`, and return the result of the access.
+// `result` holds the offset of the access within the range of the device it reached, if any.
+fn observed<A: BusAddress, M: BusManager<A> + ?Sized>(
+    manager: &M,
+    addr: A,
+    direction: IoDirection,
+    data: &[u8],
+    result: Result<Option<A::V>, bus::Error>,
+) -> Result<(), bus::Error> {
+    let offset = result.as_ref().map_or(None, |offset| *offset);
+    let result = result.map(|_| ());
+    manager.observe_access(addr, offset, direction, data, &result);
+    result
+}
+
+// Same as `observed` for a string access of `size` byte elements, which are reported one by
+// one as if they had been accessed separately.
+fn observed_string<A: BusAddress, M: BusManager<A> + ?Sized>(
+    manager: &M,
+    addr: A,
+    direction: IoDirection,
+    size: usize,
+    data: &[u8],
+    result: Result<Option<A::V>, bus::Error>,
+) -> Result<(), bus::Error> {
+    let offset = result.as_ref().map_or(None, |offset| *offset);
+    let result = result.map(|_| ());
+    for element in data.chunks(size) {
+        manager.observe_access(addr, offset, direction, element, &result);
+    }
+    result
+}
+
+// Check that a string access of `count` elements of `size` bytes fits `len` bytes exactly.
+fn check_string_access(size: usize, count: usize, len: usize) -> Result<(), bus::Error> {
+    if size == 0 || size.checked_mul(count) != Some(len) {
+        return Err(bus::Error::InvalidAccessLength(len));
+    }
+    Ok(())
+}
+
+// Return the `canonical` range along with the valid alias windows mirroring it at `aliases`.
+// The invalid ones are reported by the bus when registering them.
+fn alias_ranges<A: BusAddress>(canonical: BusRange<A>, aliases: &[A]) -> Vec<BusRange<A>> {
+    aliases
+        .iter()
+        .filter_map(|base| BusRange::new(*base, canonical.size()).ok())
+        .chain(std::iter::once(canonical))
+        .collect()
 }
 
 // This automatically provides a `PioManager` implementation for types that already implement
@@ -182,23 +364,167 @@ where
     }
 
     fn pio_read(&self, addr: PioAddress, data: &mut [u8]) -> Result<(), bus::Error> {
-        self.bus()
-            .check_access(addr, data.len())
-            .map(|(range, device)| device.pio_read(range.base(), addr - range.base(), data))
+        let bus = self.bus();
+        let len = data.len();
+        let result = match resolve_access(
+            bus,
+            addr,
+            |_| false,
+            |addr| bus.dispatch_access(addr, len, IoDirection::Read),
+            |device| device.pio_poisoned(),
+            |value| data.fill(value),
+        ) {
+            Ok(Some((device, base, offset))) => {
+                device.pio_read(base, offset, data);
+                Ok(Some(offset))
+            }
+            other => other.map(|_| None),
+        };
+        observed(self, addr, IoDirection::Read, data, result)
     }
 
     fn pio_write(&self, addr: PioAddress, data: &[u8]) -> Result<(), bus::Error> {
-        self.bus()
-            .check_access(addr, data.len())
-            .map(|(range, device)| device.pio_write(range.base(), addr - range.base(), data))
+        let bus = self.bus();
+        let result = match resolve_access(
+            bus,
+            addr,
+            |addr| bus.notify_write(addr, data),
+            |addr| bus.dispatch_access(addr, data.len(), IoDirection::Write),
+            |device| device.pio_poisoned(),
+            |_| (),
+        ) {
+            Ok(Some((device, base, offset))) => {
+                device.pio_write(base, offset, data);
+                Ok(Some(offset))
+            }
+            other => other.map(|_| None),
+        };
+        observed(self, addr, IoDirection::Write, data, result)
     }
 
     fn register_pio(&mut self, range: PioRange, device: Self::D) -> Result<(), bus::Error> {
+        self.check_range(&range)?;
         self.bus_mut().register(range, device)
     }
 
     fn deregister_pio(&mut self, addr: PioAddress) -> Option<(PioRange, Self::D)> {
-        self.bus_mut().deregister(addr)
+        let (range, device) = self.bus_mut().deregister(addr)?;
+        self.release_device(range, &device);
+        Some((range, device))
+    }
+
+    // The whole string is handed to the device at once, so it can use a bulk handler.
+    fn pio_read_string(
+        &self,
+        addr: PioAddress,
+        size: usize,
+        count: usize,
+        data: &mut [u8],
+    ) -> Result<(), bus::Error> {
+        if let Err(error) = check_string_access(size, count, data.len()) {
+            return observed(self, addr, IoDirection::Read, data, Err(error));
+        }
+        if count == 0 {
+            return Ok(());
+        }
+        let bus = self.bus();
+        let result = match resolve_access(
+            bus,
+            addr,
+            |_| false,
+            |addr| bus.dispatch_repeated_access(addr, size, count, IoDirection::Read),
+            |device| device.pio_poisoned(),
+            |value| data.fill(value),
+        ) {
+            Ok(Some((device, base, offset))) => {
+                device.pio_read_string(base, offset, size, data);
+                Ok(Some(offset))
+            }
+            other => other.map(|_| None),
+        };
+        observed_string(self, addr, IoDirection::Read, size, data, result)
+    }
+
+    fn pio_write_string(
+        &self,
+        addr: PioAddress,
+        size: usize,
+        count: usize,
+        data: &[u8],
+    ) -> Result<(), bus::Error> {
+        if let Err(error) = check_string_access(size, count, data.len()) {
+            return observed(self, addr, IoDirection::Write, data, Err(error));
+        }
+        if count == 0 {
+            return Ok(());
+        }
+        let bus = self.bus();
+        if bus.has_ioevents(bus.canonical_addr(addr)) {
+            // Notifications have to be interleaved with the dispatch of each element.
+            for element in data.chunks(size) {
+                self.pio_write(addr, element)?;
+            }
+            return Ok(());
+        }
+        let result = match resolve_access(
+            bus,
+            addr,
+            |_| false,
+            |addr| bus.dispatch_repeated_access(addr, size, count, IoDirection::Write),
+            |device| device.pio_poisoned(),
+            |_| (),
+        ) {
+            Ok(Some((device, base, offset))) => {
+                device.pio_write_string(base, offset, size, data);
+                Ok(Some(offset))
+            }
+            other => other.map(|_| None),
+        };
+        observed_string(self, addr, IoDirection::Write, size, data, result)
+    }
+
+    fn pio_batch(&self, ops: &mut [PioOp<'_>]) -> Vec<Result<(), bus::Error>> {
+        let bus = self.bus();
+        let mut cursor = bus.cursor();
+        ops.iter_mut()
+            .map(|op| match op {
+                IoOp::Read { addr, data } => {
+                    let len = data.len();
+                    let result = match resolve_access(
+                        bus,
+                        *addr,
+                        |_| false,
+                        |addr| cursor.dispatch_access(addr, len, IoDirection::Read),
+                        |device| device.pio_poisoned(),
+                        |value| data.fill(value),
+                    ) {
+                        Ok(Some((device, base, offset))) => {
+                            device.pio_read(base, offset, data);
+                            Ok(Some(offset))
+                        }
+                        other => other.map(|_| None),
+                    };
+                    observed(self, *addr, IoDirection::Read, data, result)
+                }
+                IoOp::Write { addr, data } => {
+                    let result = match resolve_access(
+                        bus,
+                        *addr,
+                        |addr| bus.notify_write(addr, data),
+                        |addr| cursor.dispatch_access(addr, data.len(), IoDirection::Write),
+                        |device| device.pio_poisoned(),
+                        |_| (),
+                    ) {
+                        Ok(Some((device, base, offset))) => {
+                            device.pio_write(base, offset, data);
+                            Ok(Some(offset))
+                        }
+                        other => other.map(|_| None),
+                    };
+                    observed(self, *addr, IoDirection::Write, data, result)
+                }
+            })
+            .collect()
     }
 }
 
@@ -215,6 +541,8 @@ pub trait MmioManager {
     fn mmio_read(&self, addr: MmioAddress, data: &mut [u8]) -> Result<(), bus::Error>;
 
     /// Dispatch a write operation to the device registered at `addr`.
+    ///
+    /// I/O events registered with the bus are signaled before the write reaches the device.
     fn mmio_write(&self, addr: MmioAddress, data: &[u8]) -> Result<(), bus::Error>;
 
     /// Register the provided device with the specified range.
@@ -223,6 +551,19 @@ pub trait MmioManager {
     /// Deregister the device currently registered at `addr` together with the
     /// associated range.
     fn deregister_mmio(&mut self, addr: MmioAddress) -> Option<(MmioRange, Self::D)>;
+
+    /// Dispatch a batch of operations in order, returning the result of each of them.
+    ///
+    /// The default implementation dispatches every operation on its own; implementations
+    /// may resolve the devices more efficiently when the addresses are sorted.
+    fn mmio_batch(&self, ops: &mut [MmioOp<'_>]) -> Vec<Result<(), bus::Error>> {
+        ops.iter_mut()
+            .map(|op| match op {
+                IoOp::Read { addr, data } => self.mmio_read(*addr, data),
+                IoOp::Write { addr, data } => self.mmio_write(*addr, data),
+            })
+            .collect()
+    }
 }
 
 // This automatically provides a `MmioManager` implementation for types that already implement
@@ -239,23 +580,97 @@ where
     }
 
     fn mmio_read(&self, addr: MmioAddress, data: &mut [u8]) -> Result<(), bus::Error> {
-        self.bus()
-            .check_access(addr, data.len())
-            .map(|(range, device)| device.mmio_read(range.base(), addr - range.base(), data))
+        let bus = self.bus();
+        let len = data.len();
+        let result = match resolve_access(
+            bus,
+            addr,
+            |_| false,
+            |addr| bus.dispatch_access(addr, len, IoDirection::Read),
+            |device| device.mmio_poisoned(),
+            |value| data.fill(value),
+        ) {
+            Ok(Some((device, base, offset))) => {
+                device.mmio_read(base, offset, data);
+                Ok(Some(offset))
+            }
+            other => other.map(|_| None),
+        };
+        observed(self, addr, IoDirection::Read, data, result)
     }
 
     fn mmio_write(&self, addr: MmioAddress, data: &[u8]) -> Result<(), bus::Error> {
-        self.bus()
-            .check_access(addr, data.len())
-            .map(|(range, device)| device.mmio_write(range.base(), addr - range.base(), data))
+        let bus = self.bus();
+        let result = match resolve_access(
+            bus,
+            addr,
+            |addr| bus.notify_write(addr, data),
+            |addr| bus.dispatch_access(addr, data.len(), IoDirection::Write),
+            |device| device.mmio_poisoned(),
+            |_| (),
+        ) {
+            Ok(Some((device, base, offset))) => {
+                device.mmio_write(base, offset, data);
+                Ok(Some(offset))
+            }
+            other => other.map(|_| None),
+        };
+        observed(self, addr, IoDirection::Write, data, result)
     }
 
     fn register_mmio(&mut self, range: MmioRange, device: Self::D) -> Result<(), bus::Error> {
+        self.check_range(&range)?;
         self.bus_mut().register(range, device)
     }
 
     fn deregister_mmio(&mut self, addr: MmioAddress) -> Option<(MmioRange, Self::D)> {
-        self.bus_mut().deregister(addr)
+        let (range, device) = self.bus_mut().deregister(addr)?;
+        self.release_device(range, &device);
+        Some((range, device))
+    }
+
+    fn mmio_batch(&self, ops: &mut [MmioOp<'_>]) -> Vec<Result<(), bus::Error>> {
+        let bus = self.bus();
+        let mut cursor = bus.cursor();
+        ops.iter_mut()
+            .map(|op| match op {
+                IoOp::Read { addr, data } => {
+                    let len = data.len();
+                    let result = match resolve_access(
+                        bus,
+                        *addr,
+                        |_| false,
+                        |addr| cursor.dispatch_access(addr, len, IoDirection::Read),
+                        |device| device.mmio_poisoned(),
+                        |value| data.fill(value),
+                    ) {
+                        Ok(Some((device, base, offset))) => {
+                            device.mmio_read(base, offset, data);
+                            Ok(Some(offset))
+                        }
+                        other => other.map(|_| None),
+                    };
+                    observed(self, *addr, IoDirection::Read, data, result)
+                }
+                IoOp::Write { addr, data } => {
+                    let result = match resolve_access(
+                        bus,
+                        *addr,
+                        |addr| bus.notify_write(addr, data),
+                        |addr| cursor.dispatch_access(addr, data.len(), IoDirection::Write),
+                        |device| device.mmio_poisoned(),
+                        |_| (),
+                    ) {
+                        Ok(Some((device, base, offset))) => {
+                            device.mmio_write(base, offset, data);
+                            Ok(Some(offset))
+                        }
+                        other => other.map(|_| None),
+                    };
+                    observed(self, *addr, IoDirection::Write, data, result)
+                }
+            })
+            .collect()
     }
 }
 
@@ -266,6 +681,20 @@ pub struct IoManager {
     pio_bus: PioBus<Arc<dyn DevicePio + Send + Sync>>,
     // Range mapping for VM exit mmio operations.
     mmio_bus: MmioBus<Arc<dyn DeviceMmio + Send + Sync>>,
+    // Sink used for tracing the devices registered while it's set.
+    trace_sink: Option<Arc<dyn TraceSink + Send + Sync>>,
+    // Coalesced MMIO ranges, flushed by `flush_coalesced_mmio`.
+    coalesced_mmio: Vec<(MmioRange, Weak<CoalescedDevice>)>,
+    // Range mapping for asynchronous pio devices.
+    async_pio_bus: PioBus<Arc<dyn AsyncDevicePio + Send + Sync>>,
+    // Range mapping for asynchronous mmio devices.
+    async_mmio_bus: MmioBus<Arc<dyn AsyncDeviceMmio + Send + Sync>>,
+    // Devices described in the generated device tree, along with their resources.
+    fdt_devices: Vec<(Arc<dyn DeviceFdt + Send + Sync>, DeviceResources)>,
+    // Devices described in the generated DSDT, along with their resources.
+    aml_devices: Vec<(Arc<dyn DeviceAml + Send + Sync>, DeviceResources)>,
+    // IRQs devices registered with their resources cannot claim.
+    reserved_irqs: Vec<u32>,
 }
 
 // Enables the automatic implementation of `PioManager` for `IoManager`.
@@ -279,6 +708,34 @@ impl BusManager<PioAddress> for IoManager {
     fn bus_mut(&mut self) -> &mut PioBus<Arc<dyn DevicePio + Send + Sync>> {
         &mut self.pio_bus
     }
+
+    // Synchronous and asynchronous devices share the address space.
+    fn check_range(&self, range: &PioRange) -> Result<(), bus::Error> {
+        if self.async_pio_bus.overlaps(range) {
+            return Err(bus::Error::DeviceOverlap);
+        }
+        Ok(())
+    }
+
+    fn release_device(&mut self, range: PioRange, _device: &Self::D) {
+        let resource = Resource::PioAddressRange {
+            base: range.base().0,
+            size: range.size(),
+        };
+        release_resource(&mut self.aml_devices, &resource);
+    }
+
+    fn observe_access(
+        &self,
+        addr: PioAddress,
+        offset: Option<PioAddressOffset>,
+        direction: IoDirection,
+        data: &[u8],
+        result: &Result<(), bus::Error>,
+    ) {
+        let offset = offset.map(u64::from);
+        self.trace(IoBus::Pio, addr.0.into(), offset, direction, data, result);
+    }
 }
 
 // Enables the automatic implementation of `MmioManager` for `IoManager`.
@@ -292,6 +749,43 @@ impl BusManager<MmioAddress> for IoManager {
     fn bus_mut(&mut self) -> &mut MmioBus<Arc<dyn DeviceMmio + Send + Sync>> {
         &mut self.mmio_bus
     }
+
+    fn check_range(&self, range: &MmioRange) -> Result<(), bus::Error> {
+        if self.async_mmio_bus.overlaps(range) {
+            return Err(bus::Error::DeviceOverlap);
+        }
+        Ok(())
+    }
+
+    fn observe_access(
+        &self,
+        addr: MmioAddress,
+        offset: Option<MmioAddressOffset>,
+        direction: IoDirection,
+        data: &[u8],
+        result: &Result<(), bus::Error>,
+    ) {
+        self.trace(IoBus::Mmio, addr.0, offset, direction, data, result);
+    }
+
+    fn release_device(&mut self, range: MmioRange, _device: &Self::D) {
+        let resource = Resource::MmioAddressRange {
+            base: range.base().0,
+            size: range.size(),
+        };
+        release_resource(&mut self.fdt_devices, &resource);
+        release_resource(&mut self.aml_devices, &resource);
+        // The writes still buffered for a coalesced range are delivered before it goes away.
+        self.coalesced_mmio.retain(|(coalesced_range, coalesced)| {
+            if *coalesced_range != range {
+                return true;
+            }
+            if let Some(coalesced) = coalesced.upgrade() {
+                coalesced.flush();
+            }
+            false
+        });
+    }
 }
 
 impl IoManager {
@@ -300,29 +794,378 @@ impl IoManager {
         IoManager::default()
     }
 
+    /// Set the sink used for tracing accesses, or disable tracing when `sink` is `None`.
+    ///
+    /// While a sink is set, every access dispatched by the manager is reported to it once it
+    /// completes, including the accesses to devices registered before the sink was set and
+    /// the accesses which fail or don't reach a device. The reported address is the one used
+    /// by the guest, before any alias translation.
+    pub fn set_trace_sink(&mut self, sink: Option<Arc<dyn TraceSink + Send + Sync>>) {
+        self.trace_sink = sink;
+    }
+
+    // Report an access to the trace sink, if any.
+    fn trace(
+        &self,
+        bus: IoBus,
+        address: u64,
+        offset: Option<u64>,
+        direction: IoDirection,
+        data: &[u8],
+        result: &Result<(), bus::Error>,
+    ) {
+        if let Some(sink) = self.trace_sink.as_ref() {
+            sink.record(IoAccess {
+                bus,
+                address,
+                offset,
+                direction,
+                data: data.to_vec(),
+                result: result.clone(),
+                timestamp: Instant::now(),
+            });
+        }
+    }
+
+    /// Reserve the resources of the platform, so devices cannot claim them.
+    ///
+    /// Registering a device with a reserved port I/O or memory-mapped I/O range then fails
+    /// with [`RangeReserved`](../bus/enum.Error.html#variant.RangeReserved), and registering
+    /// it with resources including a reserved IRQ fails with
+    /// [`ReservedIrq`](enum.Error.html#variant.ReservedIrq). Devices already registered are
+    /// left in place, so the devices emulating the reserved blocks can be registered first.
+    pub fn reserve_resources(&mut self, reserved: &ReservedResources) -> Result<(), Error> {
+        let pio_ranges = reserved
+            .pio_ranges()
+            .iter()
+            .map(|&(base, size)| PioRange::new(PioAddress(base), size))
+            .collect::<Result<Vec<_>, bus::Error>>()
+            .map_err(Error::Bus)?;
+        let mmio_ranges = reserved
+            .mmio_ranges()
+            .iter()
+            .map(|&(base, size)| MmioRange::new(MmioAddress(base), size))
+            .collect::<Result<Vec<_>, bus::Error>>()
+            .map_err(Error::Bus)?;
+
+        for range in pio_ranges {
+            self.pio_bus.reserve(range);
+            self.async_pio_bus.reserve(range);
+        }
+        for range in mmio_ranges {
+            self.mmio_bus.reserve(range);
+            self.async_mmio_bus.reserve(range);
+        }
+        self.reserved_irqs.extend_from_slice(reserved.irqs());
+        Ok(())
+    }
+
+    // Check that `resources` do not include any reserved IRQ.
+    fn check_irqs(&self, resources: &[Resource]) -> Result<(), Error> {
+        for res in resources.iter() {
+            let (base, size) = match *res {
+                Resource::LegacyIrq(irq) => (irq, 1),
+                Resource::MsiIrq { base, size, .. } => (base, size),
+                _ => continue,
+            };
+            if let Some(irq) = self
+                .reserved_irqs
+                .iter()
+                .find(|&&irq| irq >= base && irq - base < size)
+            {
+                return Err(Error::ReservedIrq(*irq));
+            }
+        }
+        Ok(())
+    }
+
+    /// Register `notifier` to be signaled on PIO writes matching `event`.
+    ///
+    /// Writes handled by an event registered with
+    /// [`NotifyMode::InsteadOfDispatch`](../bus/enum.NotifyMode.html#variant.InsteadOfDispatch)
+    /// never reach a device, so they are not counted in the access statistics. They are still
+    /// reported to the trace sink, without an offset.
+    pub fn register_pio_ioevent(
+        &mut self,
+        event: PioIoEvent,
+        mode: NotifyMode,
+        notifier: Arc<dyn Notifier + Send + Sync>,
+    ) -> Result<(), Error> {
+        self.pio_bus
+            .register_ioevent(event, mode, notifier)
+            .map_err(Error::Bus)
+    }
+
+    /// Deregister the notifier associated with the PIO `event`.
+    pub fn deregister_pio_ioevent(
+        &mut self,
+        event: &PioIoEvent,
+    ) -> Option<Arc<dyn Notifier + Send + Sync>> {
+        self.pio_bus.deregister_ioevent(event)
+    }
+
+    /// Register `notifier` to be signaled on MMIO writes matching `event`.
+    ///
+    /// As for PIO, writes handled instead of being dispatched are traced but not counted.
+    pub fn register_mmio_ioevent(
+        &mut self,
+        event: MmioIoEvent,
+        mode: NotifyMode,
+        notifier: Arc<dyn Notifier + Send + Sync>,
+    ) -> Result<(), Error> {
+        self.mmio_bus
+            .register_ioevent(event, mode, notifier)
+            .map_err(Error::Bus)
+    }
+
+    /// Deregister the notifier associated with the MMIO `event`.
+    pub fn deregister_mmio_ioevent(
+        &mut self,
+        event: &MmioIoEvent,
+    ) -> Option<Arc<dyn Notifier + Send + Sync>> {
+        self.mmio_bus.deregister_ioevent(event)
+    }
+
+    /// Enable or disable the collection of per-range access statistics on both buses.
+    ///
+    /// Statistics count the reads, writes and bytes dispatched to each registered range, as
+    /// well as the accesses which start within a range but fail to be dispatched. Enabling
+    /// them starts all counters from zero.
+    pub fn set_stats_enabled(&mut self, enabled: bool) {
+        self.pio_bus.set_stats_enabled(enabled);
+        self.mmio_bus.set_stats_enabled(enabled);
+        self.async_pio_bus.set_stats_enabled(enabled);
+        self.async_mmio_bus.set_stats_enabled(enabled);
+    }
+
+    /// Return a snapshot of the access statistics of every range registered on the PIO bus.
+    pub fn pio_stats(&self) -> Vec<(PioRange, AccessStats)> {
+        let mut stats = self.pio_bus.stats();
+        stats.extend(self.async_pio_bus.stats());
+        stats.sort_by_key(|(range, _)| *range);
+        stats
+    }
+
+    /// Return a snapshot of the access statistics of every range registered on the MMIO bus.
+    pub fn mmio_stats(&self) -> Vec<(MmioRange, AccessStats)> {
+        let mut stats = self.mmio_bus.stats();
+        stats.extend(self.async_mmio_bus.stats());
+        stats.sort_by_key(|(range, _)| *range);
+        stats
+    }
+
+    /// Reset the access statistics of both buses.
+    pub fn reset_stats(&self) {
+        self.pio_bus.reset_stats();
+        self.mmio_bus.reset_stats();
+        self.async_pio_bus.reset_stats();
+        self.async_mmio_bus.reset_stats();
+    }
+
+    /// Set how accesses to poisoned devices are handled on both buses. A device is
+    /// poisoned when a previous access panicked while holding its lock; the default is
+    /// to recover the device state and keep dispatching accesses to it.
+    ///
+    /// Asynchronous devices cannot report being poisoned, so the policy does not apply to
+    /// them.
+    pub fn set_poison_policy(&mut self, policy: PoisonPolicy) {
+        self.pio_bus.set_poison_policy(policy);
+        self.mmio_bus.set_poison_policy(policy);
+    }
+
+    /// Register an asynchronous PIO device with the specified range.
+    ///
+    /// Asynchronous devices are only reachable through the `*_async` dispatch methods. The
+    /// range must not overlap the range of any device already registered on the PIO bus.
+    pub fn register_async_pio(
+        &mut self,
+        range: PioRange,
+        device: Arc<dyn AsyncDevicePio + Send + Sync>,
+    ) -> Result<(), Error> {
+        if self.pio_bus.overlaps(&range) {
+            return Err(Error::Bus(bus::Error::DeviceOverlap));
+        }
+        self.async_pio_bus
+            .register(range, device)
+            .map_err(Error::Bus)
+    }
+
+    /// Deregister the asynchronous PIO device registered at `addr` together with the
+    /// associated range.
+    pub fn deregister_async_pio(
+        &mut self,
+        addr: PioAddress,
+    ) -> Option<(PioRange, Arc<dyn AsyncDevicePio + Send + Sync>)> {
+        self.async_pio_bus.deregister(addr)
+    }
+
+    /// Register an asynchronous MMIO device with the specified range.
+    ///
+    /// Asynchronous devices are only reachable through the `*_async` dispatch methods. The
+    /// range must not overlap the range of any device already registered on the MMIO bus.
+    pub fn register_async_mmio(
+        &mut self,
+        range: MmioRange,
+        device: Arc<dyn AsyncDeviceMmio + Send + Sync>,
+    ) -> Result<(), Error> {
+        if self.mmio_bus.overlaps(&range) {
+            return Err(Error::Bus(bus::Error::DeviceOverlap));
+        }
+        self.async_mmio_bus
+            .register(range, device)
+            .map_err(Error::Bus)
+    }
+
+    /// Deregister the asynchronous MMIO device registered at `addr` together with the
+    /// associated range.
+    pub fn deregister_async_mmio(
+        &mut self,
+        addr: MmioAddress,
+    ) -> Option<(MmioRange, Arc<dyn AsyncDeviceMmio + Send + Sync>)> {
+        self.async_mmio_bus.deregister(addr)
+    }
+
+    /// Dispatch a read operation to the device registered at `addr`, awaiting its
+    /// completion if the device is asynchronous.
+    pub async fn pio_read_async(
+        &self,
+        addr: PioAddress,
+        data: &mut [u8],
+    ) -> Result<(), bus::Error> {
+        if self.async_pio_bus.device(addr).is_none() {
+            return self.pio_read(addr, data);
+        }
+        let len = data.len();
+        let result = match resolve_access(
+            &self.async_pio_bus,
+            addr,
+            |_| false,
+            |addr| {
+                self.async_pio_bus
+                    .dispatch_access(addr, len, IoDirection::Read)
+            },
+            |_| false,
+            |value| data.fill(value),
+        ) {
+            Ok(Some((device, base, offset))) => {
+                device.pio_read(base, offset, data).await;
+                Ok(Some(offset))
+            }
+            other => other.map(|_| None),
+        };
+        observed(self, addr, IoDirection::Read, data, result)
+    }
+
+    /// Dispatch a write operation to the device registered at `addr`, awaiting its
+    /// completion if the device is asynchronous.
+    pub async fn pio_write_async(&self, addr: PioAddress, data: &[u8]) -> Result<(), bus::Error> {
+        if self.async_pio_bus.device(addr).is_none() {
+            return self.pio_write(addr, data);
+        }
+        let result = match resolve_access(
+            &self.async_pio_bus,
+            addr,
+            |addr| self.pio_bus.notify_write(addr, data),
+            |addr| {
+                self.async_pio_bus
+                    .dispatch_access(addr, data.len(), IoDirection::Write)
+            },
+            |_| false,
+            |_| (),
+        ) {
+            Ok(Some((device, base, offset))) => {
+                device.pio_write(base, offset, data).await;
+                Ok(Some(offset))
+            }
+            other => other.map(|_| None),
+        };
+        observed(self, addr, IoDirection::Write, data, result)
+    }
+
+    /// Dispatch a read operation to the device registered at `addr`, awaiting its
+    /// completion if the device is asynchronous.
+    pub async fn mmio_read_async(
+        &self,
+        addr: MmioAddress,
+        data: &mut [u8],
+    ) -> Result<(), bus::Error> {
+        if self.async_mmio_bus.device(addr).is_none() {
+            return self.mmio_read(addr, data);
+        }
+        let len = data.len();
+        let result = match resolve_access(
+            &self.async_mmio_bus,
+            addr,
+            |_| false,
+            |addr| {
+                self.async_mmio_bus
+                    .dispatch_access(addr, len, IoDirection::Read)
+            },
+            |_| false,
+            |value| data.fill(value),
+        ) {
+            Ok(Some((device, base, offset))) => {
+                device.mmio_read(base, offset, data).await;
+                Ok(Some(offset))
+            }
+            other => other.map(|_| None),
+        };
+        observed(self, addr, IoDirection::Read, data, result)
+    }
+
+    /// Dispatch a write operation to the device registered at `addr`, awaiting its
+    /// completion if the device is asynchronous.
+    pub async fn mmio_write_async(&self, addr: MmioAddress, data: &[u8]) -> Result<(), bus::Error> {
+        if self.async_mmio_bus.device(addr).is_none() {
+            return self.mmio_write(addr, data);
+        }
+        let result = match resolve_access(
+            &self.async_mmio_bus,
+            addr,
+            |addr| self.mmio_bus.notify_write(addr, data),
+            |addr| {
+                self.async_mmio_bus
+                    .dispatch_access(addr, data.len(), IoDirection::Write)
+            },
+            |_| false,
+            |_| (),
+        ) {
+            Ok(Some((device, base, offset))) => {
+                device.mmio_write(base, offset, data).await;
+                Ok(Some(offset))
+            }
+            other => other.map(|_| None),
+        };
+        observed(self, addr, IoDirection::Write, data, result)
+    }
+
     /// Register a new MMIO device with its allocated resources.
     /// VMM is responsible for providing the allocated resources to virtual device.
     ///
+    /// PCI BARs are not registered: the guest may move them by reprogramming the BAR
+    /// registers, so the VMM registers the range a BAR decodes with
+    /// [`register_mmio`](trait.MmioManager.html#tymethod.register_mmio) once the guest
+    /// enables it.
+    ///
     /// # Arguments
     ///
     /// * `device`: device instance object to be registered
     /// * `resources`: resources that this device owns, might include
-    ///                port I/O and memory-mapped I/O ranges, irq number, etc.
+    ///   port I/O and memory-mapped I/O ranges, irq number, etc.
     pub fn register_mmio_resources(
         &mut self,
         device: Arc<dyn DeviceMmio + Send + Sync>,
         resources: &[Resource],
     ) -> Result<(), Error> {
+        self.check_irqs(resources)?;
         // Register and mark device resources
         // The resources addresses being registered are sucessfully allocated before.
         for res in resources.iter() {
             match *res {
                 Resource::MmioAddressRange { base, size } => {
-                    self.register_mmio(
-                        MmioRange::new(MmioAddress(base), size).unwrap(),
-                        device.clone(),
-                    )
-                    .map_err(Error::Bus)?;
+                    let range = MmioRange::new(MmioAddress(base), size).map_err(Error::Bus)?;
+                    self.register_mmio(range, device.clone())
+                        .map_err(Error::Bus)?;
                 }
                 _ => continue,
             }
@@ -330,6 +1173,255 @@ impl IoManager {
         Ok(())
     }
 
+    /// Register a new MMIO device with its allocated resources, and describe it in the
+    /// device tree generated by [`generate_fdt`](#method.generate_fdt).
+    ///
+    /// # Arguments
+    ///
+    /// * `device`: device instance object to be registered
+    /// * `resources`: resources that this device owns; its memory-mapped I/O ranges make up
+    ///   the `reg` property of the node and its legacy IRQs the `interrupts` property
+    pub fn register_mmio_fdt<T: DeviceMmio + DeviceFdt + 'static + Send + Sync>(
+        &mut self,
+        device: Arc<T>,
+        resources: &[Resource],
+    ) -> Result<(), Error> {
+        self.register_mmio_resources(device.clone(), resources)?;
+        self.fdt_devices
+            .push((device, to_device_resources(resources)));
+        Ok(())
+    }
+
+    /// Write a node for every device registered with
+    /// [`register_mmio_fdt`](#method.register_mmio_fdt) as subnodes of the current node of
+    /// `fdt`, in address order.
+    ///
+    /// The `reg` property lists the ranges the device is currently registered with, using
+    /// two cells for addresses and two cells for sizes, so the current node is expected to
+    /// set `#address-cells` and `#size-cells` to 2. The `interrupts` property lists the
+    /// legacy IRQs of the device, encoded by
+    /// [`DeviceFdt::fdt_interrupt`](../fdt/trait.DeviceFdt.html#method.fdt_interrupt);
+    /// the interrupt parent is typically set on an ancestor node. Devices are left out once
+    /// all their ranges are deregistered.
+    pub fn write_fdt_nodes(&self, fdt: &mut FdtWriter) -> Result<(), fdt::Error> {
+        let mut nodes = Vec::new();
+        for (device, resources) in self.fdt_devices.iter() {
+            let ranges = registered_ranges(resources.get_mmio_ranges());
+            if let Some(range) = ranges.first() {
+                nodes.push((range.base().0, device, resources, ranges));
+            }
+        }
+        nodes.sort_by_key(|(base, ..)| *base);
+
+        for (base, device, resources, ranges) in nodes {
+            fdt.begin_node(&format!("{}@{:x}", device.fdt_node_name(), base))?;
+            fdt.property_string_list("compatible", &device.fdt_compatible())?;
+            let reg: Vec<u64> = ranges
+                .iter()
+                .flat_map(|range| [range.base().0, range.size()])
+                .collect();
+            fdt.property_array_u64("reg", &reg)?;
+            let interrupts: Vec<u32> = resources
+                .get_all_resources()
+                .iter()
+                .filter_map(|res| match *res {
+                    Resource::LegacyIrq(irq) => Some(device.fdt_interrupt(irq)),
+                    _ => None,
+                })
+                .flatten()
+                .collect();
+            if !interrupts.is_empty() {
+                fdt.property_array_u32("interrupts", &interrupts)?;
+            }
+            device.fdt_properties(fdt)?;
+            fdt.end_node()?;
+        }
+        Ok(())
+    }
+
+    /// Generate a flattened device tree blob whose root node holds the nodes written by
+    /// [`write_fdt_nodes`](#method.write_fdt_nodes).
+    pub fn generate_fdt(&self) -> Result<Vec<u8>, fdt::Error> {
+        let mut fdt = FdtWriter::new();
+        fdt.property_u32("#address-cells", 2)?;
+        fdt.property_u32("#size-cells", 2)?;
+        self.write_fdt_nodes(&mut fdt)?;
+        fdt.finish()
+    }
+
+    /// Register a new PIO device with its allocated resources, and describe it in the DSDT
+    /// body generated by [`generate_dsdt_body`](#method.generate_dsdt_body).
+    ///
+    /// # Arguments
+    ///
+    /// * `device`: device instance object to be registered
+    /// * `resources`: resources that this device owns; its port I/O ranges and legacy IRQs
+    ///   make up the `_CRS` object of the device
+    pub fn register_pio_aml<T: DevicePio + DeviceAml + 'static + Send + Sync>(
+        &mut self,
+        device: Arc<T>,
+        resources: &[Resource],
+    ) -> Result<(), Error> {
+        self.register_pio_resources(device.clone(), resources)?;
+        self.aml_devices
+            .push((device, to_device_resources(resources)));
+        Ok(())
+    }
+
+    /// Register a new MMIO device with its allocated resources, and describe it in the DSDT
+    /// body generated by [`generate_dsdt_body`](#method.generate_dsdt_body).
+    ///
+    /// # Arguments
+    ///
+    /// * `device`: device instance object to be registered
+    /// * `resources`: resources that this device owns; its memory-mapped I/O ranges and
+    ///   legacy IRQs make up the `_CRS` object of the device
+    pub fn register_mmio_aml<T: DeviceMmio + DeviceAml + 'static + Send + Sync>(
+        &mut self,
+        device: Arc<T>,
+        resources: &[Resource],
+    ) -> Result<(), Error> {
+        self.register_mmio_resources(device.clone(), resources)?;
+        self.aml_devices
+            .push((device, to_device_resources(resources)));
+        Ok(())
+    }
+
+    /// Generate the AML objects describing every device registered with
+    /// [`register_pio_aml`](#method.register_pio_aml) or
+    /// [`register_mmio_aml`](#method.register_mmio_aml), in registration order, within the
+    /// `\_SB_` scope. The result is meant to be placed after the header of the DSDT.
+    ///
+    /// The `_CRS` object of every device lists the ranges it is currently registered with
+    /// and its legacy IRQs. Devices are left out once all their ranges are deregistered.
+    pub fn generate_dsdt_body(&self) -> Result<Vec<u8>, acpi::Error> {
+        let mut body = Vec::new();
+        for (device, resources) in self.aml_devices.iter() {
+            if has_address_range(resources) {
+                body.extend(acpi::device_aml(
+                    device.as_ref(),
+                    resources.get_all_resources(),
+                )?);
+            }
+        }
+        acpi::scope("_SB", &body)
+    }
+
+    /// Add every range the MMIO bus dispatches, including the ranges of asynchronous devices
+    /// and alias windows, to `map` as reserved regions. Fails if any of them overlaps RAM.
+    pub fn reserve_mmio_ranges(&self, map: &mut MemoryMap) -> Result<(), memory_map::Error> {
+        for range in self
+            .mmio_bus
+            .ranges()
+            .into_iter()
+            .chain(self.async_mmio_bus.ranges())
+        {
+            map.add_reserved(range.base().0, range.size())?;
+        }
+        Ok(())
+    }
+
+    /// Register a new MMIO device whose writes are buffered instead of being dispatched
+    /// immediately.
+    ///
+    /// Up to `capacity` writes are buffered and delivered in order when
+    /// [`flush_coalesced_mmio`](#method.flush_coalesced_mmio) is called, when the buffer
+    /// is full, before a read is dispatched to the device, and when the device is
+    /// deregistered.
+    ///
+    /// # Arguments
+    ///
+    /// * `range`: MMIO range the device is registered with
+    /// * `device`: device instance object to be registered
+    /// * `capacity`: maximum number of writes to buffer
+    pub fn register_coalesced_mmio(
+        &mut self,
+        range: MmioRange,
+        device: Arc<dyn DeviceMmio + Send + Sync>,
+        capacity: usize,
+    ) -> Result<(), Error> {
+        let coalesced = Arc::new(CoalescedMmio::new(device, capacity));
+        self.register_mmio(range, coalesced.clone())
+            .map_err(Error::Bus)?;
+        self.coalesced_mmio
+            .push((range, Arc::downgrade(&coalesced)));
+        Ok(())
+    }
+
+    /// Deliver the writes buffered for all coalesced MMIO ranges. Returns the number of
+    /// delivered writes.
+    pub fn flush_coalesced_mmio(&self) -> usize {
+        self.coalesced_mmio
+            .iter()
+            .filter_map(|(_, c)| c.upgrade())
+            .map(|c| c.flush())
+            .sum()
+    }
+
+    /// Register a PIO device with the specified range, only dispatching the accesses allowed
+    /// by `permissions` to it. Forbidden accesses either fail with
+    /// [`AccessDenied`](../bus/enum.Error.html#variant.AccessDenied) or complete without
+    /// reaching the device, as configured in `permissions`.
+    pub fn register_pio_with_permissions(
+        &mut self,
+        range: PioRange,
+        device: Arc<dyn DevicePio + Send + Sync>,
+        permissions: Permissions,
+    ) -> Result<(), Error> {
+        BusManager::<PioAddress>::check_range(self, &range).map_err(Error::Bus)?;
+        self.pio_bus
+            .register_with_permissions(range, device, permissions)
+            .map_err(Error::Bus)
+    }
+
+    /// Register a MMIO device with the specified range, only dispatching the accesses allowed
+    /// by `permissions` to it. Forbidden accesses either fail with
+    /// [`AccessDenied`](../bus/enum.Error.html#variant.AccessDenied) or complete without
+    /// reaching the device, as configured in `permissions`.
+    pub fn register_mmio_with_permissions(
+        &mut self,
+        range: MmioRange,
+        device: Arc<dyn DeviceMmio + Send + Sync>,
+        permissions: Permissions,
+    ) -> Result<(), Error> {
+        BusManager::<MmioAddress>::check_range(self, &range).map_err(Error::Bus)?;
+        self.mmio_bus
+            .register_with_permissions(range, device, permissions)
+            .map_err(Error::Bus)
+    }
+
+    /// Register a PIO device with the `canonical` range, mirrored at each of the `aliases`
+    /// base addresses. See [`Bus::register_aliases`](../bus/struct.Bus.html#method.register_aliases).
+    pub fn register_pio_aliases(
+        &mut self,
+        canonical: PioRange,
+        aliases: &[PioAddress],
+        device: Arc<dyn DevicePio + Send + Sync>,
+    ) -> Result<(), Error> {
+        for range in alias_ranges(canonical, aliases) {
+            BusManager::<PioAddress>::check_range(self, &range).map_err(Error::Bus)?;
+        }
+        self.pio_bus
+            .register_aliases(canonical, aliases, device)
+            .map_err(Error::Bus)
+    }
+
+    /// Register a MMIO device with the `canonical` range, mirrored at each of the `aliases`
+    /// base addresses. See [`Bus::register_aliases`](../bus/struct.Bus.html#method.register_aliases).
+    pub fn register_mmio_aliases(
+        &mut self,
+        canonical: MmioRange,
+        aliases: &[MmioAddress],
+        device: Arc<dyn DeviceMmio + Send + Sync>,
+    ) -> Result<(), Error> {
+        for range in alias_ranges(canonical, aliases) {
+            BusManager::<MmioAddress>::check_range(self, &range).map_err(Error::Bus)?;
+        }
+        self.mmio_bus
+            .register_aliases(canonical, aliases, device)
+            .map_err(Error::Bus)
+    }
+
     /// Register a new PIO device with its allocated resources.
     /// VMM is responsible for providing the allocated resources to virtual device.
     ///
@@ -337,22 +1429,21 @@ impl IoManager {
     ///
     /// * `device`: device instance object to be registered
     /// * `resources`: resources that this device owns, might include
-    ///                port I/O and memory-mapped I/O ranges, irq number, etc.
+    ///   port I/O and memory-mapped I/O ranges, irq number, etc.
     pub fn register_pio_resources(
         &mut self,
         device: Arc<dyn DevicePio + Send + Sync>,
         resources: &[Resource],
     ) -> Result<(), Error> {
+        self.check_irqs(resources)?;
         // Register and mark device resources
         // The resources addresses being registered are sucessfully allocated before.
         for res in resources.iter() {
             match *res {
                 Resource::PioAddressRange { base, size } => {
-                    self.register_pio(
-                        PioRange::new(PioAddress(base), size).unwrap(),
-                        device.clone(),
-                    )
-                    .map_err(Error::Bus)?;
+                    let range = PioRange::new(PioAddress(base), size).map_err(Error::Bus)?;
+                    self.register_pio(range, device.clone())
+                        .map_err(Error::Bus)?;
                 }
                 _ => continue,
             }
@@ -367,7 +1458,7 @@ impl IoManager {
     ///
     /// * `device`: device instance object to be registered
     /// * `resources`: resources that this device owns, might include
-    ///                port I/O and memory-mapped I/O ranges, irq number, etc.
+    ///   port I/O and memory-mapped I/O ranges, irq number, etc.
     pub fn register_resources<T: DeviceMmio + DevicePio + 'static + Send + Sync>(
         &mut self,
         device: Arc<T>,
@@ -385,7 +1476,7 @@ impl IoManager {
     /// # Arguments
     ///
     /// * `resources`: resources that this device owns, might include
-    ///                port I/O and memory-mapped I/O ranges, irq number, etc.
+    ///   port I/O and memory-mapped I/O ranges, irq number, etc.
     pub fn deregister_resources(&mut self, resources: &[Resource]) -> usize {
         let mut count = 0;
         for res in resources.iter() {
@@ -407,6 +1498,32 @@ impl IoManager {
     }
 }
 
+fn to_device_resources(resources: &[Resource]) -> DeviceResources {
+    let mut device_resources = DeviceResources::new();
+    for res in resources.iter() {
+        device_resources.append(res.clone());
+    }
+    device_resources
+}
+
+// Remove the address range `resource` from the resources of the described `devices`, and
+// forget the devices left without any address range.
+fn release_resource<T: ?Sized>(devices: &mut Vec<(Arc<T>, DeviceResources)>, resource: &Resource) {
+    devices
+        .retain_mut(|(_, resources)| !resources.remove(resource) || has_address_range(resources));
+}
+
+// Ranges of a device which was registered, and whose ranges were therefore validated.
+fn registered_ranges<R>(ranges: Result<Vec<R>, resources::Error>) -> Vec<R> {
+    ranges.unwrap_or_default()
+}
+
+// Whether the registered device described by `resources` still has an address range.
+fn has_address_range(resources: &DeviceResources) -> bool {
+    !registered_ranges(resources.get_pio_ranges()).is_empty()
+        || !registered_ranges(resources.get_mmio_ranges()).is_empty()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -414,7 +1531,8 @@ mod tests {
     use std::error::Error;
     use std::sync::Mutex;
 
-    use bus::{MmioAddressOffset, PioAddressOffset};
+    use crate::resources::PciBarType;
+    use bus::DeniedAccess;
 
     const PIO_ADDRESS_SIZE: u16 = 4;
     const PIO_ADDRESS_BASE: u16 = 0x40;
@@ -497,6 +1615,32 @@ mod tests {
         assert_eq!(io_mgr.deregister_resources(&resource), 2);
     }
 
+    #[test]
+    fn test_register_mmio_resources_pci_bar() {
+        let mut io_mgr = IoManager::new();
+        let dum = Arc::new(DummyDevice::new(0));
+
+        // PCI BARs are left for the VMM to register once the guest enables them.
+        let resource = vec![
+            Resource::MmioAddressRange {
+                base: MMIO_ADDRESS_BASE,
+                size: MMIO_ADDRESS_SIZE,
+            },
+            Resource::PciBar {
+                index: 0,
+                base: 0xe000_0000,
+                size: 0x1000,
+                bar_type: PciBarType::Mmio32,
+                prefetchable: false,
+            },
+        ];
+
+        assert!(io_mgr.register_mmio_resources(dum, &resource).is_ok());
+        assert!(io_mgr.mmio_device(MmioAddress(MMIO_ADDRESS_BASE)).is_some());
+        assert!(io_mgr.mmio_device(MmioAddress(0xe000_0000)).is_none());
+        assert_eq!(io_mgr.deregister_resources(&resource), 1);
+    }
+
     #[test]
     fn test_mmio_read_write() {
         let mut io_mgr: IoManager = Default::default();
@@ -572,6 +1716,1151 @@ mod tests {
             .is_err());
     }
 
+    #[test]
+    fn test_trace_sink() {
+        let mut io_mgr = IoManager::new();
+        let sink = Arc::new(Mutex::new(Vec::new()));
+        let dum = Arc::new(DummyDevice::new(CONFIG_DATA));
+
+        let range = PioRange::new(PioAddress(PIO_ADDRESS_BASE), PIO_ADDRESS_SIZE).unwrap();
+        io_mgr.register_pio(range, dum.clone()).unwrap();
+
+        io_mgr.set_trace_sink(Some(sink.clone()));
+        let range = MmioRange::new(MmioAddress(MMIO_ADDRESS_BASE), MMIO_ADDRESS_SIZE).unwrap();
+        io_mgr.register_mmio(range, dum).unwrap();
+        let window = PioRange::new(PioAddress(0x400), 0x400).unwrap();
+        io_mgr
+            .pio_bus
+            .register_alias(window, PioAddress(0), Some(0x3ff))
+            .unwrap();
+
+        // Devices registered before setting the sink are traced as well.
+        let mut data = [0; 4];
+        io_mgr
+            .pio_read(PioAddress(PIO_ADDRESS_BASE), &mut data)
+            .unwrap();
+        io_mgr
+            .mmio_read(MmioAddress(MMIO_ADDRESS_BASE + 4), &mut data)
+            .unwrap();
+        io_mgr
+            .mmio_write(MmioAddress(MMIO_ADDRESS_BASE), &[0x56])
+            .unwrap();
+        // Aliased accesses are traced at the address used by the guest, and failed ones too.
+        io_mgr
+            .pio_write(PioAddress(0x400 + PIO_ADDRESS_BASE + 1), &[0x78])
+            .unwrap();
+        assert!(io_mgr.pio_read(PioAddress(0x500), &mut data).is_err());
+
+        let accesses = sink.lock().unwrap();
+        assert_eq!(accesses.len(), 5);
+        assert_eq!(accesses[0].bus, IoBus::Pio);
+        assert_eq!(accesses[0].address, u64::from(PIO_ADDRESS_BASE));
+        assert_eq!(accesses[1].bus, IoBus::Mmio);
+        assert_eq!(accesses[1].address, MMIO_ADDRESS_BASE + 4);
+        assert_eq!(accesses[1].offset, Some(4));
+        assert_eq!(accesses[1].direction, IoDirection::Read);
+        assert_eq!(accesses[1].data, vec![0x34, 0x12, 0, 0]);
+        assert_eq!(accesses[2].direction, IoDirection::Write);
+        assert_eq!(accesses[2].data, vec![0x56]);
+        assert_eq!(accesses[3].address, u64::from(0x400 + PIO_ADDRESS_BASE + 1));
+        assert_eq!(accesses[3].offset, Some(1));
+        assert_eq!(accesses[3].result, Ok(()));
+        assert_eq!(accesses[4].address, 0x500);
+        assert_eq!(accesses[4].offset, None);
+        assert_eq!(accesses[4].result, Err(bus::Error::DeviceNotFound));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut io_mgr = IoManager::new();
+        let dum = Arc::new(DummyDevice::new(CONFIG_DATA));
+        let pio_range = PioRange::new(PioAddress(PIO_ADDRESS_BASE), PIO_ADDRESS_SIZE).unwrap();
+        let mmio_range = MmioRange::new(MmioAddress(MMIO_ADDRESS_BASE), MMIO_ADDRESS_SIZE).unwrap();
+
+        io_mgr.register_pio(pio_range, dum.clone()).unwrap();
+        io_mgr.set_stats_enabled(true);
+        io_mgr.register_mmio(mmio_range, dum).unwrap();
+
+        let mut data = [0; 4];
+        io_mgr
+            .pio_read(PioAddress(PIO_ADDRESS_BASE), &mut data)
+            .unwrap();
+        io_mgr
+            .pio_write(PioAddress(PIO_ADDRESS_BASE + 1), &data[..2])
+            .unwrap();
+        assert!(io_mgr
+            .pio_read(PioAddress(PIO_ADDRESS_BASE + 1), &mut data)
+            .is_err());
+        io_mgr
+            .mmio_write(MmioAddress(MMIO_ADDRESS_BASE), &data)
+            .unwrap();
+
+        assert_eq!(
+            io_mgr.pio_stats(),
+            vec![(
+                pio_range,
+                AccessStats {
+                    reads: 1,
+                    writes: 1,
+                    bytes_read: 4,
+                    bytes_written: 2,
+                    failed: 1,
+                    ignored: 0,
+                }
+            )]
+        );
+        let mmio_stats = io_mgr.mmio_stats();
+        assert_eq!(mmio_stats.len(), 1);
+        assert_eq!(mmio_stats[0].1.writes, 1);
+        assert_eq!(mmio_stats[0].1.bytes_written, 4);
+
+        io_mgr.reset_stats();
+        assert_eq!(io_mgr.pio_stats()[0].1, AccessStats::default());
+        assert_eq!(io_mgr.mmio_stats()[0].1, AccessStats::default());
+
+        io_mgr.set_stats_enabled(false);
+        assert!(io_mgr.pio_stats().is_empty());
+        assert!(io_mgr.mmio_stats().is_empty());
+    }
+
+    #[test]
+    fn test_permissions() {
+        let mut io_mgr = IoManager::new();
+        let rom = Arc::new(DummyDevice::new(CONFIG_DATA));
+        let doorbell = Arc::new(DummyDevice::new(CONFIG_DATA));
+        let strict = Arc::new(DummyDevice::new(CONFIG_DATA));
+        io_mgr
+            .register_mmio_with_permissions(
+                MmioRange::new(MmioAddress(0x1000), 0x100).unwrap(),
+                rom.clone(),
+                Permissions::read_only(DeniedAccess::Ignore(0)),
+            )
+            .unwrap();
+        io_mgr
+            .register_mmio_with_permissions(
+                MmioRange::new(MmioAddress(0x2000), 0x100).unwrap(),
+                doorbell.clone(),
+                Permissions::write_only(DeniedAccess::Ignore(0xff)),
+            )
+            .unwrap();
+        io_mgr
+            .register_pio_with_permissions(
+                PioRange::new(PioAddress(PIO_ADDRESS_BASE), PIO_ADDRESS_SIZE).unwrap(),
+                strict.clone(),
+                Permissions::read_only(DeniedAccess::Fail),
+            )
+            .unwrap();
+
+        // Ignored writes never reach the device.
+        io_mgr.mmio_write(MmioAddress(0x1000), &[0x12]).unwrap();
+        assert_eq!(*rom.config.lock().unwrap(), CONFIG_DATA);
+        let mut data = [0; 2];
+        io_mgr.mmio_read(MmioAddress(0x1000), &mut data).unwrap();
+        assert_eq!(data, [CONFIG_DATA as u8, (CONFIG_DATA >> 8) as u8]);
+
+        // Ignored reads return the configured value.
+        io_mgr.mmio_write(MmioAddress(0x2000), &[0x34]).unwrap();
+        assert_eq!(*doorbell.config.lock().unwrap(), 0x34);
+        io_mgr.mmio_read(MmioAddress(0x2000), &mut data).unwrap();
+        assert_eq!(data, [0xff, 0xff]);
+        assert_eq!(
+            io_mgr.mmio_batch(&mut [IoOp::Read {
+                addr: MmioAddress(0x2001),
+                data: &mut data[..1],
+            }]),
+            vec![Ok(())]
+        );
+
+        // Failed accesses are reported.
+        let denied = Err(bus::Error::AccessDenied);
+        assert_eq!(
+            io_mgr.pio_write(PioAddress(PIO_ADDRESS_BASE), &[0x56]),
+            denied
+        );
+        assert_eq!(
+            io_mgr.pio_write_string(PioAddress(PIO_ADDRESS_BASE), 1, 2, &[1, 2]),
+            denied
+        );
+        assert_eq!(*strict.config.lock().unwrap(), CONFIG_DATA);
+        io_mgr
+            .pio_read(PioAddress(PIO_ADDRESS_BASE), &mut data)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut io_mgr = IoManager::new();
+        let dum = Arc::new(DummyDevice::new(CONFIG_DATA));
+        let canonical = PioRange::new(PioAddress(PIO_ADDRESS_BASE), PIO_ADDRESS_SIZE).unwrap();
+        io_mgr
+            .register_pio_aliases(
+                canonical,
+                &[PioAddress(0x140), PioAddress(0x240)],
+                dum.clone(),
+            )
+            .unwrap();
+        assert!(io_mgr
+            .register_pio(PioRange::new(PioAddress(0x142), 1).unwrap(), dum.clone())
+            .is_err());
+
+        // Writes through a mirror reach the device at the canonical offset.
+        io_mgr.pio_write(PioAddress(0x241), &[0x12]).unwrap();
+        let mut data = [0; 2];
+        io_mgr.pio_read(PioAddress(0x140), &mut data).unwrap();
+        assert_eq!(data, [0x12, 0]);
+        assert_eq!(
+            io_mgr.pio_batch(&mut [
+                IoOp::Write {
+                    addr: PioAddress(0x143),
+                    data: &[0x34],
+                },
+                IoOp::Read {
+                    addr: PioAddress(PIO_ADDRESS_BASE),
+                    data: &mut data,
+                },
+            ]),
+            vec![Ok(()), Ok(())]
+        );
+        assert_eq!(data, [0x34, 0]);
+        io_mgr
+            .pio_write_string(PioAddress(0x240), 1, 2, &[1, 2])
+            .unwrap();
+        assert_eq!(*dum.config.lock().unwrap(), 2);
+
+        // Deregistering the device removes its mirrors.
+        io_mgr.deregister_pio(PioAddress(PIO_ADDRESS_BASE)).unwrap();
+        assert_eq!(
+            io_mgr.pio_read(PioAddress(0x140), &mut data),
+            Err(bus::Error::DeviceNotFound)
+        );
+        io_mgr
+            .register_pio(PioRange::new(PioAddress(0x140), 1).unwrap(), dum)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_ioevents() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut io_mgr = IoManager::new();
+        let dum = Arc::new(DummyDevice::new(CONFIG_DATA));
+        let range = MmioRange::new(MmioAddress(MMIO_ADDRESS_BASE), MMIO_ADDRESS_SIZE).unwrap();
+        io_mgr.register_mmio(range, dum.clone()).unwrap();
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let count2 = count.clone();
+        let notifier: Arc<dyn Notifier + Send + Sync> = Arc::new(move || {
+            count2.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // A notification followed by the regular dispatch.
+        let before = MmioIoEvent::new(MmioAddress(MMIO_ADDRESS_BASE), None, None).unwrap();
+        io_mgr
+            .register_mmio_ioevent(before, NotifyMode::BeforeDispatch, notifier.clone())
+            .unwrap();
+        io_mgr
+            .mmio_write(MmioAddress(MMIO_ADDRESS_BASE), &[0x56])
+            .unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert_eq!(*dum.config.lock().unwrap(), 0x56);
+
+        // A notification replacing the dispatch.
+        let instead =
+            MmioIoEvent::new(MmioAddress(MMIO_ADDRESS_BASE), Some(1), Some(0x78)).unwrap();
+        io_mgr
+            .register_mmio_ioevent(instead, NotifyMode::InsteadOfDispatch, notifier.clone())
+            .unwrap();
+        io_mgr
+            .mmio_write(MmioAddress(MMIO_ADDRESS_BASE), &[0x78])
+            .unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(*dum.config.lock().unwrap(), 0x56);
+
+        assert!(io_mgr
+            .register_mmio_ioevent(instead, NotifyMode::BeforeDispatch, notifier.clone())
+            .is_err());
+        assert!(io_mgr.deregister_mmio_ioevent(&instead).is_some());
+        assert!(io_mgr.deregister_mmio_ioevent(&before).is_some());
+
+        // Writes to addresses without a device succeed when a notifier handles them.
+        let doorbell = PioIoEvent::new(PioAddress(PIO_ADDRESS_BASE), Some(2), None).unwrap();
+        io_mgr
+            .register_pio_ioevent(doorbell, NotifyMode::InsteadOfDispatch, notifier)
+            .unwrap();
+        io_mgr
+            .pio_write(PioAddress(PIO_ADDRESS_BASE), &[0x1, 0x0])
+            .unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+        assert_eq!(
+            io_mgr.pio_write(PioAddress(PIO_ADDRESS_BASE), &[0x1]),
+            Err(bus::Error::DeviceNotFound)
+        );
+        assert!(io_mgr.deregister_pio_ioevent(&doorbell).is_some());
+    }
+
+    #[test]
+    fn test_coalesced_mmio() {
+        let mut io_mgr = IoManager::new();
+        let dum = Arc::new(DummyDevice::new(CONFIG_DATA));
+        let range = MmioRange::new(MmioAddress(MMIO_ADDRESS_BASE), MMIO_ADDRESS_SIZE).unwrap();
+        io_mgr
+            .register_coalesced_mmio(range, dum.clone(), 16)
+            .unwrap();
+        assert!(io_mgr
+            .register_coalesced_mmio(range, dum.clone(), 16)
+            .is_err());
+
+        io_mgr
+            .mmio_write(MmioAddress(MMIO_ADDRESS_BASE), &[0x56])
+            .unwrap();
+        io_mgr
+            .mmio_write(MmioAddress(MMIO_ADDRESS_BASE), &[0x78])
+            .unwrap();
+        assert_eq!(*dum.config.lock().unwrap(), CONFIG_DATA);
+
+        assert_eq!(io_mgr.flush_coalesced_mmio(), 2);
+        assert_eq!(*dum.config.lock().unwrap(), 0x78);
+
+        // Reads flush the pending writes first.
+        io_mgr
+            .mmio_write(MmioAddress(MMIO_ADDRESS_BASE), &[0x9a])
+            .unwrap();
+        let mut data = [0; 1];
+        io_mgr
+            .mmio_read(MmioAddress(MMIO_ADDRESS_BASE), &mut data)
+            .unwrap();
+        assert_eq!(data, [0x9a]);
+        assert_eq!(io_mgr.flush_coalesced_mmio(), 0);
+
+        // Deregistering the range delivers its pending writes.
+        io_mgr
+            .mmio_write(MmioAddress(MMIO_ADDRESS_BASE), &[0xbc])
+            .unwrap();
+        io_mgr
+            .deregister_mmio(MmioAddress(MMIO_ADDRESS_BASE))
+            .unwrap();
+        assert_eq!(*dum.config.lock().unwrap(), 0xbc);
+        assert!(io_mgr.coalesced_mmio.is_empty());
+        io_mgr.register_coalesced_mmio(range, dum, 16).unwrap();
+        assert_eq!(io_mgr.coalesced_mmio.len(), 1);
+    }
+
+    #[test]
+    fn test_batch() {
+        let mut io_mgr = IoManager::new();
+        let dum = Arc::new(DummyDevice::new(CONFIG_DATA));
+        let dum2 = Arc::new(DummyDevice::new(CONFIG_DATA));
+        let pio_range = PioRange::new(PioAddress(PIO_ADDRESS_BASE), PIO_ADDRESS_SIZE).unwrap();
+        let pio_range2 =
+            PioRange::new(PioAddress(PIO_ADDRESS_BASE + 0x10), PIO_ADDRESS_SIZE).unwrap();
+        io_mgr.register_pio(pio_range, dum.clone()).unwrap();
+        io_mgr.register_pio(pio_range2, dum2.clone()).unwrap();
+
+        let mut before = [0; 2];
+        let mut after = [0; 2];
+        let mut results = io_mgr.pio_batch(&mut [
+            PioOp::Read {
+                addr: PioAddress(PIO_ADDRESS_BASE),
+                data: &mut before,
+            },
+            PioOp::Write {
+                addr: PioAddress(PIO_ADDRESS_BASE + 1),
+                data: &[0x11],
+            },
+            PioOp::Write {
+                addr: PioAddress(PIO_ADDRESS_BASE + 8),
+                data: &[0x22],
+            },
+            PioOp::Write {
+                addr: PioAddress(PIO_ADDRESS_BASE + 0x10),
+                data: &[0x33],
+            },
+            // Operations are dispatched in order, whatever their addresses.
+            PioOp::Read {
+                addr: PioAddress(PIO_ADDRESS_BASE + 2),
+                data: &mut after,
+            },
+        ]);
+        assert_eq!(results.len(), 5);
+        assert_eq!(results.remove(2), Err(bus::Error::DeviceNotFound));
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(before, [0x34, 0x12]);
+        assert_eq!(after, [0x11, 0]);
+        assert_eq!(*dum2.config.lock().unwrap(), 0x33);
+
+        let mmio_range = MmioRange::new(MmioAddress(MMIO_ADDRESS_BASE), MMIO_ADDRESS_SIZE).unwrap();
+        io_mgr.register_mmio(mmio_range, dum.clone()).unwrap();
+        let mut data = [0; 1];
+        let results = io_mgr.mmio_batch(&mut [
+            MmioOp::Write {
+                addr: MmioAddress(MMIO_ADDRESS_BASE),
+                data: &[0x44],
+            },
+            MmioOp::Read {
+                addr: MmioAddress(MMIO_ADDRESS_BASE + 1),
+                data: &mut data,
+            },
+        ]);
+        assert_eq!(results, vec![Ok(()), Ok(())]);
+        assert_eq!(data, [0x44]);
+    }
+
+    #[test]
+    fn test_string_io() {
+        use crate::MutDevicePio;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // A FIFO which only accepts string accesses through its bulk handlers.
+        #[derive(Default)]
+        struct Fifo {
+            data: Vec<u8>,
+        }
+
+        impl MutDevicePio for Fifo {
+            fn pio_read(&mut self, _base: PioAddress, _offset: PioAddressOffset, _data: &mut [u8]) {
+                panic!("unexpected single read");
+            }
+
+            fn pio_write(&mut self, _base: PioAddress, _offset: PioAddressOffset, _data: &[u8]) {
+                panic!("unexpected single write");
+            }
+
+            fn pio_read_string(
+                &mut self,
+                _base: PioAddress,
+                _offset: PioAddressOffset,
+                _size: usize,
+                data: &mut [u8],
+            ) {
+                let len = data.len();
+                data.copy_from_slice(&self.data[..len]);
+                self.data.drain(..len);
+            }
+
+            fn pio_write_string(
+                &mut self,
+                _base: PioAddress,
+                _offset: PioAddressOffset,
+                _size: usize,
+                data: &[u8],
+            ) {
+                self.data.extend_from_slice(data);
+            }
+        }
+
+        let mut io_mgr = IoManager::new();
+        io_mgr.set_stats_enabled(true);
+        let fifo = Arc::new(Mutex::new(Fifo::default()));
+        let range = PioRange::new(PioAddress(PIO_ADDRESS_BASE), PIO_ADDRESS_SIZE).unwrap();
+        io_mgr.register_pio(range, fifo.clone()).unwrap();
+
+        let addr = PioAddress(PIO_ADDRESS_BASE + 2);
+        io_mgr
+            .pio_write_string(addr, 2, 3, &[1, 2, 3, 4, 5, 6])
+            .unwrap();
+        assert_eq!(fifo.lock().unwrap().data, vec![1, 2, 3, 4, 5, 6]);
+
+        let mut data = [0; 4];
+        io_mgr.pio_read_string(addr, 2, 2, &mut data).unwrap();
+        assert_eq!(data, [1, 2, 3, 4]);
+
+        let stats = io_mgr.pio_stats()[0].1;
+        assert_eq!(stats.writes, 3);
+        assert_eq!(stats.reads, 2);
+
+        assert_eq!(
+            io_mgr.pio_read_string(addr, 2, 3, &mut data),
+            Err(bus::Error::InvalidAccessLength(4))
+        );
+        assert_eq!(
+            io_mgr.pio_write_string(addr, 0, 0, &[]),
+            Err(bus::Error::InvalidAccessLength(0))
+        );
+        assert!(io_mgr.pio_write_string(addr, 4, 0, &[]).is_ok());
+        // Elements must fit within the device range.
+        assert_eq!(
+            io_mgr.pio_read_string(addr, 4, 1, &mut data),
+            Err(bus::Error::DeviceNotFound)
+        );
+
+        // Devices without a bulk handler get one access per element, and notifiers are
+        // signaled for each of them.
+        let dum = Arc::new(DummyDevice::new(CONFIG_DATA));
+        let range = PioRange::new(PioAddress(0x10), PIO_ADDRESS_SIZE).unwrap();
+        io_mgr.register_pio(range, dum.clone()).unwrap();
+        let count = Arc::new(AtomicUsize::new(0));
+        let count2 = count.clone();
+        let notifier: Arc<dyn Notifier + Send + Sync> = Arc::new(move || {
+            count2.fetch_add(1, Ordering::SeqCst);
+        });
+        let event = PioIoEvent::new(PioAddress(0x10), Some(1), None).unwrap();
+        io_mgr
+            .register_pio_ioevent(event, NotifyMode::BeforeDispatch, notifier)
+            .unwrap();
+
+        io_mgr
+            .pio_write_string(PioAddress(0x10), 1, 3, &[7, 8, 9])
+            .unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(*dum.config.lock().unwrap(), 9);
+
+        let mut data = [0; 2];
+        io_mgr
+            .pio_read_string(PioAddress(0x10), 1, 2, &mut data)
+            .unwrap();
+        assert_eq!(data, [9, 9]);
+
+        // Devices still get the whole string, which is traced element by element.
+        let sink = Arc::new(Mutex::new(Vec::new()));
+        io_mgr.set_trace_sink(Some(sink.clone()));
+        let fifo = Arc::new(Mutex::new(Fifo::default()));
+        let range = PioRange::new(PioAddress(0x20), PIO_ADDRESS_SIZE).unwrap();
+        io_mgr.register_pio(range, fifo.clone()).unwrap();
+        io_mgr
+            .pio_write_string(PioAddress(0x20), 2, 2, &[1, 2, 3, 4])
+            .unwrap();
+        io_mgr
+            .pio_read_string(PioAddress(0x20), 2, 1, &mut data)
+            .unwrap();
+        assert_eq!(data, [1, 2]);
+        assert_eq!(fifo.lock().unwrap().data, vec![3, 4]);
+
+        let accesses = sink.lock().unwrap();
+        let traced: Vec<_> = accesses
+            .iter()
+            .map(|access| (access.address, access.direction, access.data.clone()))
+            .collect();
+        assert_eq!(
+            traced,
+            vec![
+                (0x20, IoDirection::Write, vec![1, 2]),
+                (0x20, IoDirection::Write, vec![3, 4]),
+                (0x20, IoDirection::Read, vec![1, 2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_poison_policy() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        use std::sync::PoisonError;
+
+        use crate::{MutDeviceMmio, MutDevicePio};
+
+        // Panics when written 0xff.
+        struct FragileDevice {
+            config: u8,
+        }
+
+        impl MutDevicePio for FragileDevice {
+            fn pio_read(&mut self, _base: PioAddress, _offset: PioAddressOffset, data: &mut [u8]) {
+                data[0] = self.config;
+            }
+
+            fn pio_write(&mut self, _base: PioAddress, _offset: PioAddressOffset, data: &[u8]) {
+                assert_ne!(data[0], 0xff);
+                self.config = data[0];
+            }
+        }
+
+        impl MutDeviceMmio for FragileDevice {
+            fn mmio_read(
+                &mut self,
+                _base: MmioAddress,
+                _offset: MmioAddressOffset,
+                data: &mut [u8],
+            ) {
+                data[0] = self.config;
+            }
+
+            fn mmio_write(&mut self, _base: MmioAddress, _offset: MmioAddressOffset, data: &[u8]) {
+                assert_ne!(data[0], 0xff);
+                self.config = data[0];
+            }
+        }
+
+        let mut io_mgr = IoManager::new();
+        let device = Arc::new(Mutex::new(FragileDevice { config: 0 }));
+        let pio_addr = PioAddress(PIO_ADDRESS_BASE);
+        let mmio_addr = MmioAddress(MMIO_ADDRESS_BASE);
+        io_mgr
+            .register_pio(
+                PioRange::new(pio_addr, PIO_ADDRESS_SIZE).unwrap(),
+                device.clone(),
+            )
+            .unwrap();
+        io_mgr
+            .register_mmio(
+                MmioRange::new(mmio_addr, MMIO_ADDRESS_SIZE).unwrap(),
+                device.clone(),
+            )
+            .unwrap();
+
+        io_mgr.pio_write(pio_addr, &[1]).unwrap();
+        // Poison the device from another vCPU thread.
+        std::thread::scope(|s| {
+            assert!(s
+                .spawn(|| io_mgr.pio_write(pio_addr, &[0xff]))
+                .join()
+                .is_err());
+        });
+        assert!(device.is_poisoned());
+
+        // The state is recovered by default.
+        let mut data = [0; 1];
+        io_mgr.pio_read(pio_addr, &mut data).unwrap();
+        assert_eq!(data, [1]);
+        io_mgr.mmio_write(mmio_addr, &[2]).unwrap();
+
+        io_mgr.set_poison_policy(PoisonPolicy::Fail);
+        assert_eq!(
+            io_mgr.pio_read(pio_addr, &mut data),
+            Err(bus::Error::DevicePoisoned)
+        );
+        assert_eq!(
+            io_mgr.mmio_write(mmio_addr, &[3]),
+            Err(bus::Error::DevicePoisoned)
+        );
+        assert_eq!(
+            io_mgr.pio_write_string(pio_addr, 1, 2, &[3, 4]),
+            Err(bus::Error::DevicePoisoned)
+        );
+        assert_eq!(
+            io_mgr.mmio_batch(&mut [IoOp::Read {
+                addr: mmio_addr,
+                data: &mut data,
+            }]),
+            vec![Err(bus::Error::DevicePoisoned)]
+        );
+        assert_eq!(
+            device.lock().unwrap_or_else(PoisonError::into_inner).config,
+            2
+        );
+
+        io_mgr.set_poison_policy(PoisonPolicy::Panic);
+        assert!(catch_unwind(AssertUnwindSafe(|| io_mgr.pio_read(pio_addr, &mut data))).is_err());
+    }
+
+    // Minimal executor driving `future` to completion on the current thread.
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        use std::task::{Context, Poll, Wake, Waker};
+        use std::thread::{self, Thread};
+
+        struct ThreadWaker(Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_async_dispatch() {
+        use crate::async_io::IoFuture;
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+
+        // Completes on the second poll, as if waiting for a backend.
+        struct Yield(bool);
+
+        impl Future for Yield {
+            type Output = ();
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+                if self.0 {
+                    return Poll::Ready(());
+                }
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+
+        struct AsyncDevice {
+            config: Mutex<u8>,
+        }
+
+        impl AsyncDevicePio for AsyncDevice {
+            fn pio_read<'a>(
+                &'a self,
+                _base: PioAddress,
+                offset: PioAddressOffset,
+                data: &'a mut [u8],
+            ) -> IoFuture<'a> {
+                Box::pin(async move {
+                    Yield(false).await;
+                    data[0] = *self.config.lock().unwrap() + offset as u8;
+                })
+            }
+
+            fn pio_write<'a>(
+                &'a self,
+                _base: PioAddress,
+                _offset: PioAddressOffset,
+                data: &'a [u8],
+            ) -> IoFuture<'a> {
+                Box::pin(async move {
+                    Yield(false).await;
+                    *self.config.lock().unwrap() = data[0];
+                })
+            }
+        }
+
+        impl AsyncDeviceMmio for AsyncDevice {
+            fn mmio_read<'a>(
+                &'a self,
+                _base: MmioAddress,
+                offset: MmioAddressOffset,
+                data: &'a mut [u8],
+            ) -> IoFuture<'a> {
+                Box::pin(async move {
+                    data[0] = *self.config.lock().unwrap() + offset as u8;
+                })
+            }
+
+            fn mmio_write<'a>(
+                &'a self,
+                _base: MmioAddress,
+                _offset: MmioAddressOffset,
+                data: &'a [u8],
+            ) -> IoFuture<'a> {
+                Box::pin(async move {
+                    *self.config.lock().unwrap() = data[0];
+                })
+            }
+        }
+
+        let mut io_mgr = IoManager::new();
+        let dum = Arc::new(DummyDevice::new(CONFIG_DATA));
+        let async_dev = Arc::new(AsyncDevice {
+            config: Mutex::new(0),
+        });
+        let pio_range = PioRange::new(PioAddress(PIO_ADDRESS_BASE), PIO_ADDRESS_SIZE).unwrap();
+        let async_pio_range = PioRange::new(PioAddress(0x10), PIO_ADDRESS_SIZE).unwrap();
+        let mmio_range = MmioRange::new(MmioAddress(MMIO_ADDRESS_BASE), MMIO_ADDRESS_SIZE).unwrap();
+
+        io_mgr.register_pio(pio_range, dum.clone()).unwrap();
+        io_mgr
+            .register_async_pio(async_pio_range, async_dev.clone())
+            .unwrap();
+        io_mgr
+            .register_async_mmio(mmio_range, async_dev.clone())
+            .unwrap();
+        assert!(io_mgr
+            .register_async_pio(pio_range, async_dev.clone())
+            .is_err());
+        // Nor can synchronous devices be registered over asynchronous ones.
+        assert_eq!(
+            io_mgr.register_pio(async_pio_range, dum.clone()),
+            Err(bus::Error::DeviceOverlap)
+        );
+        assert!(io_mgr
+            .register_mmio_with_permissions(mmio_range, dum.clone(), Permissions::default())
+            .is_err());
+        assert!(io_mgr
+            .register_pio_aliases(
+                PioRange::new(PioAddress(0x40), PIO_ADDRESS_SIZE).unwrap(),
+                &[PioAddress(0x12)],
+                dum.clone()
+            )
+            .is_err());
+
+        // Asynchronous devices.
+        block_on(io_mgr.pio_write_async(PioAddress(0x10), &[0x20])).unwrap();
+        let mut data = [0; 1];
+        block_on(io_mgr.pio_read_async(PioAddress(0x12), &mut data)).unwrap();
+        assert_eq!(data, [0x22]);
+        block_on(io_mgr.mmio_write_async(MmioAddress(MMIO_ADDRESS_BASE), &[0x30])).unwrap();
+        block_on(io_mgr.mmio_read_async(MmioAddress(MMIO_ADDRESS_BASE + 1), &mut data)).unwrap();
+        assert_eq!(data, [0x31]);
+        assert_eq!(
+            block_on(io_mgr.pio_read_async(PioAddress(0x13), &mut [0; 2])),
+            Err(bus::Error::DeviceNotFound)
+        );
+
+        // Synchronous devices are reachable through the same methods.
+        block_on(io_mgr.pio_write_async(PioAddress(PIO_ADDRESS_BASE), &[0x40])).unwrap();
+        block_on(io_mgr.pio_read_async(PioAddress(PIO_ADDRESS_BASE), &mut data)).unwrap();
+        assert_eq!(data, [0x40]);
+        assert_eq!(
+            block_on(io_mgr.mmio_read_async(MmioAddress(0), &mut data)),
+            Err(bus::Error::DeviceNotFound)
+        );
+
+        // But asynchronous devices aren't reachable through the synchronous ones.
+        assert!(io_mgr.pio_read(PioAddress(0x10), &mut data).is_err());
+
+        assert!(io_mgr.deregister_async_pio(PioAddress(0x10)).is_some());
+        assert!(io_mgr
+            .deregister_async_mmio(MmioAddress(MMIO_ADDRESS_BASE))
+            .is_some());
+        assert!(block_on(io_mgr.pio_read_async(PioAddress(0x10), &mut data)).is_err());
+    }
+
+    #[test]
+    fn test_fdt() {
+        use crate::fdt::tests::parse;
+
+        struct VirtioMmio;
+
+        impl DeviceMmio for VirtioMmio {
+            fn mmio_read(&self, _base: MmioAddress, _offset: MmioAddressOffset, _data: &mut [u8]) {}
+            fn mmio_write(&self, _base: MmioAddress, _offset: MmioAddressOffset, _data: &[u8]) {}
+        }
+
+        impl DeviceFdt for VirtioMmio {
+            fn fdt_node_name(&self) -> &str {
+                "virtio_mmio"
+            }
+
+            fn fdt_compatible(&self) -> Vec<&str> {
+                vec!["virtio,mmio"]
+            }
+
+            // GIC shared peripheral interrupt, level triggered.
+            fn fdt_interrupt(&self, irq: u32) -> Vec<u32> {
+                vec![0, irq, 4]
+            }
+
+            fn fdt_properties(&self, fdt: &mut FdtWriter) -> Result<(), fdt::Error> {
+                fdt.property_null("dma-coherent")
+            }
+        }
+
+        struct Rtc;
+
+        impl DeviceMmio for Rtc {
+            fn mmio_read(&self, _base: MmioAddress, _offset: MmioAddressOffset, _data: &mut [u8]) {}
+            fn mmio_write(&self, _base: MmioAddress, _offset: MmioAddressOffset, _data: &[u8]) {}
+        }
+
+        impl DeviceFdt for Rtc {
+            fn fdt_node_name(&self) -> &str {
+                "rtc"
+            }
+
+            fn fdt_compatible(&self) -> Vec<&str> {
+                vec!["arm,pl031", "arm,primecell"]
+            }
+        }
+
+        let mut io_mgr = IoManager::new();
+        let virtio = vec![
+            Resource::MmioAddressRange {
+                base: 0xd000_0000,
+                size: 0x1000,
+            },
+            Resource::LegacyIrq(5),
+        ];
+        let rtc = vec![
+            Resource::MmioAddressRange {
+                base: 0x0901_0000,
+                size: 0x1000,
+            },
+            Resource::MmioAddressRange {
+                base: 0x0902_0000,
+                size: 0x100,
+            },
+        ];
+        io_mgr
+            .register_mmio_fdt(Arc::new(VirtioMmio), &virtio)
+            .unwrap();
+        io_mgr.register_mmio_fdt(Arc::new(Rtc), &rtc).unwrap();
+        // Devices which are not described have no node.
+        io_mgr
+            .register_mmio(
+                MmioRange::new(MmioAddress(0), 0x1000).unwrap(),
+                Arc::new(DummyDevice::new(0)),
+            )
+            .unwrap();
+        // Overlapping ranges are rejected before the device is described.
+        assert!(io_mgr.register_mmio_fdt(Arc::new(Rtc), &rtc[1..]).is_err());
+
+        let properties = parse(&io_mgr.generate_fdt().unwrap());
+        let expected: Vec<(&str, &str, Vec<u8>)> = vec![
+            ("/", "#address-cells", vec![0, 0, 0, 2]),
+            ("/", "#size-cells", vec![0, 0, 0, 2]),
+            (
+                "/rtc@9010000",
+                "compatible",
+                b"arm,pl031\0arm,primecell\0".to_vec(),
+            ),
+            (
+                "/rtc@9010000",
+                "reg",
+                [0x0901_0000u64, 0x1000, 0x0902_0000, 0x100]
+                    .iter()
+                    .flat_map(|v| v.to_be_bytes())
+                    .collect(),
+            ),
+            (
+                "/virtio_mmio@d0000000",
+                "compatible",
+                b"virtio,mmio\0".to_vec(),
+            ),
+            (
+                "/virtio_mmio@d0000000",
+                "reg",
+                vec![0, 0, 0, 0, 0xd0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10, 0],
+            ),
+            (
+                "/virtio_mmio@d0000000",
+                "interrupts",
+                vec![0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 4],
+            ),
+            ("/virtio_mmio@d0000000", "dma-coherent", vec![]),
+        ];
+        let expected: Vec<(String, String, Vec<u8>)> = expected
+            .into_iter()
+            .map(|(node, name, value)| (node.to_string(), name.to_string(), value))
+            .collect();
+        assert_eq!(properties, expected);
+
+        // Ranges which are no longer registered are left out, as are devices without any.
+        io_mgr.deregister_mmio(MmioAddress(0x0902_0000)).unwrap();
+        io_mgr.deregister_mmio(MmioAddress(0xd000_0000)).unwrap();
+        // Registering another device with the range of a forgotten one doesn't describe it.
+        io_mgr
+            .register_mmio(
+                MmioRange::new(MmioAddress(0xd000_0000), 0x1000).unwrap(),
+                Arc::new(DummyDevice::new(0)),
+            )
+            .unwrap();
+        let properties = parse(&io_mgr.generate_fdt().unwrap());
+        assert_eq!(properties.len(), 4);
+        assert_eq!(properties[3].2, expected[3].2[..16].to_vec());
+        assert_eq!(io_mgr.fdt_devices.len(), 1);
+    }
+
+    #[test]
+    fn test_dsdt() {
+        struct Serial(u32);
+
+        impl DevicePio for Serial {
+            fn pio_read(&self, _base: PioAddress, _offset: PioAddressOffset, _data: &mut [u8]) {}
+            fn pio_write(&self, _base: PioAddress, _offset: PioAddressOffset, _data: &[u8]) {}
+        }
+
+        impl DeviceAml for Serial {
+            fn aml_name(&self) -> &str {
+                if self.0 == 1 {
+                    "COM1"
+                } else {
+                    "COM2"
+                }
+            }
+
+            fn aml_hid(&self) -> &str {
+                "PNP0501"
+            }
+
+            fn aml_uid(&self) -> Option<u32> {
+                Some(self.0)
+            }
+        }
+
+        struct Ged;
+
+        impl DeviceMmio for Ged {
+            fn mmio_read(&self, _base: MmioAddress, _offset: MmioAddressOffset, _data: &mut [u8]) {}
+            fn mmio_write(&self, _base: MmioAddress, _offset: MmioAddressOffset, _data: &[u8]) {}
+        }
+
+        impl DeviceAml for Ged {
+            fn aml_name(&self) -> &str {
+                "GED"
+            }
+
+            fn aml_hid(&self) -> &str {
+                "ACPI0013"
+            }
+        }
+
+        let mut io_mgr = IoManager::new();
+        let com1 = vec![
+            Resource::PioAddressRange {
+                base: 0x3f8,
+                size: 8,
+            },
+            Resource::LegacyIrq(4),
+        ];
+        let com2 = vec![
+            Resource::PioAddressRange {
+                base: 0x2f8,
+                size: 8,
+            },
+            Resource::LegacyIrq(3),
+        ];
+        let ged = vec![
+            Resource::MmioAddressRange {
+                base: 0xfed0_0000,
+                size: 0x1000,
+            },
+            Resource::LegacyIrq(5),
+        ];
+        io_mgr.register_pio_aml(Arc::new(Serial(1)), &com1).unwrap();
+        io_mgr.register_mmio_aml(Arc::new(Ged), &ged).unwrap();
+        io_mgr.register_pio_aml(Arc::new(Serial(2)), &com2).unwrap();
+        assert!(io_mgr.register_pio_aml(Arc::new(Serial(3)), &com2).is_err());
+
+        let mut expected = acpi::device_aml(&Serial(1), &com1).unwrap();
+        expected.extend(acpi::device_aml(&Ged, &ged).unwrap());
+        expected.extend(acpi::device_aml(&Serial(2), &com2).unwrap());
+        assert_eq!(
+            io_mgr.generate_dsdt_body().unwrap(),
+            acpi::scope("_SB", &expected).unwrap()
+        );
+
+        // Devices which are no longer registered are left out, even when another device is
+        // registered with their range.
+        io_mgr.deregister_resources(&com1);
+        io_mgr.deregister_mmio(MmioAddress(0xfed0_0000)).unwrap();
+        io_mgr
+            .register_mmio(
+                MmioRange::new(MmioAddress(0xfed0_0000), 0x1000).unwrap(),
+                Arc::new(DummyDevice::new(0)),
+            )
+            .unwrap();
+        assert_eq!(
+            io_mgr.generate_dsdt_body().unwrap(),
+            acpi::scope("_SB", &acpi::device_aml(&Serial(2), &com2).unwrap()).unwrap()
+        );
+        assert_eq!(io_mgr.aml_devices.len(), 1);
+    }
+
+    #[test]
+    fn test_reserve_mmio_ranges() {
+        use crate::memory_map::{E820Entry, E820_RAM, E820_RESERVED};
+
+        let mut io_mgr = IoManager::new();
+        let device = Arc::new(DummyDevice::new(0));
+        io_mgr
+            .register_mmio(
+                MmioRange::new(MmioAddress(0xd000_0000), 0x1000).unwrap(),
+                device.clone(),
+            )
+            .unwrap();
+        io_mgr
+            .register_mmio_aliases(
+                MmioRange::new(MmioAddress(0xd000_1000), 0x1000).unwrap(),
+                &[MmioAddress(0xe000_0000)],
+                device.clone(),
+            )
+            .unwrap();
+
+        let mut map = MemoryMap::new();
+        map.add_ram(0, 0xc000_0000).unwrap();
+        io_mgr.reserve_mmio_ranges(&mut map).unwrap();
+        let entry = |addr, size, ty| E820Entry { addr, size, ty };
+        assert_eq!(
+            map.e820_entries(),
+            vec![
+                entry(0, 0xc000_0000, E820_RAM),
+                entry(0xd000_0000, 0x2000, E820_RESERVED),
+                entry(0xe000_0000, 0x1000, E820_RESERVED),
+            ]
+        );
+
+        // Device ranges cannot be presented as RAM.
+        io_mgr
+            .register_mmio(
+                MmioRange::new(MmioAddress(0xbfff_f000), 0x1000).unwrap(),
+                device,
+            )
+            .unwrap();
+        let mut map = MemoryMap::new();
+        map.add_ram(0, 0xc000_0000).unwrap();
+        assert!(matches!(
+            io_mgr.reserve_mmio_ranges(&mut map),
+            Err(memory_map::Error::Overlap(_))
+        ));
+    }
+
+    #[test]
+    fn test_reserve_resources() {
+        use crate::resources::MsiIrqType;
+
+        let mut io_mgr = IoManager::new();
+        let device = Arc::new(DummyDevice::new(0));
+        let ioapic = MmioRange::new(MmioAddress(0xfec0_0000), 0x1000).unwrap();
+        io_mgr.register_mmio(ioapic, device.clone()).unwrap();
+
+        let mut reserved = ReservedResources::new();
+        reserved.reserve_pio(0x20, 2);
+        reserved.reserve_mmio(0xfec0_0000, 0x10_0000);
+        reserved.reserve_irq(2);
+        io_mgr.reserve_resources(&reserved).unwrap();
+        // The platform device registered before is kept.
+        assert!(io_mgr.mmio_bus.device(MmioAddress(0xfec0_0000)).is_some());
+
+        assert!(matches!(
+            io_mgr.register_pio(PioRange::new(PioAddress(0x21), 1).unwrap(), device.clone()),
+            Err(bus::Error::RangeReserved)
+        ));
+        assert!(matches!(
+            io_mgr.register_mmio_resources(
+                device.clone(),
+                &[Resource::MmioAddressRange {
+                    base: 0xfec0_1000,
+                    size: 0x1000
+                }]
+            ),
+            Err(super::Error::Bus(bus::Error::RangeReserved))
+        ));
+        assert!(matches!(
+            io_mgr.register_coalesced_mmio(
+                MmioRange::new(MmioAddress(0xfecf_f000), 0x1000).unwrap(),
+                device.clone(),
+                8
+            ),
+            Err(super::Error::Bus(bus::Error::RangeReserved))
+        ));
+
+        assert!(io_mgr
+            .async_mmio_bus
+            .is_reserved(&MmioRange::new(MmioAddress(0xfec8_0000), 0x1000).unwrap()));
+
+        // Reserved IRQs are rejected before any range gets registered.
+        let resources = [
+            Resource::PioAddressRange {
+                base: 0x3f8,
+                size: 8,
+            },
+            Resource::MsiIrq {
+                ty: MsiIrqType::GenericMsi,
+                base: 0,
+                size: 4,
+            },
+        ];
+        assert!(matches!(
+            io_mgr.register_pio_resources(device.clone(), &resources),
+            Err(super::Error::ReservedIrq(2))
+        ));
+        assert!(io_mgr.pio_bus.device(PioAddress(0x3f8)).is_none());
+        io_mgr
+            .register_pio_resources(device, &resources[..1])
+            .unwrap();
+
+        let mut invalid = ReservedResources::new();
+        invalid.reserve_mmio(0x1000, 0);
+        assert!(matches!(
+            io_mgr.reserve_resources(&invalid),
+            Err(super::Error::Bus(bus::Error::InvalidRange))
+        ));
+    }
+
     #[test]
     fn test_error_code() {
         let err = super::Error::Bus(bus::Error::DeviceOverlap);
@@ -0,0 +1,425 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! Flattened device tree (FDT) generation.
+//!
+//! Platforms without ACPI discover their devices through a device tree handed over by the
+//! VMM. [`FdtWriter`] serializes such a tree into the flattened format (DTB, version 17)
+//! expected by guest kernels, and devices implementing [`DeviceFdt`] describe the node
+//! they expect to be represented by.
+//!
+//! Devices registered with
+//! [`IoManager::register_mmio_fdt`](../device_manager/struct.IoManager.html#method.register_mmio_fdt)
+//! get their nodes generated by
+//! [`IoManager::generate_fdt`](../device_manager/struct.IoManager.html#method.generate_fdt),
+//! with the `reg` and `interrupts` properties built from the ranges they are registered
+//! with and the IRQs they are assigned, so the tree cannot drift from what is dispatched.
+//!
+//! # Example
+//!
+//! ```
+//! # use vm_device::fdt::FdtWriter;
+//! let mut fdt = FdtWriter::new();
+//! fdt.property_u32("#address-cells", 2).unwrap();
+//! fdt.property_u32("#size-cells", 2).unwrap();
+//!
+//! fdt.begin_node("chosen").unwrap();
+//! fdt.property_string("bootargs", "console=ttyS0").unwrap();
+//! fdt.end_node().unwrap();
+//!
+//! let blob = fdt.finish().unwrap();
+//! assert_eq!(&blob[..4], &[0xd0, 0x0d, 0xfe, 0xed]);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_VERSION: u32 = 17;
+const FDT_LAST_COMP_VERSION: u32 = 16;
+const FDT_HEADER_SIZE: usize = 40;
+// A single terminating entry, as no memory is reserved.
+const FDT_MEM_RSVMAP_SIZE: usize = 16;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_END: u32 = 0x9;
+
+/// Errors encountered while building a device tree.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The node or property name is empty or contains forbidden characters.
+    InvalidName(String),
+    /// A string property value contains a NUL character.
+    InvalidString,
+    /// Properties must be added to a node before its subnodes.
+    PropertyAfterNode,
+    /// The root node cannot be ended explicitly.
+    NoOpenNode,
+    /// A node was not ended before finishing the tree.
+    UnclosedNode,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidName(name) => write!(f, "invalid node or property name: {:?}", name),
+            Error::InvalidString => write!(f, "string property contains a NUL character"),
+            Error::PropertyAfterNode => write!(f, "property added after a subnode"),
+            Error::NoOpenNode => write!(f, "no open node to end"),
+            Error::UnclosedNode => write!(f, "node left open"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Description of the device tree node of a device.
+///
+/// The `compatible`, `reg` and `interrupts` properties are generated by the
+/// [`IoManager`](../device_manager/struct.IoManager.html), the device only needs to provide
+/// its identity and any additional property.
+pub trait DeviceFdt {
+    /// Return the name of the node, without the unit address (e.g. `"virtio_mmio"`).
+    fn fdt_node_name(&self) -> &str;
+
+    /// Return the values of the `compatible` property, from the most to the least specific.
+    fn fdt_compatible(&self) -> Vec<&str>;
+
+    /// Encode `irq` as the cells of an `interrupts` entry, in the format expected by the
+    /// interrupt parent of the node. Defaults to a single cell holding `irq`.
+    fn fdt_interrupt(&self, irq: u32) -> Vec<u32> {
+        vec![irq]
+    }
+
+    /// Add the device specific properties to the node, which is the current node of `fdt`.
+    fn fdt_properties(&self, _fdt: &mut FdtWriter) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Builder of flattened device tree blobs.
+///
+/// The root node is open when the writer is created. Nodes are added with
+/// [`begin_node`](#method.begin_node) and [`end_node`](#method.end_node), and properties
+/// are added to the current node before any of its subnodes.
+pub struct FdtWriter {
+    structure: Vec<u8>,
+    strings: Vec<u8>,
+    string_offsets: HashMap<String, u32>,
+    // Number of open nodes, including the root.
+    depth: usize,
+    // Whether the current node already has subnodes.
+    has_subnodes: bool,
+}
+
+impl Default for FdtWriter {
+    fn default() -> Self {
+        let mut fdt = FdtWriter {
+            structure: Vec::new(),
+            strings: Vec::new(),
+            string_offsets: HashMap::new(),
+            depth: 1,
+            has_subnodes: false,
+        };
+        fdt.append_u32(FDT_BEGIN_NODE);
+        fdt.append_string("");
+        fdt
+    }
+}
+
+impl FdtWriter {
+    /// Create a writer with an empty root node.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a subnode of the current node, named `name` (e.g. `"serial@3f8"`).
+    pub fn begin_node(&mut self, name: &str) -> Result<(), Error> {
+        check_name(name)?;
+        self.append_u32(FDT_BEGIN_NODE);
+        self.append_string(name);
+        self.depth += 1;
+        self.has_subnodes = false;
+        Ok(())
+    }
+
+    /// End the current node, going back to its parent.
+    pub fn end_node(&mut self) -> Result<(), Error> {
+        if self.depth <= 1 {
+            return Err(Error::NoOpenNode);
+        }
+        self.append_u32(FDT_END_NODE);
+        self.depth -= 1;
+        self.has_subnodes = true;
+        Ok(())
+    }
+
+    /// Add a property with a raw value to the current node.
+    pub fn property(&mut self, name: &str, value: &[u8]) -> Result<(), Error> {
+        check_name(name)?;
+        if self.has_subnodes {
+            return Err(Error::PropertyAfterNode);
+        }
+        let name_offset = self.string_offset(name);
+        self.append_u32(FDT_PROP);
+        self.append_u32(value.len() as u32);
+        self.append_u32(name_offset);
+        self.structure.extend_from_slice(value);
+        self.align();
+        Ok(())
+    }
+
+    /// Add a property without value, used as a flag (e.g. `"dma-coherent"`).
+    pub fn property_null(&mut self, name: &str) -> Result<(), Error> {
+        self.property(name, &[])
+    }
+
+    /// Add a property holding a single cell.
+    pub fn property_u32(&mut self, name: &str, value: u32) -> Result<(), Error> {
+        self.property(name, &value.to_be_bytes())
+    }
+
+    /// Add a property holding a 64-bit value, stored as two cells.
+    pub fn property_u64(&mut self, name: &str, value: u64) -> Result<(), Error> {
+        self.property(name, &value.to_be_bytes())
+    }
+
+    /// Add a property holding a list of cells.
+    pub fn property_array_u32(&mut self, name: &str, values: &[u32]) -> Result<(), Error> {
+        let value: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        self.property(name, &value)
+    }
+
+    /// Add a property holding a list of 64-bit values, each stored as two cells.
+    pub fn property_array_u64(&mut self, name: &str, values: &[u64]) -> Result<(), Error> {
+        let value: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        self.property(name, &value)
+    }
+
+    /// Add a property holding a string.
+    pub fn property_string(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        self.property_string_list(name, &[value])
+    }
+
+    /// Add a property holding a list of strings.
+    pub fn property_string_list(&mut self, name: &str, values: &[&str]) -> Result<(), Error> {
+        let mut value = Vec::new();
+        for s in values {
+            if s.contains('\0') {
+                return Err(Error::InvalidString);
+            }
+            value.extend_from_slice(s.as_bytes());
+            value.push(0);
+        }
+        self.property(name, &value)
+    }
+
+    /// End the root node and return the flattened device tree blob.
+    pub fn finish(mut self) -> Result<Vec<u8>, Error> {
+        if self.depth != 1 {
+            return Err(Error::UnclosedNode);
+        }
+        self.append_u32(FDT_END_NODE);
+        self.append_u32(FDT_END);
+
+        let off_mem_rsvmap = FDT_HEADER_SIZE;
+        let off_dt_struct = off_mem_rsvmap + FDT_MEM_RSVMAP_SIZE;
+        let off_dt_strings = off_dt_struct + self.structure.len();
+        let total_size = off_dt_strings + self.strings.len();
+
+        let mut blob = Vec::with_capacity(total_size);
+        for field in &[
+            FDT_MAGIC,
+            total_size as u32,
+            off_dt_struct as u32,
+            off_dt_strings as u32,
+            off_mem_rsvmap as u32,
+            FDT_VERSION,
+            FDT_LAST_COMP_VERSION,
+            // boot_cpuid_phys
+            0,
+            self.strings.len() as u32,
+            self.structure.len() as u32,
+        ] {
+            blob.extend_from_slice(&field.to_be_bytes());
+        }
+        blob.resize(off_dt_struct, 0);
+        blob.extend_from_slice(&self.structure);
+        blob.extend_from_slice(&self.strings);
+        Ok(blob)
+    }
+
+    fn append_u32(&mut self, value: u32) {
+        self.structure.extend_from_slice(&value.to_be_bytes());
+    }
+
+    // Append a NUL terminated string, padded to the next cell.
+    fn append_string(&mut self, s: &str) {
+        self.structure.extend_from_slice(s.as_bytes());
+        self.structure.push(0);
+        self.align();
+    }
+
+    fn align(&mut self) {
+        let len = (self.structure.len() + 3) & !3;
+        self.structure.resize(len, 0);
+    }
+
+    // Return the offset of `name` in the strings block, adding it if needed.
+    fn string_offset(&mut self, name: &str) -> u32 {
+        if let Some(offset) = self.string_offsets.get(name) {
+            return *offset;
+        }
+        let offset = self.strings.len() as u32;
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        self.string_offsets.insert(name.to_string(), offset);
+        offset
+    }
+}
+
+fn check_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.contains(['\0', '/']) {
+        return Err(Error::InvalidName(name.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use std::convert::TryInto;
+
+    fn read_u32(blob: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(blob[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_str(blob: &[u8], offset: usize) -> &str {
+        let len = blob[offset..].iter().position(|b| *b == 0).unwrap();
+        std::str::from_utf8(&blob[offset..offset + len]).unwrap()
+    }
+
+    // Parse `blob` into the list of its properties, as (node path, name, value) tuples.
+    pub(crate) fn parse(blob: &[u8]) -> Vec<(String, String, Vec<u8>)> {
+        assert_eq!(read_u32(blob, 0), FDT_MAGIC);
+        assert_eq!(read_u32(blob, 4) as usize, blob.len());
+        let off_dt_struct = read_u32(blob, 8) as usize;
+        let off_dt_strings = read_u32(blob, 12) as usize;
+
+        let mut properties = Vec::new();
+        let mut path: Vec<String> = Vec::new();
+        let mut offset = off_dt_struct;
+        loop {
+            let token = read_u32(blob, offset);
+            offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = read_str(blob, offset);
+                    offset = (offset + name.len() + 4) & !3;
+                    path.push(name.to_string());
+                }
+                FDT_END_NODE => {
+                    path.pop().unwrap();
+                }
+                FDT_PROP => {
+                    let len = read_u32(blob, offset) as usize;
+                    let name = read_str(blob, off_dt_strings + read_u32(blob, offset + 4) as usize);
+                    offset += 8;
+                    let node = if path.len() == 1 {
+                        "/".to_string()
+                    } else {
+                        path.join("/")
+                    };
+                    properties.push((node, name.to_string(), blob[offset..offset + len].to_vec()));
+                    offset = (offset + len + 3) & !3;
+                }
+                FDT_END => break,
+                _ => panic!("unexpected token {}", token),
+            }
+        }
+        assert!(path.is_empty());
+        properties
+    }
+
+    #[test]
+    fn test_fdt_writer() {
+        let mut fdt = FdtWriter::new();
+        fdt.property_u32("#address-cells", 2).unwrap();
+        fdt.property_string_list("compatible", &["linux,dummy-virt", "virt"])
+            .unwrap();
+        fdt.begin_node("memory@80000000").unwrap();
+        fdt.property_string("device_type", "memory").unwrap();
+        fdt.property_array_u64("reg", &[0x8000_0000, 0x1000_0000])
+            .unwrap();
+        fdt.end_node().unwrap();
+        fdt.begin_node("psci").unwrap();
+        fdt.property_null("dma-coherent").unwrap();
+        fdt.property_u64("phandle", 1).unwrap();
+        fdt.property_array_u32("cells", &[1, 2]).unwrap();
+        fdt.end_node().unwrap();
+
+        // Errors.
+        assert_eq!(fdt.property_u32("late", 1), Err(Error::PropertyAfterNode));
+        assert_eq!(fdt.end_node(), Err(Error::NoOpenNode));
+        assert_eq!(
+            fdt.begin_node("a/b"),
+            Err(Error::InvalidName("a/b".to_string()))
+        );
+        assert_eq!(fdt.begin_node(""), Err(Error::InvalidName(String::new())));
+        fdt.begin_node("chosen").unwrap();
+        assert_eq!(
+            fdt.property_string("bootargs", "a\0b"),
+            Err(Error::InvalidString)
+        );
+        assert_eq!(
+            FdtWriter::new().property("", &[]),
+            Err(Error::InvalidName(String::new()))
+        );
+        let mut unclosed = FdtWriter::new();
+        unclosed.begin_node("chosen").unwrap();
+        assert_eq!(unclosed.finish(), Err(Error::UnclosedNode));
+        fdt.end_node().unwrap();
+
+        let blob = fdt.finish().unwrap();
+        assert_eq!(read_u32(&blob, 20), FDT_VERSION);
+        assert_eq!(read_u32(&blob, 24), FDT_LAST_COMP_VERSION);
+        // The memory reservation map only holds the terminating entry.
+        assert_eq!(read_u32(&blob, 16) as usize, FDT_HEADER_SIZE);
+        assert!(blob[FDT_HEADER_SIZE..FDT_HEADER_SIZE + FDT_MEM_RSVMAP_SIZE]
+            .iter()
+            .all(|b| *b == 0));
+
+        let properties = parse(&blob);
+        let expected: Vec<(&str, &str, Vec<u8>)> = vec![
+            ("/", "#address-cells", vec![0, 0, 0, 2]),
+            ("/", "compatible", b"linux,dummy-virt\0virt\0".to_vec()),
+            ("/memory@80000000", "device_type", b"memory\0".to_vec()),
+            (
+                "/memory@80000000",
+                "reg",
+                vec![0, 0, 0, 0, 0x80, 0, 0, 0, 0, 0, 0, 0, 0x10, 0, 0, 0],
+            ),
+            ("/psci", "dma-coherent", vec![]),
+            ("/psci", "phandle", vec![0, 0, 0, 0, 0, 0, 0, 1]),
+            ("/psci", "cells", vec![0, 0, 0, 1, 0, 0, 0, 2]),
+        ];
+        assert_eq!(
+            properties,
+            expected
+                .into_iter()
+                .map(|(node, name, value)| (node.to_string(), name.to_string(), value))
+                .collect::<Vec<_>>()
+        );
+
+        // Property names are stored once in the strings block.
+        let strings = &blob[read_u32(&blob, 12) as usize..];
+        assert_eq!(read_u32(&blob, 32) as usize, strings.len());
+        assert_eq!(
+            strings,
+            &b"#address-cells\0compatible\0device_type\0reg\0dma-coherent\0phandle\0cells\0"[..]
+        );
+    }
+}
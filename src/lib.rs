@@ -6,14 +6,15 @@
 //! This crate provides:
 //! * device traits defining read and write operations on specialized buses
 //! * device manager (bus-specific traits and a concrete implementation) for
-//! operating devices and dispatching I/O
+//!   operating devices and dispatching I/O
 //! * abstractions for defining resources and their constraints (e.g. a specific bus
-//! address range, IRQ number, etc)
+//!   address range, IRQ number, etc)
 //!
 //! [`MutDevicePio`] and [`MutDeviceMmio`] traits help with composite inner mutability
 //! (i.e. if we have a `Mutex` that holds a `T` which implements [`MutDevicePio`],
 //! then the `Mutex` can implement [`DevicePio`] based on its inner
-//! mutability properties).
+//! mutability properties). `RwLock` provides the same implementations, while `Arc`,
+//! `Box` and references forward to the device they point to.
 //!
 //! # Example
 //!
@@ -50,12 +51,23 @@
 //! manager.pio_write(PioAddress(0), &vec![b'o', b'k']).unwrap();
 //! ```
 
+pub mod acpi;
+pub mod async_io;
+pub mod bridge;
 pub mod bus;
+pub mod cmdline;
+pub mod coalesced;
+pub mod composite;
 pub mod device_manager;
+pub mod fdt;
+pub mod memory_map;
+pub mod pio_over_mmio;
+pub mod replay;
 pub mod resources;
+pub mod trace;
 
-use std::ops::Deref;
-use std::sync::{Arc, Mutex};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
 
 use bus::{MmioAddress, MmioAddressOffset, PioAddress, PioAddressOffset};
 
@@ -105,6 +117,64 @@ pub trait DevicePio {
     /// * `offset`: base address' offset
     /// * `data`:   a buffer provided by the caller holding the data to write
     fn pio_write(&self, base: PioAddress, offset: PioAddressOffset, data: &[u8]);
+
+    /// Handle a string read operation (`rep ins`) on the device, where consecutive
+    /// elements of `size` bytes are all read from the same address.
+    ///
+    /// The default implementation calls [`pio_read`](#tymethod.pio_read) for each element;
+    /// devices can override it with a faster bulk handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `base`:   base address on a PIO bus
+    /// * `offset`: base address' offset
+    /// * `size`:   size of each element, which `data.len()` is a multiple of
+    /// * `data`:   a buffer provided by the caller to store the read elements
+    fn pio_read_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &mut [u8],
+    ) {
+        for element in data.chunks_mut(size) {
+            self.pio_read(base, offset, element);
+        }
+    }
+
+    /// Handle a string write operation (`rep outs`) to the device, where consecutive
+    /// elements of `size` bytes are all written to the same address.
+    ///
+    /// The default implementation calls [`pio_write`](#tymethod.pio_write) for each
+    /// element; devices can override it with a faster bulk handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `base`:   base address on a PIO bus
+    /// * `offset`: base address' offset
+    /// * `size`:   size of each element, which `data.len()` is a multiple of
+    /// * `data`:   a buffer provided by the caller holding the elements to write
+    fn pio_write_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &[u8],
+    ) {
+        for element in data.chunks(size) {
+            self.pio_write(base, offset, element);
+        }
+    }
+
+    /// Check whether the device is poisoned, i.e. a previous access panicked and left it
+    /// in a state which may be inconsistent. The device managers check this before every
+    /// access and act according to the [`PoisonPolicy`](bus/enum.PoisonPolicy.html) of
+    /// the bus.
+    ///
+    /// The default implementation returns `false`.
+    fn pio_poisoned(&self) -> bool {
+        false
+    }
 }
 
 /// Allows a device to be attached to a
@@ -153,6 +223,16 @@ pub trait DeviceMmio {
     /// * `offset`: base address' offset
     /// * `data`:   a buffer provided by the caller holding the data to write
     fn mmio_write(&self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]);
+
+    /// Check whether the device is poisoned, i.e. a previous access panicked and left it
+    /// in a state which may be inconsistent. The device managers check this before every
+    /// access and act according to the [`PoisonPolicy`](bus/enum.PoisonPolicy.html) of
+    /// the bus.
+    ///
+    /// The default implementation returns `false`.
+    fn mmio_poisoned(&self) -> bool {
+        false
+    }
 }
 
 /// Same as [DevicePio] but the methods are invoked with a mutable self borrow.
@@ -197,6 +277,54 @@ pub trait MutDevicePio {
     /// * `offset`: base address' offset
     /// * `data`:   a buffer provided by the caller holding the data to write
     fn pio_write(&mut self, base: PioAddress, offset: PioAddressOffset, data: &[u8]);
+
+    /// Handle a string read operation (`rep ins`) on the device, where consecutive
+    /// elements of `size` bytes are all read from the same address.
+    ///
+    /// The default implementation calls [`pio_read`](#tymethod.pio_read) for each element;
+    /// devices can override it with a faster bulk handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `base`:   base address on a PIO bus
+    /// * `offset`: base address' offset
+    /// * `size`:   size of each element, which `data.len()` is a multiple of
+    /// * `data`:   a buffer provided by the caller to store the read elements
+    fn pio_read_string(
+        &mut self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &mut [u8],
+    ) {
+        for element in data.chunks_mut(size) {
+            self.pio_read(base, offset, element);
+        }
+    }
+
+    /// Handle a string write operation (`rep outs`) to the device, where consecutive
+    /// elements of `size` bytes are all written to the same address.
+    ///
+    /// The default implementation calls [`pio_write`](#tymethod.pio_write) for each
+    /// element; devices can override it with a faster bulk handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `base`:   base address on a PIO bus
+    /// * `offset`: base address' offset
+    /// * `size`:   size of each element, which `data.len()` is a multiple of
+    /// * `data`:   a buffer provided by the caller holding the elements to write
+    fn pio_write_string(
+        &mut self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &[u8],
+    ) {
+        for element in data.chunks(size) {
+            self.pio_write(base, offset, element);
+        }
+    }
 }
 
 /// Same as [DeviceMmio] but the methods are invoked with a mutable self borrow.
@@ -252,6 +380,10 @@ impl<T: DeviceMmio + ?Sized> DeviceMmio for Arc<T> {
     fn mmio_write(&self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
         self.deref().mmio_write(base, offset, data);
     }
+
+    fn mmio_poisoned(&self) -> bool {
+        self.deref().mmio_poisoned()
+    }
 }
 
 impl<T: DevicePio + ?Sized> DevicePio for Arc<T> {
@@ -262,26 +394,447 @@ impl<T: DevicePio + ?Sized> DevicePio for Arc<T> {
     fn pio_write(&self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
         self.deref().pio_write(base, offset, data);
     }
+
+    fn pio_read_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &mut [u8],
+    ) {
+        self.deref().pio_read_string(base, offset, size, data);
+    }
+
+    fn pio_write_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &[u8],
+    ) {
+        self.deref().pio_write_string(base, offset, size, data);
+    }
+
+    fn pio_poisoned(&self) -> bool {
+        self.deref().pio_poisoned()
+    }
+}
+
+// Blanket implementations for Box<T>.
+
+impl<T: DeviceMmio + ?Sized> DeviceMmio for Box<T> {
+    fn mmio_read(&self, base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+        self.deref().mmio_read(base, offset, data);
+    }
+
+    fn mmio_write(&self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+        self.deref().mmio_write(base, offset, data);
+    }
+
+    fn mmio_poisoned(&self) -> bool {
+        self.deref().mmio_poisoned()
+    }
+}
+
+impl<T: DevicePio + ?Sized> DevicePio for Box<T> {
+    fn pio_read(&self, base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
+        self.deref().pio_read(base, offset, data);
+    }
+
+    fn pio_write(&self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
+        self.deref().pio_write(base, offset, data);
+    }
+
+    fn pio_read_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &mut [u8],
+    ) {
+        self.deref().pio_read_string(base, offset, size, data);
+    }
+
+    fn pio_write_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &[u8],
+    ) {
+        self.deref().pio_write_string(base, offset, size, data);
+    }
+
+    fn pio_poisoned(&self) -> bool {
+        self.deref().pio_poisoned()
+    }
+}
+
+impl<T: MutDeviceMmio + ?Sized> MutDeviceMmio for Box<T> {
+    fn mmio_read(&mut self, base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+        self.deref_mut().mmio_read(base, offset, data);
+    }
+
+    fn mmio_write(&mut self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+        self.deref_mut().mmio_write(base, offset, data);
+    }
+}
+
+impl<T: MutDevicePio + ?Sized> MutDevicePio for Box<T> {
+    fn pio_read(&mut self, base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
+        self.deref_mut().pio_read(base, offset, data);
+    }
+
+    fn pio_write(&mut self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
+        self.deref_mut().pio_write(base, offset, data);
+    }
+
+    fn pio_read_string(
+        &mut self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &mut [u8],
+    ) {
+        self.deref_mut().pio_read_string(base, offset, size, data);
+    }
+
+    fn pio_write_string(
+        &mut self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &[u8],
+    ) {
+        self.deref_mut().pio_write_string(base, offset, size, data);
+    }
+}
+
+// Blanket implementations for references.
+
+impl<T: DeviceMmio + ?Sized> DeviceMmio for &T {
+    fn mmio_read(&self, base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+        (**self).mmio_read(base, offset, data);
+    }
+
+    fn mmio_write(&self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+        (**self).mmio_write(base, offset, data);
+    }
+
+    fn mmio_poisoned(&self) -> bool {
+        (**self).mmio_poisoned()
+    }
+}
+
+impl<T: DevicePio + ?Sized> DevicePio for &T {
+    fn pio_read(&self, base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
+        (**self).pio_read(base, offset, data);
+    }
+
+    fn pio_write(&self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
+        (**self).pio_write(base, offset, data);
+    }
+
+    fn pio_read_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &mut [u8],
+    ) {
+        (**self).pio_read_string(base, offset, size, data);
+    }
+
+    fn pio_write_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &[u8],
+    ) {
+        (**self).pio_write_string(base, offset, size, data);
+    }
+
+    fn pio_poisoned(&self) -> bool {
+        (**self).pio_poisoned()
+    }
+}
+
+impl<T: MutDeviceMmio + ?Sized> MutDeviceMmio for &mut T {
+    fn mmio_read(&mut self, base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+        (**self).mmio_read(base, offset, data);
+    }
+
+    fn mmio_write(&mut self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+        (**self).mmio_write(base, offset, data);
+    }
+}
+
+impl<T: MutDevicePio + ?Sized> MutDevicePio for &mut T {
+    fn pio_read(&mut self, base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
+        (**self).pio_read(base, offset, data);
+    }
+
+    fn pio_write(&mut self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
+        (**self).pio_write(base, offset, data);
+    }
+
+    fn pio_read_string(
+        &mut self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &mut [u8],
+    ) {
+        (**self).pio_read_string(base, offset, size, data);
+    }
+
+    fn pio_write_string(
+        &mut self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &[u8],
+    ) {
+        (**self).pio_write_string(base, offset, size, data);
+    }
 }
 
 // Blanket implementations for Mutex<T>.
+//
+// A device which panicked while handling an access on another thread leaves its lock
+// poisoned. The inner state is recovered instead of propagating the panic to every
+// subsequent access, and the device reports itself as poisoned so that the device
+// managers can apply their poison policy.
 
 impl<T: MutDeviceMmio + ?Sized> DeviceMmio for Mutex<T> {
     fn mmio_read(&self, base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
-        self.lock().unwrap().mmio_read(base, offset, data)
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .mmio_read(base, offset, data);
     }
 
     fn mmio_write(&self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
-        self.lock().unwrap().mmio_write(base, offset, data)
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .mmio_write(base, offset, data);
+    }
+
+    fn mmio_poisoned(&self) -> bool {
+        self.is_poisoned()
     }
 }
 
 impl<T: MutDevicePio + ?Sized> DevicePio for Mutex<T> {
     fn pio_read(&self, base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
-        self.lock().unwrap().pio_read(base, offset, data)
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pio_read(base, offset, data);
+    }
+
+    fn pio_write(&self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pio_write(base, offset, data);
+    }
+
+    fn pio_read_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &mut [u8],
+    ) {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pio_read_string(base, offset, size, data);
+    }
+
+    fn pio_write_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &[u8],
+    ) {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pio_write_string(base, offset, size, data);
+    }
+
+    fn pio_poisoned(&self) -> bool {
+        self.is_poisoned()
+    }
+}
+
+// Blanket implementations for RwLock<T>. Every access takes the write lock, since the
+// inner device is mutably borrowed.
+
+impl<T: MutDeviceMmio + ?Sized> DeviceMmio for RwLock<T> {
+    fn mmio_read(&self, base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+        self.write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .mmio_read(base, offset, data);
+    }
+
+    fn mmio_write(&self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+        self.write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .mmio_write(base, offset, data);
+    }
+
+    fn mmio_poisoned(&self) -> bool {
+        self.is_poisoned()
+    }
+}
+
+impl<T: MutDevicePio + ?Sized> DevicePio for RwLock<T> {
+    fn pio_read(&self, base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
+        self.write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pio_read(base, offset, data);
     }
 
     fn pio_write(&self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
-        self.lock().unwrap().pio_write(base, offset, data)
+        self.write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pio_write(base, offset, data);
+    }
+
+    fn pio_read_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &mut [u8],
+    ) {
+        self.write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pio_read_string(base, offset, size, data);
+    }
+
+    fn pio_write_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &[u8],
+    ) {
+        self.write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pio_write_string(base, offset, size, data);
+    }
+
+    fn pio_poisoned(&self) -> bool {
+        self.is_poisoned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct DummyDevice {
+        config: u8,
+    }
+
+    impl MutDevicePio for DummyDevice {
+        fn pio_read(&mut self, _base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
+            data[0] = self.config + offset as u8;
+        }
+
+        fn pio_write(&mut self, _base: PioAddress, _offset: PioAddressOffset, data: &[u8]) {
+            self.config = data[0];
+        }
+    }
+
+    impl MutDeviceMmio for DummyDevice {
+        fn mmio_read(&mut self, _base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+            data[0] = self.config + offset as u8;
+        }
+
+        fn mmio_write(&mut self, _base: MmioAddress, _offset: MmioAddressOffset, data: &[u8]) {
+            self.config = data[0];
+        }
+    }
+
+    fn check_pio<T: DevicePio>(device: T) {
+        let mut data = [0; 1];
+        device.pio_write(PioAddress(0), 0, &[1]);
+        device.pio_read(PioAddress(0), 1, &mut data);
+        assert_eq!(data, [2]);
+        device.pio_write_string(PioAddress(0), 0, 1, &[3, 4]);
+        device.pio_read(PioAddress(0), 0, &mut data);
+        assert_eq!(data, [4]);
+    }
+
+    fn check_mmio<T: DeviceMmio>(device: T) {
+        let mut data = [0; 1];
+        device.mmio_write(MmioAddress(0), 0, &[1]);
+        device.mmio_read(MmioAddress(0), 1, &mut data);
+        assert_eq!(data, [2]);
+    }
+
+    fn check_mut_pio<T: MutDevicePio>(mut device: T) {
+        let mut data = [0; 2];
+        device.pio_write(PioAddress(0), 0, &[1]);
+        device.pio_read_string(PioAddress(0), 1, 1, &mut data);
+        assert_eq!(data, [2, 2]);
+    }
+
+    fn check_mut_mmio<T: MutDeviceMmio>(mut device: T) {
+        let mut data = [0; 1];
+        device.mmio_write(MmioAddress(0), 0, &[1]);
+        device.mmio_read(MmioAddress(0), 1, &mut data);
+        assert_eq!(data, [2]);
+    }
+
+    #[test]
+    fn test_blanket_impls() {
+        assert!(!Mutex::new(DummyDevice::default()).pio_poisoned());
+        check_pio(Mutex::new(DummyDevice::default()));
+        check_mmio(Mutex::new(DummyDevice::default()));
+        check_pio(RwLock::new(DummyDevice::default()));
+        check_mmio(RwLock::new(DummyDevice::default()));
+        check_pio(Arc::new(RwLock::new(DummyDevice::default())));
+        check_mmio(Box::new(RwLock::new(DummyDevice::default())));
+
+        let device = Mutex::new(DummyDevice::default());
+        check_pio(&device);
+        check_mmio(&device);
+        let boxed: Box<dyn DevicePio> = Box::new(Mutex::new(DummyDevice::default()));
+        check_pio(boxed);
+
+        let mut device = DummyDevice::default();
+        check_mut_pio(&mut device);
+        check_mut_mmio(&mut device);
+        check_mut_pio(Box::new(DummyDevice::default()));
+        let boxed: Box<dyn MutDeviceMmio> = Box::new(DummyDevice::default());
+        check_pio(Mutex::new(Box::new(DummyDevice::default())));
+        check_mmio(Mutex::new(boxed));
+    }
+
+    #[test]
+    fn test_poisoned_lock() {
+        let mutex = Mutex::new(DummyDevice::default());
+        let rwlock = RwLock::new(DummyDevice::default());
+        std::thread::scope(|s| {
+            let _ = s
+                .spawn(|| {
+                    let _guard = mutex.lock().unwrap();
+                    let _write = rwlock.write().unwrap();
+                    panic!("device panicked");
+                })
+                .join();
+        });
+        assert!(mutex.is_poisoned());
+        assert!(rwlock.is_poisoned());
+
+        // Accesses keep reaching the device.
+        assert!(mutex.pio_poisoned());
+        assert!(Arc::new(&rwlock).mmio_poisoned());
+        check_pio(&mutex);
+        check_mmio(&rwlock);
     }
 }
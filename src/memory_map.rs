@@ -0,0 +1,328 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! Guest physical memory map generation.
+//!
+//! A [`MemoryMap`] combines the RAM regions of a guest with the reserved windows of its
+//! devices, and produces the E820 table handed to x86 guests or the memory nodes of a device
+//! tree. Adding a region which overlaps RAM fails, so the map never presents device ranges
+//! as usable memory.
+//!
+//! The reserved windows typically come from two places: the ranges the MMIO bus dispatches,
+//! added by [`IoManager::reserve_mmio_ranges`](../device_manager/struct.IoManager.html#method.reserve_mmio_ranges),
+//! and the windows the VMM hands to its resource allocator for devices which are not
+//! registered yet (such as the PCI MMIO hole), added with
+//! [`add_reserved`](struct.MemoryMap.html#method.add_reserved).
+//!
+//! # Example
+//!
+//! ```
+//! # use vm_device::memory_map::{MemoryMap, E820_RESERVED};
+//! let mut map = MemoryMap::new();
+//! map.add_ram(0, 0xc000_0000).unwrap();
+//! map.add_reserved(0xfec0_0000, 0x1000).unwrap();
+//! map.add_ram(0x1_0000_0000, 0x4000_0000).unwrap();
+//! // The reserved window cannot be used as RAM.
+//! assert!(map.add_ram(0xfe00_0000, 0x100_0000).is_err());
+//!
+//! let table = map.e820_entries();
+//! assert_eq!(table.len(), 3);
+//! assert_eq!({ table[1].ty }, E820_RESERVED);
+//! ```
+
+use std::fmt::{Display, Formatter};
+
+use crate::fdt::{self, FdtWriter};
+
+/// Type of E820 entries describing usable RAM.
+pub const E820_RAM: u32 = 1;
+/// Type of E820 entries describing reserved memory.
+pub const E820_RESERVED: u32 = 2;
+
+/// Errors encountered while building a memory map.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The region is empty or its last address overflows.
+    InvalidRegion,
+    /// The region overlaps the provided region, and one of them is RAM.
+    Overlap(MemoryRegion),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidRegion => write!(f, "invalid memory region"),
+            Error::Overlap(region) => write!(
+                f,
+                "memory region overlaps {:?} region at {:#x}",
+                region.region_type(),
+                region.base()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Type of a memory region.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RegionType {
+    /// Memory usable by the guest.
+    Ram,
+    /// Memory the guest must not use, such as device windows.
+    Reserved,
+}
+
+/// A region of guest physical memory.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MemoryRegion {
+    base: u64,
+    size: u64,
+    ty: RegionType,
+}
+
+impl MemoryRegion {
+    /// Create a region, if it is not empty and its last address does not overflow.
+    pub fn new(base: u64, size: u64, ty: RegionType) -> Option<Self> {
+        if size == 0 || base.checked_add(size - 1).is_none() {
+            return None;
+        }
+        Some(MemoryRegion { base, size, ty })
+    }
+
+    /// Return the first address of the region.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Return the size of the region.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Return the last address of the region.
+    pub fn last(&self) -> u64 {
+        self.base + (self.size - 1)
+    }
+
+    /// Return the type of the region.
+    pub fn region_type(&self) -> RegionType {
+        self.ty
+    }
+
+    /// Check whether the region overlaps `other`.
+    pub fn overlaps(&self, other: &MemoryRegion) -> bool {
+        self.base <= other.last() && other.base <= self.last()
+    }
+}
+
+/// An entry of the E820 table, laid out like the `boot_e820_entry` of the Linux boot
+/// protocol.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct E820Entry {
+    /// First address of the region.
+    pub addr: u64,
+    /// Size of the region.
+    pub size: u64,
+    /// Type of the region ([`E820_RAM`] or [`E820_RESERVED`]).
+    pub ty: u32,
+}
+
+/// The memory map of a guest.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryMap {
+    regions: Vec<MemoryRegion>,
+}
+
+impl MemoryMap {
+    /// Create an empty memory map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a region of RAM, which must not overlap any region already in the map.
+    pub fn add_ram(&mut self, base: u64, size: u64) -> Result<(), Error> {
+        self.add(base, size, RegionType::Ram)
+    }
+
+    /// Add a reserved region, which must not overlap RAM. Reserved regions can overlap each
+    /// other, in which case they are merged.
+    pub fn add_reserved(&mut self, base: u64, size: u64) -> Result<(), Error> {
+        self.add(base, size, RegionType::Reserved)
+    }
+
+    fn add(&mut self, base: u64, size: u64, ty: RegionType) -> Result<(), Error> {
+        let region = MemoryRegion::new(base, size, ty).ok_or(Error::InvalidRegion)?;
+        if let Some(other) = self.regions.iter().find(|other| {
+            region.overlaps(other) && (ty == RegionType::Ram || other.ty == RegionType::Ram)
+        }) {
+            return Err(Error::Overlap(*other));
+        }
+        self.regions.push(region);
+        Ok(())
+    }
+
+    /// Return the regions of the map in address order, with the overlapping and adjacent
+    /// regions of the same type merged.
+    pub fn regions(&self) -> Vec<MemoryRegion> {
+        let mut sorted = self.regions.clone();
+        sorted.sort_by_key(|region| region.base);
+
+        let mut merged: Vec<MemoryRegion> = Vec::new();
+        for region in sorted {
+            if let Some(prev) = merged.last_mut() {
+                let contiguous = prev.last() == u64::MAX || prev.last() + 1 >= region.base;
+                if prev.ty == region.ty && contiguous {
+                    let last = prev.last().max(region.last());
+                    prev.size = last - prev.base + 1;
+                    continue;
+                }
+            }
+            merged.push(region);
+        }
+        merged
+    }
+
+    /// Return the E820 table describing the map.
+    pub fn e820_entries(&self) -> Vec<E820Entry> {
+        self.regions()
+            .into_iter()
+            .map(|region| E820Entry {
+                addr: region.base,
+                size: region.size,
+                ty: match region.ty {
+                    RegionType::Ram => E820_RAM,
+                    RegionType::Reserved => E820_RESERVED,
+                },
+            })
+            .collect()
+    }
+
+    /// Write a `memory` node for every RAM region of the map as subnodes of the current
+    /// node of `fdt`, which is expected to use two cells for addresses and sizes. Reserved
+    /// regions are simply left out of the device tree.
+    pub fn write_fdt_memory_nodes(&self, fdt: &mut FdtWriter) -> Result<(), fdt::Error> {
+        for region in self
+            .regions()
+            .into_iter()
+            .filter(|region| region.ty == RegionType::Ram)
+        {
+            fdt.begin_node(&format!("memory@{:x}", region.base))?;
+            fdt.property_string("device_type", "memory")?;
+            fdt.property_array_u64("reg", &[region.base, region.size])?;
+            fdt.end_node()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fdt::tests::parse;
+
+    #[test]
+    fn test_memory_region() {
+        assert!(MemoryRegion::new(0, 0, RegionType::Ram).is_none());
+        assert!(MemoryRegion::new(u64::MAX, 2, RegionType::Ram).is_none());
+        let region = MemoryRegion::new(u64::MAX, 1, RegionType::Reserved).unwrap();
+        assert_eq!(region.last(), u64::MAX);
+        assert_eq!(region.region_type(), RegionType::Reserved);
+
+        let ram = MemoryRegion::new(0x1000, 0x1000, RegionType::Ram).unwrap();
+        assert!(ram.overlaps(&MemoryRegion::new(0x1fff, 1, RegionType::Ram).unwrap()));
+        assert!(!ram.overlaps(&MemoryRegion::new(0x2000, 1, RegionType::Ram).unwrap()));
+        assert!(!ram.overlaps(&MemoryRegion::new(0, 0x1000, RegionType::Ram).unwrap()));
+    }
+
+    #[test]
+    fn test_memory_map() {
+        let mut map = MemoryMap::new();
+        map.add_reserved(0xfec0_0000, 0x1000).unwrap();
+        map.add_reserved(0xfec0_0800, 0x1000).unwrap();
+        map.add_reserved(0xfee0_0000, 0x1000).unwrap();
+        map.add_ram(0x10_0000, 0xbff0_0000).unwrap();
+        map.add_ram(0, 0xa_0000).unwrap();
+        // Adjacent RAM regions are merged.
+        map.add_ram(0x1_0000_0000, 0x1000_0000).unwrap();
+        map.add_ram(0x1_1000_0000, 0x1000_0000).unwrap();
+
+        let reserved = MemoryRegion::new(0xfec0_0000, 0x1000, RegionType::Reserved).unwrap();
+        assert_eq!(
+            map.add_ram(0xfe00_0000, 0x100_0000),
+            Err(Error::Overlap(reserved))
+        );
+        let ram = MemoryRegion::new(0, 0xa_0000, RegionType::Ram).unwrap();
+        assert_eq!(map.add_reserved(0x9_f000, 0x2000), Err(Error::Overlap(ram)));
+        assert_eq!(map.add_ram(0, 1), Err(Error::Overlap(ram)));
+        assert_eq!(map.add_ram(2, u64::MAX), Err(Error::InvalidRegion));
+
+        let entry = |addr, size, ty| E820Entry { addr, size, ty };
+        assert_eq!(
+            map.e820_entries(),
+            vec![
+                entry(0, 0xa_0000, E820_RAM),
+                entry(0x10_0000, 0xbff0_0000, E820_RAM),
+                entry(0xfec0_0000, 0x1800, E820_RESERVED),
+                entry(0xfee0_0000, 0x1000, E820_RESERVED),
+                entry(0x1_0000_0000, 0x2000_0000, E820_RAM),
+            ]
+        );
+        assert_eq!(std::mem::size_of::<E820Entry>(), 20);
+
+        // Regions reaching the end of the address space.
+        let mut map = MemoryMap::new();
+        map.add_reserved(u64::MAX - 0xfff, 0x1000).unwrap();
+        map.add_reserved(u64::MAX, 1).unwrap();
+        assert_eq!(
+            map.regions(),
+            vec![MemoryRegion::new(u64::MAX - 0xfff, 0x1000, RegionType::Reserved).unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_fdt_memory_nodes() {
+        let mut map = MemoryMap::new();
+        map.add_ram(0x8000_0000, 0x1000_0000).unwrap();
+        map.add_reserved(0x9000_0000, 0x1000).unwrap();
+        map.add_ram(0x1_0000_0000, 0x1000).unwrap();
+
+        let mut fdt = FdtWriter::new();
+        map.write_fdt_memory_nodes(&mut fdt).unwrap();
+        let properties = parse(&fdt.finish().unwrap());
+        let reg = |base: u64, size: u64| {
+            base.to_be_bytes()
+                .iter()
+                .chain(size.to_be_bytes().iter())
+                .copied()
+                .collect::<Vec<u8>>()
+        };
+        assert_eq!(
+            properties,
+            vec![
+                (
+                    "/memory@80000000".to_string(),
+                    "device_type".to_string(),
+                    b"memory\0".to_vec()
+                ),
+                (
+                    "/memory@80000000".to_string(),
+                    "reg".to_string(),
+                    reg(0x8000_0000, 0x1000_0000)
+                ),
+                (
+                    "/memory@100000000".to_string(),
+                    "device_type".to_string(),
+                    b"memory\0".to_vec()
+                ),
+                (
+                    "/memory@100000000".to_string(),
+                    "reg".to_string(),
+                    reg(0x1_0000_0000, 0x1000)
+                ),
+            ]
+        );
+    }
+}
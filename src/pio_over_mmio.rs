@@ -0,0 +1,209 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! Port I/O devices exposed on the MMIO bus.
+//!
+//! Architectures such as aarch64 and riscv have no port I/O instructions, so legacy devices
+//! (an 8250 UART, an RTC, etc) are mapped into a MMIO window instead. [`PioOverMmio`] wraps
+//! any [`DevicePio`] and implements [`DeviceMmio`] on top of it, so the same device
+//! implementation can be registered on the PIO bus on x86 and on the MMIO bus elsewhere.
+//!
+//! Registers are spread over the window with a stride of `1 << shift` bytes, matching the
+//! `reg-shift` property of device tree nodes. An access at `offset` within the window
+//! reaches the port `offset >> shift` of the device, with the access width unchanged.
+//! Accesses which do not start on a register boundary read zeros and writes to them are
+//! ignored.
+//!
+//! The shift is the only layout parameter: like `reg-shift`, it describes the stride, while
+//! the register width (`reg-io-width`) is carried by each access and needs no configuration.
+//! Strides which are not powers of two are not used by such devices, so there is no
+//! separate stride parameter that could disagree with the shift.
+//!
+//! # Example
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use vm_device::bus::{MmioAddress, MmioRange, PioAddress, PioAddressOffset};
+//! # use vm_device::device_manager::{IoManager, MmioManager};
+//! # use vm_device::pio_over_mmio::PioOverMmio;
+//! # use vm_device::DevicePio;
+//! struct Uart;
+//!
+//! impl DevicePio for Uart {
+//!     fn pio_read(&self, _base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
+//!         // Line status register: transmitter empty.
+//!         data[0] = if offset == 5 { 0x60 } else { 0 };
+//!     }
+//!     fn pio_write(&self, _base: PioAddress, _offset: PioAddressOffset, _data: &[u8]) {}
+//! }
+//!
+//! // Registers are 4 bytes apart in the MMIO window.
+//! let uart = PioOverMmio::new(Uart, PioAddress(0x3f8), 2).unwrap();
+//! let mut manager = IoManager::new();
+//! let window = MmioRange::new(MmioAddress(0x0900_0000), 0x20).unwrap();
+//! manager.register_mmio(window, Arc::new(uart)).unwrap();
+//!
+//! let mut data = [0; 1];
+//! manager.mmio_read(MmioAddress(0x0900_0014), &mut data).unwrap();
+//! assert_eq!(data, [0x60]);
+//! ```
+
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+
+use crate::bus::{MmioAddress, MmioAddressOffset, PioAddress, PioAddressOffset};
+use crate::{DeviceMmio, DevicePio};
+
+/// Largest register shift, for which the stride still fits a MMIO offset.
+pub const MAX_SHIFT: u32 = 63;
+
+/// Errors encountered while creating a [`PioOverMmio`] wrapper.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The register shift is larger than [`MAX_SHIFT`].
+    InvalidShift(u32),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidShift(shift) => write!(f, "invalid register shift ({})", shift),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Wrapper exposing a PIO device as a MMIO device.
+pub struct PioOverMmio<T> {
+    inner: T,
+    pio_base: PioAddress,
+    shift: u32,
+}
+
+impl<T: DevicePio> PioOverMmio<T> {
+    /// Expose `inner` on the MMIO bus, with its registers `1 << shift` bytes apart.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner`: the wrapped device
+    /// * `pio_base`: base address the device is handed, as if it was on a PIO bus
+    /// * `shift`: log2 of the distance between registers in the MMIO window, failing with
+    ///   [`Error::InvalidShift`] if larger than [`MAX_SHIFT`]
+    pub fn new(inner: T, pio_base: PioAddress, shift: u32) -> Result<Self, Error> {
+        if shift > MAX_SHIFT {
+            return Err(Error::InvalidShift(shift));
+        }
+        Ok(PioOverMmio {
+            inner,
+            pio_base,
+            shift,
+        })
+    }
+
+    /// Return a reference to the wrapped device.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Return the distance in bytes between consecutive registers in the MMIO window.
+    pub fn stride(&self) -> MmioAddressOffset {
+        1 << self.shift
+    }
+
+    /// Translate `offset` within the MMIO window into the offset of the register it starts
+    /// at, if it starts on a register boundary and the register exists on a PIO bus.
+    pub fn port_offset(&self, offset: MmioAddressOffset) -> Option<PioAddressOffset> {
+        if offset & (self.stride() - 1) != 0 {
+            return None;
+        }
+        PioAddressOffset::try_from(offset >> self.shift).ok()
+    }
+}
+
+impl<T: DevicePio> DeviceMmio for PioOverMmio<T> {
+    fn mmio_read(&self, _base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+        match self.port_offset(offset) {
+            Some(port) => self.inner.pio_read(self.pio_base, port, data),
+            None => data.fill(0),
+        }
+    }
+
+    fn mmio_write(&self, _base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+        if let Some(port) = self.port_offset(offset) {
+            self.inner.pio_write(self.pio_base, port, data);
+        }
+    }
+
+    fn mmio_poisoned(&self) -> bool {
+        self.inner.pio_poisoned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    // Logs the writes it receives, and reads back the low byte of the offset.
+    #[derive(Default)]
+    struct LogDevice {
+        writes: Mutex<Vec<(PioAddress, PioAddressOffset, Vec<u8>)>>,
+    }
+
+    impl DevicePio for LogDevice {
+        fn pio_read(&self, _base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
+            data.fill(offset as u8);
+        }
+
+        fn pio_write(&self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
+            self.writes
+                .lock()
+                .unwrap()
+                .push((base, offset, data.to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_pio_over_mmio() {
+        let base = MmioAddress(0x1000);
+
+        // Dense mapping.
+        let device = PioOverMmio::new(LogDevice::default(), PioAddress(0x70), 0).unwrap();
+        assert_eq!(device.stride(), 1);
+        let mut data = [0; 2];
+        device.mmio_read(base, 1, &mut data);
+        assert_eq!(data, [1, 1]);
+        assert_eq!(device.port_offset(0x1_0000), None);
+
+        // Registers 4 bytes apart.
+        let device = PioOverMmio::new(LogDevice::default(), PioAddress(0x3f8), 2).unwrap();
+        assert_eq!(device.stride(), 4);
+        assert_eq!(device.port_offset(0x14), Some(5));
+        assert_eq!(device.port_offset(0x15), None);
+
+        let mut data = [0xff; 4];
+        device.mmio_read(base, 0x14, &mut data);
+        assert_eq!(data, [5; 4]);
+        device.mmio_read(base, 0x16, &mut data[..1]);
+        assert_eq!(data[0], 0);
+
+        device.mmio_write(base, 0x8, &[1]);
+        device.mmio_write(base, 0x9, &[2]);
+        assert_eq!(
+            *device.inner().writes.lock().unwrap(),
+            vec![(PioAddress(0x3f8), 2, vec![1])]
+        );
+        assert!(!device.mmio_poisoned());
+
+        // The stride must fit the window offsets.
+        let device = PioOverMmio::new(LogDevice::default(), PioAddress(0), MAX_SHIFT).unwrap();
+        assert_eq!(device.stride(), 1 << 63);
+        assert_eq!(device.port_offset(1 << 63), Some(1));
+        assert!(matches!(
+            PioOverMmio::new(LogDevice::default(), PioAddress(0), 64),
+            Err(Error::InvalidShift(64))
+        ));
+    }
+}
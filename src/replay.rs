@@ -0,0 +1,771 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! Record and replay of bus traffic.
+//!
+//! A [`Recorder`] is a [`TraceSink`] which encodes the accesses it receives into a compact
+//! binary log. Installing it with
+//! [`IoManager::set_trace_sink`](../device_manager/struct.IoManager.html#method.set_trace_sink)
+//! captures every access dispatched by the manager, at the address used by the guest and
+//! along with its result and the data returned by reads. Failed accesses and writes handled
+//! by I/O events are part of the log as well. The log can later be fed to [`replay`], which
+//! issues the same sequence of accesses on another manager and stops at the first access
+//! whose result or read data differs.
+//!
+//! # Log format
+//!
+//! The log starts with the [`MAGIC`] bytes followed by a one byte [`VERSION`]. Each record
+//! is then encoded as a tag byte (bit 0 set for writes, bit 1 set for MMIO, bit 2 set for
+//! failed accesses), the absolute address (2 bytes for PIO, 8 bytes for MMIO, little
+//! endian), the data length and the data itself. Failed accesses end with the code of their
+//! [`bus::Error`], followed by the length for
+//! [`InvalidAccessLength`](../bus/enum.Error.html#variant.InvalidAccessLength) errors. The length is encoded in LEB128 (7 bits per byte, least significant group first,
+//! bit 7 set on all bytes but the last), so records of any size the buses accept can be
+//! stored. The data is read incrementally, so a corrupt length cannot make the reader
+//! allocate more memory than the log holds.
+//!
+//! # Example
+//!
+//! ```
+//! # use std::sync::{Arc, Mutex};
+//! # use vm_device::bus::{PioAddress, PioAddressOffset, PioRange};
+//! # use vm_device::device_manager::{IoManager, PioManager};
+//! # use vm_device::replay::{replay, Recorder};
+//! # use vm_device::MutDevicePio;
+//! struct Register(u8);
+//!
+//! impl MutDevicePio for Register {
+//!     fn pio_read(&mut self, _base: PioAddress, _offset: PioAddressOffset, data: &mut [u8]) {
+//!         data[0] = self.0;
+//!     }
+//!     fn pio_write(&mut self, _base: PioAddress, _offset: PioAddressOffset, data: &[u8]) {
+//!         self.0 = data[0];
+//!     }
+//! }
+//!
+//! let range = PioRange::new(PioAddress(0x70), 1).unwrap();
+//! let recorder = Arc::new(Recorder::new(Vec::new()).unwrap());
+//!
+//! let mut manager = IoManager::new();
+//! manager.set_trace_sink(Some(recorder.clone()));
+//! manager
+//!     .register_pio(range, Arc::new(Mutex::new(Register(0))))
+//!     .unwrap();
+//! manager.pio_write(PioAddress(0x70), &[0x42]).unwrap();
+//! manager.pio_read(PioAddress(0x70), &mut [0]).unwrap();
+//! drop(manager);
+//!
+//! let log = Arc::try_unwrap(recorder).ok().unwrap().into_inner().unwrap();
+//!
+//! // Replay the log against a freshly built manager.
+//! let mut manager = IoManager::new();
+//! manager
+//!     .register_pio(range, Arc::new(Mutex::new(Register(0))))
+//!     .unwrap();
+//! assert_eq!(replay(log.as_slice(), &manager).unwrap(), 2);
+//! ```
+
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::io::{self, Read, Write};
+use std::result::Result;
+use std::sync::Mutex;
+
+use crate::bus::{self, IoDirection, MmioAddress, PioAddress};
+use crate::device_manager::{MmioManager, PioManager};
+use crate::trace::{IoAccess, IoBus, TraceSink};
+
+/// Bytes identifying the start of a log.
+pub const MAGIC: [u8; 4] = *b"VMDR";
+/// Version of the log format.
+pub const VERSION: u8 = 1;
+
+const TAG_WRITE: u8 = 1 << 0;
+const TAG_MMIO: u8 = 1 << 1;
+const TAG_FAILED: u8 = 1 << 2;
+
+/// Errors encountered while decoding or replaying a log.
+#[derive(Debug)]
+pub enum Error {
+    /// The log does not start with a supported header.
+    InvalidHeader,
+    /// The log contains a malformed record.
+    InvalidRecord,
+    /// Error while reading or writing the log.
+    Io(io::Error),
+    /// An access completed differently during replay.
+    Divergence(Divergence),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidHeader => write!(f, "replay: invalid log header"),
+            Error::InvalidRecord => write!(f, "replay: invalid log record"),
+            Error::Io(_) => write!(f, "replay: log I/O error"),
+            Error::Divergence(d) => write!(f, "replay: divergence at record {}", d.index),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A single access stored in a log.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Record {
+    /// Bus the access was issued on.
+    pub bus: IoBus,
+    /// Absolute bus address of the access.
+    pub address: u64,
+    /// Direction of the access.
+    pub direction: IoDirection,
+    /// Data written to the device, or returned by the device for reads.
+    pub data: Vec<u8>,
+    /// Result of the access.
+    pub result: Result<(), bus::Error>,
+}
+
+impl Record {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut tag = 0;
+        if self.direction == IoDirection::Write {
+            tag |= TAG_WRITE;
+        }
+        if self.bus == IoBus::Mmio {
+            tag |= TAG_MMIO;
+        }
+        if self.result.is_err() {
+            tag |= TAG_FAILED;
+        }
+        writer.write_all(&[tag])?;
+        match self.bus {
+            // PIO records are created from `PioAddress` values, so this is lossless.
+            IoBus::Pio => writer.write_all(&(self.address as u16).to_le_bytes())?,
+            IoBus::Mmio => writer.write_all(&self.address.to_le_bytes())?,
+        }
+        // `usize` is at most 64 bits wide on the supported platforms.
+        write_len(writer, self.data.len() as u64)?;
+        writer.write_all(&self.data)?;
+        match self.result {
+            Ok(()) => Ok(()),
+            Err(ref error) => write_error(writer, error),
+        }
+    }
+
+    // Returns `Ok(None)` when the reader is exhausted right at a record boundary.
+    fn read_from<R: Read>(reader: &mut R) -> Result<Option<Self>, Error> {
+        let mut tag = [0u8; 1];
+        if reader.read(&mut tag).map_err(Error::Io)? == 0 {
+            return Ok(None);
+        }
+        let tag = tag[0];
+        if tag & !(TAG_WRITE | TAG_MMIO | TAG_FAILED) != 0 {
+            return Err(Error::InvalidRecord);
+        }
+
+        let direction = if tag & TAG_WRITE != 0 {
+            IoDirection::Write
+        } else {
+            IoDirection::Read
+        };
+        let (bus, address) = if tag & TAG_MMIO != 0 {
+            let mut bytes = [0u8; 8];
+            read_exact(reader, &mut bytes)?;
+            (IoBus::Mmio, u64::from_le_bytes(bytes))
+        } else {
+            let mut bytes = [0u8; 2];
+            read_exact(reader, &mut bytes)?;
+            (IoBus::Pio, u64::from(u16::from_le_bytes(bytes)))
+        };
+
+        let len = read_len(reader)?;
+        let mut data = Vec::new();
+        reader
+            .by_ref()
+            .take(len)
+            .read_to_end(&mut data)
+            .map_err(Error::Io)?;
+        if u64::try_from(data.len()) != Ok(len) {
+            return Err(Error::InvalidRecord);
+        }
+        let result = if tag & TAG_FAILED != 0 {
+            Err(read_error(reader)?)
+        } else {
+            Ok(())
+        };
+
+        Ok(Some(Record {
+            bus,
+            address,
+            direction,
+            data,
+            result,
+        }))
+    }
+}
+
+impl From<IoAccess> for Record {
+    fn from(access: IoAccess) -> Self {
+        Record {
+            bus: access.bus,
+            address: access.address,
+            direction: access.direction,
+            data: access.data,
+            result: access.result,
+        }
+    }
+}
+
+// Write `len` in LEB128.
+fn write_len<W: Write>(writer: &mut W, mut len: u64) -> io::Result<()> {
+    loop {
+        let byte = (len & 0x7f) as u8;
+        len >>= 7;
+        if len == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+// Read a length encoded in LEB128, rejecting values which don't fit 64 bits.
+fn read_len<R: Read>(reader: &mut R) -> Result<u64, Error> {
+    let mut len = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8; 1];
+        read_exact(reader, &mut byte)?;
+        let bits = u64::from(byte[0] & 0x7f);
+        if bits.checked_shl(shift).map(|v| v >> shift) != Some(bits) {
+            return Err(Error::InvalidRecord);
+        }
+        len |= bits << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(len);
+        }
+    }
+    Err(Error::InvalidRecord)
+}
+
+// Write the code of `error`, followed by its length for invalid access lengths.
+fn write_error<W: Write>(writer: &mut W, error: &bus::Error) -> io::Result<()> {
+    let code = match error {
+        bus::Error::DeviceNotFound => 1,
+        bus::Error::DeviceOverlap => 2,
+        bus::Error::InvalidAccessLength(len) => {
+            writer.write_all(&[3])?;
+            return write_len(writer, *len as u64);
+        }
+        bus::Error::InvalidRange => 4,
+        bus::Error::InvalidIoEvent => 5,
+        bus::Error::IoEventExists => 6,
+        bus::Error::DevicePoisoned => 7,
+        bus::Error::AccessDenied => 8,
+        bus::Error::RangeReserved => 9,
+    };
+    writer.write_all(&[code])
+}
+
+// Read an error written by `write_error`.
+fn read_error<R: Read>(reader: &mut R) -> Result<bus::Error, Error> {
+    let mut code = [0u8; 1];
+    read_exact(reader, &mut code)?;
+    Ok(match code[0] {
+        1 => bus::Error::DeviceNotFound,
+        2 => bus::Error::DeviceOverlap,
+        3 => {
+            let len = usize::try_from(read_len(reader)?).map_err(|_| Error::InvalidRecord)?;
+            bus::Error::InvalidAccessLength(len)
+        }
+        4 => bus::Error::InvalidRange,
+        5 => bus::Error::InvalidIoEvent,
+        6 => bus::Error::IoEventExists,
+        7 => bus::Error::DevicePoisoned,
+        8 => bus::Error::AccessDenied,
+        9 => bus::Error::RangeReserved,
+        _ => return Err(Error::InvalidRecord),
+    })
+}
+
+// Truncated records are reported as invalid rather than as I/O errors.
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), Error> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => Error::InvalidRecord,
+        _ => Error::Io(e),
+    })
+}
+
+/// Describes the first access whose result, or data for reads, differs from the recorded one.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Divergence {
+    /// Index of the record within the log.
+    pub index: usize,
+    /// The recorded access.
+    pub record: Record,
+    /// Data returned during replay.
+    pub actual: Vec<u8>,
+    /// Result of the access during replay.
+    pub result: Result<(), bus::Error>,
+}
+
+struct RecorderState<W> {
+    writer: W,
+    // `TraceSink::record` cannot fail, so the first error is kept and stops the recording.
+    error: Option<io::Error>,
+}
+
+/// [`TraceSink`] which encodes the accesses it receives into a log.
+///
+/// Recording stops at the first error writing the log, which is then reported by
+/// [`flush`](#method.flush) and [`into_inner`](#method.into_inner).
+pub struct Recorder<W: Write> {
+    state: Mutex<RecorderState<W>>,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Create a recorder writing the log to `writer`, starting with the log header.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(Recorder {
+            state: Mutex::new(RecorderState {
+                writer,
+                error: None,
+            }),
+        })
+    }
+
+    /// Flush the log, returning an error if recording failed at any point.
+    pub fn flush(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match state.error.as_ref() {
+            Some(e) => Err(io::Error::new(e.kind(), e.to_string())),
+            None => state.writer.flush(),
+        }
+    }
+
+    /// Flush the log and return the underlying writer, or the first error encountered while
+    /// recording.
+    pub fn into_inner(self) -> io::Result<W> {
+        let mut state = self.state.into_inner().unwrap();
+        match state.error {
+            Some(e) => Err(e),
+            None => state.writer.flush().map(|_| state.writer),
+        }
+    }
+}
+
+impl<W: Write> TraceSink for Recorder<W> {
+    fn record(&self, access: IoAccess) {
+        let mut state = self.state.lock().unwrap();
+        if state.error.is_some() {
+            return;
+        }
+        if let Err(e) = Record::from(access).write_to(&mut state.writer) {
+            state.error = Some(e);
+        }
+    }
+}
+
+/// Iterator over the records of a log.
+pub struct LogReader<R> {
+    reader: R,
+}
+
+impl<R: Read> LogReader<R> {
+    /// Create a reader for the log provided by `reader`, after validating the log header.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut header = [0u8; 5];
+        reader.read_exact(&mut header).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::InvalidHeader,
+            _ => Error::Io(e),
+        })?;
+        if header[..4] != MAGIC || header[4] != VERSION {
+            return Err(Error::InvalidHeader);
+        }
+        Ok(LogReader { reader })
+    }
+}
+
+impl<R: Read> Iterator for LogReader<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Record::read_from(&mut self.reader).transpose()
+    }
+}
+
+/// Replay the log provided by `reader` on `manager`.
+///
+/// Returns the number of replayed records, or an error describing the first record that
+/// could not be replayed faithfully.
+pub fn replay<R, M>(reader: R, manager: &M) -> Result<usize, Error>
+where
+    R: Read,
+    M: PioManager + MmioManager,
+{
+    let mut count = 0;
+    for (index, record) in LogReader::new(reader)?.enumerate() {
+        let record = record?;
+        let mut actual = vec![0u8; record.data.len()];
+
+        let result = match (record.bus, record.direction) {
+            (IoBus::Pio, IoDirection::Read) => {
+                manager.pio_read(PioAddress(record.address as u16), &mut actual)
+            }
+            (IoBus::Pio, IoDirection::Write) => {
+                manager.pio_write(PioAddress(record.address as u16), &record.data)
+            }
+            (IoBus::Mmio, IoDirection::Read) => {
+                manager.mmio_read(MmioAddress(record.address), &mut actual)
+            }
+            (IoBus::Mmio, IoDirection::Write) => {
+                manager.mmio_write(MmioAddress(record.address), &record.data)
+            }
+        };
+
+        let diverged = match result {
+            Ok(()) => {
+                record.result.is_err()
+                    || (record.direction == IoDirection::Read && actual != record.data)
+            }
+            Err(ref error) => record.result.as_ref().err() != Some(error),
+        };
+        if diverged {
+            return Err(Error::Divergence(Divergence {
+                index,
+                record,
+                actual,
+                result,
+            }));
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::bus::{MmioAddressOffset, MmioRange, PioAddressOffset, PioRange};
+    use crate::device_manager::IoManager;
+    use crate::{MutDeviceMmio, MutDevicePio};
+
+    // Each read returns the value of an internal counter which is incremented on writes.
+    struct Counter(u8);
+
+    impl MutDevicePio for Counter {
+        fn pio_read(&mut self, _base: PioAddress, _offset: PioAddressOffset, data: &mut [u8]) {
+            data.iter_mut().for_each(|b| *b = self.0);
+        }
+
+        fn pio_write(&mut self, _base: PioAddress, _offset: PioAddressOffset, data: &[u8]) {
+            self.0 = self.0.wrapping_add(data[0]);
+        }
+    }
+
+    impl MutDeviceMmio for Counter {
+        fn mmio_read(&mut self, _base: MmioAddress, _offset: MmioAddressOffset, data: &mut [u8]) {
+            data.iter_mut().for_each(|b| *b = self.0);
+        }
+
+        fn mmio_write(&mut self, _base: MmioAddress, _offset: MmioAddressOffset, data: &[u8]) {
+            self.0 = self.0.wrapping_add(data[0]);
+        }
+    }
+
+    fn build_manager(initial: u8) -> IoManager {
+        let device = Arc::new(Mutex::new(Counter(initial)));
+        let mut manager = IoManager::new();
+        manager
+            .register_pio(PioRange::new(PioAddress(0x60), 4).unwrap(), device.clone())
+            .unwrap();
+        manager
+            .register_mmio(
+                MmioRange::new(MmioAddress(0xd000_0000), 0x1000).unwrap(),
+                device,
+            )
+            .unwrap();
+        manager
+    }
+
+    fn record_log() -> Vec<u8> {
+        let recorder = Arc::new(Recorder::new(Vec::new()).unwrap());
+        // Devices registered before the recorder is installed are recorded as well.
+        let mut manager = build_manager(0);
+        manager.set_trace_sink(Some(recorder.clone()));
+
+        manager.pio_write(PioAddress(0x61), &[2]).unwrap();
+        manager.pio_read(PioAddress(0x62), &mut [0; 2]).unwrap();
+        manager
+            .mmio_write(MmioAddress(0xd000_0010), &[3, 0, 0, 0])
+            .unwrap();
+        manager
+            .mmio_read(MmioAddress(0xd000_0020), &mut [0; 4])
+            .unwrap();
+        // Failed accesses are recorded with their error.
+        assert!(manager.pio_read(PioAddress(0x70), &mut [0]).is_err());
+        drop(manager);
+
+        Arc::try_unwrap(recorder)
+            .ok()
+            .unwrap()
+            .into_inner()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_log_format() {
+        let log = record_log();
+        assert_eq!(&log[..4], &MAGIC);
+        assert_eq!(log[4], VERSION);
+
+        let records: Vec<Record> = LogReader::new(log.as_slice())
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(records.len(), 5);
+        assert_eq!(
+            records[0],
+            Record {
+                bus: IoBus::Pio,
+                address: 0x61,
+                direction: IoDirection::Write,
+                data: vec![2],
+                result: Ok(()),
+            }
+        );
+        assert_eq!(records[1].data, vec![2, 2]);
+        assert_eq!(records[2].bus, IoBus::Mmio);
+        assert_eq!(records[2].address, 0xd000_0010);
+        assert_eq!(records[3].direction, IoDirection::Read);
+        assert_eq!(records[3].data, vec![5; 4]);
+        assert_eq!(records[4].address, 0x70);
+        assert_eq!(records[4].result, Err(bus::Error::DeviceNotFound));
+
+        // Tag, address, length and data: 1 + 2 + 1 + 1 bytes for the first record. Failed
+        // records end with the error code.
+        assert_eq!(
+            log.len(),
+            5 + (1 + 2 + 1 + 1) + (4 + 2) + (10 + 4) + (10 + 4) + (4 + 1 + 1)
+        );
+
+        assert!(matches!(
+            LogReader::new(&b"VMDX\x01"[..]),
+            Err(Error::InvalidHeader)
+        ));
+        assert!(matches!(
+            LogReader::new(&log[..3]),
+            Err(Error::InvalidHeader)
+        ));
+        let truncated = &log[..log.len() - 1];
+        assert!(matches!(
+            LogReader::new(truncated).unwrap().last(),
+            Some(Err(Error::InvalidRecord))
+        ));
+        let mut bad_tag = log.clone();
+        bad_tag[5] = 0x80;
+        assert!(matches!(
+            LogReader::new(bad_tag.as_slice()).unwrap().next(),
+            Some(Err(Error::InvalidRecord))
+        ));
+
+        // A corrupt length only reads the data available.
+        let mut huge = log[..5].to_vec();
+        huge.extend_from_slice(&[TAG_WRITE, 0x61, 0]);
+        huge.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x0f]);
+        assert!(matches!(
+            LogReader::new(huge.as_slice()).unwrap().next(),
+            Some(Err(Error::InvalidRecord))
+        ));
+        // Lengths which don't fit 64 bits are rejected.
+        let mut overflow = log[..5].to_vec();
+        overflow.extend_from_slice(&[TAG_WRITE, 0x61, 0]);
+        overflow.extend_from_slice(&[0xff; 9]);
+        overflow.push(0x02);
+        assert!(matches!(
+            LogReader::new(overflow.as_slice()).unwrap().next(),
+            Some(Err(Error::InvalidRecord))
+        ));
+
+        // Accesses larger than the PIO bus allows are stored for MMIO.
+        let record = Record {
+            bus: IoBus::Mmio,
+            address: 0,
+            direction: IoDirection::Write,
+            data: vec![0x5a; 0x12345],
+            result: Ok(()),
+        };
+        let mut large = log[..5].to_vec();
+        record.write_to(&mut large).unwrap();
+        assert_eq!(large.len(), 5 + 1 + 8 + 3 + 0x12345);
+        let mut records = LogReader::new(large.as_slice()).unwrap();
+        assert_eq!(records.next().unwrap().unwrap(), record);
+        assert!(records.next().is_none());
+
+        // Errors are stored along with their payload, and unknown codes are rejected.
+        let record = Record {
+            bus: IoBus::Pio,
+            address: 0x60,
+            direction: IoDirection::Read,
+            data: vec![0; 3],
+            result: Err(bus::Error::InvalidAccessLength(3)),
+        };
+        let mut failed = log[..5].to_vec();
+        record.write_to(&mut failed).unwrap();
+        assert_eq!(
+            LogReader::new(failed.as_slice())
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap(),
+            record
+        );
+        let last = failed.len() - 2;
+        failed[last] = 0;
+        assert!(matches!(
+            LogReader::new(failed.as_slice()).unwrap().next(),
+            Some(Err(Error::InvalidRecord))
+        ));
+    }
+
+    #[test]
+    fn test_replay() {
+        let log = record_log();
+
+        assert_eq!(replay(log.as_slice(), &build_manager(0)).unwrap(), 5);
+
+        // A device starting from a different state diverges at the first read.
+        match replay(log.as_slice(), &build_manager(1)) {
+            Err(Error::Divergence(d)) => {
+                assert_eq!(d.index, 1);
+                assert_eq!(d.record.data, vec![2, 2]);
+                assert_eq!(d.actual, vec![3, 3]);
+            }
+            _ => panic!("expected a divergence"),
+        }
+
+        // Replaying on a manager without devices diverges at the first record.
+        match replay(log.as_slice(), &IoManager::new()) {
+            Err(Error::Divergence(d)) => {
+                assert_eq!(d.index, 0);
+                assert_eq!(d.result, Err(bus::Error::DeviceNotFound));
+            }
+            _ => panic!("expected a divergence"),
+        }
+
+        // Accesses succeeding where they failed when recorded diverge as well.
+        let mut manager = build_manager(0);
+        manager
+            .register_pio(
+                PioRange::new(PioAddress(0x70), 1).unwrap(),
+                Arc::new(Mutex::new(Counter(0))),
+            )
+            .unwrap();
+        match replay(log.as_slice(), &manager) {
+            Err(Error::Divergence(d)) => {
+                assert_eq!(d.index, 4);
+                assert_eq!(d.record.result, Err(bus::Error::DeviceNotFound));
+                assert_eq!(d.result, Ok(()));
+            }
+            _ => panic!("expected a divergence"),
+        }
+    }
+
+    #[test]
+    fn test_record_guest_accesses() {
+        struct Doorbell;
+
+        impl crate::bus::Notifier for Doorbell {
+            fn notify(&self) {}
+        }
+
+        let recorder = Arc::new(Recorder::new(Vec::new()).unwrap());
+        let mut manager = IoManager::new();
+        manager
+            .register_mmio_aliases(
+                MmioRange::new(MmioAddress(0xd000_0000), 0x1000).unwrap(),
+                &[MmioAddress(0xe000_0000)],
+                Arc::new(Mutex::new(Counter(0))),
+            )
+            .unwrap();
+        manager
+            .register_mmio_ioevent(
+                crate::bus::MmioIoEvent::new(MmioAddress(0xd000_0100), None, None).unwrap(),
+                crate::bus::NotifyMode::InsteadOfDispatch,
+                Arc::new(Doorbell),
+            )
+            .unwrap();
+        manager.set_trace_sink(Some(recorder.clone()));
+
+        manager
+            .mmio_read(MmioAddress(0xe000_0010), &mut [0])
+            .unwrap();
+        manager.mmio_write(MmioAddress(0xd000_0100), &[1]).unwrap();
+        drop(manager);
+
+        let log = Arc::try_unwrap(recorder)
+            .ok()
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        let records: Vec<Record> = LogReader::new(log.as_slice())
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        // Aliased accesses are recorded at the address used by the guest.
+        assert_eq!(records[0].address, 0xe000_0010);
+        // Writes handled by an I/O event are recorded although no device sees them.
+        assert_eq!(records[1].address, 0xd000_0100);
+        assert_eq!(records[1].direction, IoDirection::Write);
+    }
+
+    #[test]
+    fn test_recorder_error() {
+        struct FailingWriter(usize);
+
+        impl Write for FailingWriter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.0 < buf.len() {
+                    return Err(io::Error::other("full"));
+                }
+                self.0 -= buf.len();
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        assert!(Recorder::new(FailingWriter(0)).is_err());
+
+        let recorder = Recorder::new(FailingWriter(5)).unwrap();
+        recorder.record(IoAccess {
+            bus: IoBus::Pio,
+            address: 0,
+            offset: Some(0),
+            direction: IoDirection::Read,
+            data: vec![0],
+            result: Ok(()),
+            timestamp: std::time::Instant::now(),
+        });
+        assert!(recorder.flush().is_err());
+        // The log is incomplete, so the error keeps being reported.
+        assert!(recorder.flush().is_err());
+        assert_eq!(
+            recorder.into_inner().err().unwrap().kind(),
+            io::ErrorKind::Other
+        );
+    }
+}
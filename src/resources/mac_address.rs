@@ -0,0 +1,237 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::resources::Error;
+
+/// Length of a MAC address in bytes.
+pub const MAC_ADDRESS_LEN: usize = 6;
+
+/// An Ethernet MAC address, formatted as six colon separated hexadecimal bytes.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MacAddress([u8; MAC_ADDRESS_LEN]);
+
+impl MacAddress {
+    /// Create a MAC address from its bytes.
+    pub fn new(bytes: [u8; MAC_ADDRESS_LEN]) -> Self {
+        MacAddress(bytes)
+    }
+
+    /// Generate a locally administered unicast address from `seed`, such as the identifier
+    /// of a VM followed by the index of a device. The same seed always yields the same
+    /// address, on any host.
+    pub fn from_seed(seed: &[u8]) -> Self {
+        // 64-bit FNV-1a, whose output does not depend on the platform or the toolchain.
+        let hash = seed.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+            (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+        });
+        let mut bytes = [0; MAC_ADDRESS_LEN];
+        bytes.copy_from_slice(&hash.to_be_bytes()[..MAC_ADDRESS_LEN]);
+        // Set the locally administered bit and clear the multicast bit.
+        bytes[0] = (bytes[0] & !0x01) | 0x02;
+        MacAddress(bytes)
+    }
+
+    /// Return the bytes of the address.
+    pub fn bytes(&self) -> [u8; MAC_ADDRESS_LEN] {
+        self.0
+    }
+
+    /// Check whether the address is locally administered rather than assigned by a vendor.
+    pub fn is_local(&self) -> bool {
+        self.0[0] & 0x02 != 0
+    }
+
+    /// Check whether the address designates a single interface, as opposed to a group.
+    pub fn is_unicast(&self) -> bool {
+        self.0[0] & 0x01 == 0
+    }
+}
+
+impl Display for MacAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            b[0], b[1], b[2], b[3], b[4], b[5]
+        )
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidMacAddress(s.to_string());
+        let mut bytes = [0; MAC_ADDRESS_LEN];
+        let mut parts = s.split(':');
+        for byte in bytes.iter_mut() {
+            let part = parts.next().ok_or_else(invalid)?;
+            if part.len() != 2 || !part.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+            *byte = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+        }
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(MacAddress(bytes))
+    }
+}
+
+// Addresses are serialized in their text form, which keeps the format of
+// `Resource::MacAddress` unchanged from when it held a plain string.
+#[cfg(feature = "serde")]
+impl serde::Serialize for MacAddress {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MacAddress {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Allocator of MAC addresses unique within a VM, resolving
+/// [`ResourceConstraint::MacAddress`](enum.ResourceConstraint.html#variant.MacAddress)
+/// constraints.
+///
+/// Generated addresses are derived from the seed of the allocator, so a VM created with the
+/// same seed and devices gets the same addresses.
+#[derive(Clone, Debug)]
+pub struct MacAddressAllocator {
+    seed: Vec<u8>,
+    // Number of addresses generated so far, appended to the seed of the next one.
+    generated: u64,
+    allocated: BTreeSet<MacAddress>,
+}
+
+impl MacAddressAllocator {
+    /// Create an allocator generating addresses from `seed`, such as the identifier of a VM.
+    pub fn new(seed: &[u8]) -> Self {
+        MacAddressAllocator {
+            seed: seed.to_vec(),
+            generated: 0,
+            allocated: BTreeSet::new(),
+        }
+    }
+
+    /// Allocate `requested` if specified, or generate a locally administered unicast address
+    /// otherwise. Fails if the requested address is multicast or already allocated.
+    pub fn allocate(&mut self, requested: Option<MacAddress>) -> Result<MacAddress, Error> {
+        if let Some(addr) = requested {
+            if !addr.is_unicast() {
+                return Err(Error::MulticastMacAddress(addr));
+            }
+            if !self.allocated.insert(addr) {
+                return Err(Error::MacAddressInUse(addr));
+            }
+            return Ok(addr);
+        }
+        loop {
+            let mut seed = self.seed.clone();
+            seed.extend_from_slice(&self.generated.to_le_bytes());
+            self.generated += 1;
+            let addr = MacAddress::from_seed(&seed);
+            if self.allocated.insert(addr) {
+                return Ok(addr);
+            }
+        }
+    }
+
+    /// Free `addr`, returning whether it was allocated.
+    pub fn free(&mut self, addr: &MacAddress) -> bool {
+        self.allocated.remove(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mac_address() {
+        let addr: MacAddress = "00:08:63:66:86:AA".parse().unwrap();
+        assert_eq!(addr.bytes(), [0x00, 0x08, 0x63, 0x66, 0x86, 0xaa]);
+        assert_eq!(addr.to_string(), "00:08:63:66:86:aa");
+        assert_eq!(MacAddress::new(addr.bytes()), addr);
+        assert!(addr.is_unicast());
+        assert!(!addr.is_local());
+
+        let broadcast = MacAddress::new([0xff; MAC_ADDRESS_LEN]);
+        assert!(!broadcast.is_unicast());
+        assert!(broadcast.is_local());
+
+        for invalid in &[
+            "",
+            "00:08:63:66:86",
+            "00:08:63:66:86:88:99",
+            "00-08-63-66-86-88",
+            "0:08:63:66:86:88",
+            "00:08:63:66:86:g8",
+            "00:08:63:66:86:+8",
+        ] {
+            assert_eq!(
+                invalid.parse::<MacAddress>(),
+                Err(Error::InvalidMacAddress(invalid.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_seed() {
+        let addr = MacAddress::from_seed(b"vm-1");
+        assert_eq!(addr, MacAddress::from_seed(b"vm-1"));
+        assert_ne!(addr, MacAddress::from_seed(b"vm-2"));
+        assert!(addr.is_local() && addr.is_unicast());
+        // The generated addresses must not change across releases.
+        assert_eq!(MacAddress::from_seed(b"").to_string(), "ca:f2:9c:e4:84:22");
+    }
+
+    #[test]
+    fn test_allocator() {
+        let mut allocator = MacAddressAllocator::new(b"vm-1");
+        let first = allocator.allocate(None).unwrap();
+        let second = allocator.allocate(None).unwrap();
+        assert_ne!(first, second);
+        assert!(first.is_local() && first.is_unicast());
+
+        // The same seed yields the same sequence.
+        let mut other = MacAddressAllocator::new(b"vm-1");
+        assert_eq!(other.allocate(None).unwrap(), first);
+
+        let requested: MacAddress = "52:54:00:12:34:56".parse().unwrap();
+        assert_eq!(allocator.allocate(Some(requested)), Ok(requested));
+        assert_eq!(
+            allocator.allocate(Some(requested)),
+            Err(Error::MacAddressInUse(requested))
+        );
+        assert_eq!(
+            allocator.allocate(Some(first)),
+            Err(Error::MacAddressInUse(first))
+        );
+        let multicast: MacAddress = "01:00:5e:00:00:01".parse().unwrap();
+        assert_eq!(
+            allocator.allocate(Some(multicast)),
+            Err(Error::MulticastMacAddress(multicast))
+        );
+
+        // Generated addresses skip the ones already allocated.
+        let mut other = MacAddressAllocator::new(b"vm-1");
+        other.allocate(Some(second)).unwrap();
+        assert_eq!(other.allocate(None).unwrap(), first);
+        assert_ne!(other.allocate(None).unwrap(), second);
+
+        assert!(allocator.free(&requested));
+        assert!(!allocator.free(&requested));
+        assert_eq!(allocator.allocate(Some(requested)), Ok(requested));
+    }
+}
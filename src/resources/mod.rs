@@ -0,0 +1,885 @@
+// Copyright (C) 2019 Alibaba Cloud. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Structs to manage device resources.
+//!
+//! The high level flow of resource management among the VMM, the device manager, and the device
+//! is as below:
+//! 1) the VMM creates a new device object.
+//! 2) the VMM asks the new device object for its resource constraints.
+//! 3) the VMM allocates resources for the device object according to resource constraints.
+//! 4) the VMM passes the allocated resources to the device object.
+//! 5) the VMM registers the new device onto corresponding device managers according the allocated
+//!    resources.
+//!
+//! The resources allocated in step 3 can be checked against the constraints of the device with
+//! [`DeviceResources::validate`].
+//!
+//! With the `serde` feature enabled, resource constraints and resources can be serialized, for
+//! example to persist the configuration of a VM or to migrate it. [`DeviceResources`] is
+//! serialized along with the version of its format, and deserializing an unknown version fails.
+//! It is the unit meant to be persisted: the other types carry no version of their own, so
+//! callers serializing them on their own are responsible for versioning their format.
+
+use std::fmt::{Display, Formatter};
+
+use crate::bus::{self, MmioAddress, MmioRange, PioAddress, PioRange};
+
+mod mac_address;
+mod pci_bar;
+mod reserved;
+mod validate;
+
+pub use mac_address::{MacAddress, MacAddressAllocator, MAC_ADDRESS_LEN};
+pub use pci_bar::{PciBarAllocator, PciBarType, PCI_BAR_COUNT};
+pub use reserved::ReservedResources;
+
+/// Errors encountered while handling device resources.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The string is not a MAC address made of six colon separated hexadecimal bytes.
+    InvalidMacAddress(String),
+    /// The MAC address is already allocated.
+    MacAddressInUse(MacAddress),
+    /// The MAC address is a multicast address, which cannot be assigned to a device.
+    MulticastMacAddress(MacAddress),
+    /// The PCI BAR index is out of the header, or its 64-bit BAR does not fit in it.
+    InvalidPciBarIndex(u8),
+    /// The PCI BAR size is not a power of two of at least 16 bytes.
+    InvalidPciBarSize(u64),
+    /// No PCI window has room for the BAR with the provided index.
+    PciBarSpaceExhausted(u8),
+    /// The resource overlaps a resource reserved for the platform.
+    ReservedResource(Resource),
+    /// The resources do not satisfy the constraints they are checked against.
+    ConstraintMismatch {
+        /// Constraints not satisfied by any resource.
+        unmatched: Vec<ResourceConstraint>,
+        /// Resources not matching any constraint.
+        extra: Vec<Resource>,
+    },
+    /// The address range resource is empty or its last address overflows.
+    InvalidRange(Resource),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidMacAddress(s) => write!(f, "invalid MAC address: {:?}", s),
+            Error::MacAddressInUse(addr) => write!(f, "MAC address {} is already in use", addr),
+            Error::MulticastMacAddress(addr) => write!(f, "MAC address {} is multicast", addr),
+            Error::InvalidPciBarIndex(index) => write!(f, "invalid PCI BAR index {}", index),
+            Error::InvalidPciBarSize(size) => write!(f, "invalid PCI BAR size {:#x}", size),
+            Error::PciBarSpaceExhausted(index) => {
+                write!(f, "no space left for PCI BAR {}", index)
+            }
+            Error::ReservedResource(res) => write!(f, "resource {:?} is reserved", res),
+            Error::ConstraintMismatch { unmatched, extra } => write!(
+                f,
+                "resources do not match constraints: unmatched {:?}, extra {:?}",
+                unmatched, extra
+            ),
+            Error::InvalidRange(res) => write!(f, "invalid address range {:?}", res),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Enumeration describing a device's resource constraints.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ResourceConstraint {
+    /// Constraint for an IO Port address range.
+    PioAddress {
+        /// Allocating resource within the range [`min`, `max`] if specified.
+        range: Option<(u16, u16)>,
+        /// Alignment for the allocated address.
+        align: u16,
+        /// Size for the allocated address range.
+        size: u16,
+    },
+    /// Constraint for a Memory Mapped IO address range.
+    MmioAddress {
+        /// Allocating resource within the range [`min`, `max`] if specified.
+        range: Option<(u64, u64)>,
+        /// Alignment for the allocated address.
+        align: u64,
+        /// Size for the allocated address range.
+        size: u64,
+    },
+    /// Constraint for a legacy IRQ.
+    LegacyIrq {
+        /// Reserving the pre-allocated IRQ if it's specified.
+        irq: Option<u32>,
+    },
+    /// Constraint for PCI MSI IRQs.
+    PciMsiIrq {
+        /// Number of Irqs to allocate.
+        size: u32,
+    },
+    /// Constraint for PCI MSIx IRQs.
+    PciMsixIrq {
+        /// Number of Irqs to allocate.
+        size: u32,
+    },
+    /// Constraint for generic IRQs.
+    GenericIrq {
+        /// Number of Irqs to allocate.
+        size: u32,
+    },
+    /// Constraint for KVM mem_slot indexes to map memory into the guest.
+    KvmMemSlot {
+        /// Allocating kvm memory slots starting from the index `slot` if specified.
+        slot: Option<u32>,
+        /// Number of slots to allocate.
+        size: u32,
+    },
+    /// Constraint for a NIC MAC address.
+    MacAddress {
+        /// Allocating the address `addr` if specified, or generating one otherwise.
+        addr: Option<MacAddress>,
+    },
+    /// Constraint for a PCI memory BAR, naturally aligned to its size.
+    PciBar {
+        /// Index of the BAR in the PCI header.
+        index: u8,
+        /// Size of the BAR, a power of two.
+        size: u64,
+        /// Width of the BAR.
+        bar_type: PciBarType,
+        /// Whether the BAR is prefetchable.
+        prefetchable: bool,
+    },
+}
+
+impl ResourceConstraint {
+    /// Create a new PIO address constraint object with default configuration.
+    pub fn new_pio(size: u16) -> Self {
+        ResourceConstraint::PioAddress {
+            range: None,
+            align: 0x1,
+            size,
+        }
+    }
+
+    /// Create a new PIO address constraint object.
+    pub fn pio_with_constraints(size: u16, range: Option<(u16, u16)>, align: u16) -> Self {
+        ResourceConstraint::PioAddress { range, align, size }
+    }
+
+    /// Create a new MMIO address constraint object with default configuration.
+    pub fn new_mmio(size: u64) -> Self {
+        ResourceConstraint::MmioAddress {
+            range: None,
+            align: 0x1000,
+            size,
+        }
+    }
+
+    /// Create a new MMIO address constraint object.
+    pub fn mmio_with_constraints(size: u64, range: Option<(u64, u64)>, align: u64) -> Self {
+        ResourceConstraint::MmioAddress { range, align, size }
+    }
+
+    /// Create a new legacy IRQ constraint object.
+    ///
+    /// Allocating the pre-allocated legacy Irq `irq` if specified.
+    pub fn new_legacy_irq(irq: Option<u32>) -> Self {
+        ResourceConstraint::LegacyIrq { irq }
+    }
+
+    /// Create a new KVM memory slot constraint object.
+    ///
+    /// Allocating kvm memory slots starting from the index `slot` if specified.
+    pub fn new_kvm_mem_slot(size: u32, slot: Option<u32>) -> Self {
+        ResourceConstraint::KvmMemSlot { slot, size }
+    }
+
+    /// Create a new MAC address constraint object.
+    ///
+    /// Allocating the pre-assigned address `addr` if specified.
+    pub fn new_mac_address(addr: Option<MacAddress>) -> Self {
+        ResourceConstraint::MacAddress { addr }
+    }
+
+    /// Create a new PCI memory BAR constraint object.
+    pub fn new_pci_bar(index: u8, size: u64, bar_type: PciBarType, prefetchable: bool) -> Self {
+        ResourceConstraint::PciBar {
+            index,
+            size,
+            bar_type,
+            prefetchable,
+        }
+    }
+}
+
+/// Type of Message Signaled Interrupt
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MsiIrqType {
+    /// PCI MSI IRQ numbers.
+    PciMsi,
+    /// PCI MSIx IRQ numbers.
+    PciMsix,
+    /// Generic MSI IRQ numbers.
+    GenericMsi,
+}
+
+/// Enumeration for device resources.
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Resource {
+    /// IO Port address range.
+    PioAddressRange { base: u16, size: u16 },
+    /// Memory Mapped IO address range.
+    MmioAddressRange { base: u64, size: u64 },
+    /// Legacy IRQ number.
+    LegacyIrq(u32),
+    /// Message Signaled Interrupt
+    MsiIrq {
+        ty: MsiIrqType,
+        base: u32,
+        size: u32,
+    },
+    /// Network Interface Card MAC address.
+    MacAddress(MacAddress),
+    /// KVM memslot index.
+    KvmMemSlot(u32),
+    /// PCI memory BAR.
+    PciBar {
+        index: u8,
+        base: u64,
+        size: u64,
+        bar_type: PciBarType,
+        prefetchable: bool,
+    },
+}
+
+/// Newtype to store a set of device resources.
+///
+/// With the `serde` feature enabled, this is the versioned unit in which resources are meant
+/// to be persisted.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DeviceResources(Vec<Resource>);
+
+// Version of the serialized `DeviceResources` format, to be bumped on incompatible changes.
+#[cfg(feature = "serde")]
+const SERDE_VERSION: u32 = 1;
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "DeviceResources")]
+struct SerdeDeviceResources<R> {
+    version: u32,
+    resources: R,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DeviceResources {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerdeDeviceResources {
+            version: SERDE_VERSION,
+            resources: &self.0,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DeviceResources {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized = SerdeDeviceResources::<Vec<Resource>>::deserialize(deserializer)?;
+        if serialized.version != SERDE_VERSION {
+            return Err(serde::de::Error::custom(format!(
+                "unsupported DeviceResources version {}",
+                serialized.version
+            )));
+        }
+        Ok(DeviceResources(serialized.resources))
+    }
+}
+
+impl DeviceResources {
+    /// Create a container object to store device resources.
+    pub fn new() -> Self {
+        DeviceResources(Vec::new())
+    }
+
+    /// Append a device resource to the container object.
+    pub fn append(&mut self, entry: Resource) {
+        self.0.push(entry);
+    }
+
+    /// Remove the first entry equal to `entry`, returning whether there was one.
+    pub fn remove(&mut self, entry: &Resource) -> bool {
+        match self.0.iter().position(|res| res == entry) {
+            Some(index) => {
+                self.0.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace the first entry equal to `old` with `new`, keeping its position. Returns
+    /// whether there was such an entry.
+    pub fn replace(&mut self, old: &Resource, new: Resource) -> bool {
+        match self.0.iter_mut().find(|res| *res == old) {
+            Some(res) => {
+                *res = new;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Keep only the entries for which `f` returns `true`.
+    pub fn retain<F: FnMut(&Resource) -> bool>(&mut self, f: F) {
+        self.0.retain(f);
+    }
+
+    /// Append the entries of `other` which are not already part of the container object.
+    pub fn merge(&mut self, other: &DeviceResources) {
+        for res in other.0.iter() {
+            if !self.0.contains(res) {
+                self.0.push(res.clone());
+            }
+        }
+    }
+
+    /// Get the IO port address resources as ranges, failing if any of them is invalid.
+    pub fn get_pio_ranges(&self) -> Result<Vec<PioRange>, Error> {
+        let mut vec = Vec::new();
+        for entry in self.0.iter() {
+            if let Resource::PioAddressRange { base, size } = *entry {
+                vec.push(to_range(entry, PioRange::new(PioAddress(base), size))?);
+            }
+        }
+        Ok(vec)
+    }
+
+    /// Get the Memory Mapped IO address resources as ranges, failing if any of them is
+    /// invalid.
+    pub fn get_mmio_ranges(&self) -> Result<Vec<MmioRange>, Error> {
+        let mut vec = Vec::new();
+        for entry in self.0.iter() {
+            if let Resource::MmioAddressRange { base, size } = *entry {
+                vec.push(to_range(entry, MmioRange::new(MmioAddress(base), size))?);
+            }
+        }
+        Ok(vec)
+    }
+
+    /// Iterate over all the legacy interrupt numbers (IRQs).
+    pub fn legacy_irqs(&self) -> impl Iterator<Item = u32> + '_ {
+        self.0.iter().filter_map(|entry| match *entry {
+            Resource::LegacyIrq(irq) => Some(irq),
+            _ => None,
+        })
+    }
+
+    /// Iterate over all the Message Signaled Interrupt blocks, as (type, base, size) tuples.
+    pub fn msi_irqs(&self) -> impl Iterator<Item = (MsiIrqType, u32, u32)> + '_ {
+        self.0.iter().filter_map(|entry| match *entry {
+            Resource::MsiIrq { ty, base, size } => Some((ty, base, size)),
+            _ => None,
+        })
+    }
+
+    /// Get the IO port address resources.
+    pub fn get_pio_address_ranges(&self) -> Vec<(u16, u16)> {
+        let mut vec = Vec::new();
+        for entry in self.0.iter().as_ref() {
+            if let Resource::PioAddressRange { base, size } = entry {
+                vec.push((*base, *size));
+            }
+        }
+        vec
+    }
+
+    /// Get the Memory Mapped IO address resources.
+    pub fn get_mmio_address_ranges(&self) -> Vec<(u64, u64)> {
+        let mut vec = Vec::new();
+        for entry in self.0.iter().as_ref() {
+            if let Resource::MmioAddressRange { base, size } = entry {
+                vec.push((*base, *size));
+            }
+        }
+        vec
+    }
+
+    /// Get the first legacy interrupt number(IRQ).
+    pub fn get_legacy_irq(&self) -> Option<u32> {
+        for entry in self.0.iter().as_ref() {
+            if let Resource::LegacyIrq(base) = entry {
+                return Some(*base);
+            }
+        }
+        None
+    }
+
+    /// Get information about the first PCI MSI interrupt resource.
+    pub fn get_pci_msi_irqs(&self) -> Option<(u32, u32)> {
+        self.get_msi_irqs(MsiIrqType::PciMsi)
+    }
+
+    /// Get information about the first PCI MSIx interrupt resource.
+    pub fn get_pci_msix_irqs(&self) -> Option<(u32, u32)> {
+        self.get_msi_irqs(MsiIrqType::PciMsix)
+    }
+
+    /// Get information about the first Generic MSI interrupt resource.
+    pub fn get_generic_msi_irqs(&self) -> Option<(u32, u32)> {
+        self.get_msi_irqs(MsiIrqType::GenericMsi)
+    }
+
+    fn get_msi_irqs(&self, ty: MsiIrqType) -> Option<(u32, u32)> {
+        for entry in self.0.iter().as_ref() {
+            if let Resource::MsiIrq {
+                ty: msi_type,
+                base,
+                size,
+            } = entry
+            {
+                if ty == *msi_type {
+                    return Some((*base, *size));
+                }
+            }
+        }
+        None
+    }
+
+    /// Get the KVM memory slots to map memory into the guest.
+    pub fn get_kvm_mem_slots(&self) -> Vec<u32> {
+        let mut vec = Vec::new();
+        for entry in self.0.iter().as_ref() {
+            if let Resource::KvmMemSlot(index) = entry {
+                vec.push(*index);
+            }
+        }
+        vec
+    }
+
+    /// Get the first resource information for NIC MAC address.
+    pub fn get_mac_address(&self) -> Option<MacAddress> {
+        for entry in self.0.iter().as_ref() {
+            if let Resource::MacAddress(addr) = entry {
+                return Some(*addr);
+            }
+        }
+        None
+    }
+
+    /// Get the base and size of the PCI BAR with index `index`.
+    pub fn get_pci_bar(&self, index: u8) -> Option<(u64, u64)> {
+        self.0.iter().find_map(|entry| match *entry {
+            Resource::PciBar {
+                index: i,
+                base,
+                size,
+                ..
+            } if i == index => Some((base, size)),
+            _ => None,
+        })
+    }
+
+    /// Get immutable reference to all the resources.
+    pub fn get_all_resources(&self) -> &[Resource] {
+        &self.0
+    }
+}
+
+// Map the result of building the range described by `entry` to a resource error.
+fn to_range<R>(entry: &Resource, range: Result<R, bus::Error>) -> Result<R, Error> {
+    range.map_err(|_| Error::InvalidRange(entry.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIO_ADDRESS_SIZE: u16 = 5;
+    const PIO_ADDRESS_BASE: u16 = 0;
+    const MMIO_ADDRESS_SIZE: u64 = 0x8765_4321;
+    const MMIO_ADDRESS_BASE: u64 = 0x1234_5678;
+    const LEGACY_IRQ: u32 = 0x168;
+    const PCI_MSI_IRQ_SIZE: u32 = 0x8888;
+    const PCI_MSI_IRQ_BASE: u32 = 0x6666;
+    const PCI_MSIX_IRQ_SIZE: u32 = 0x16666;
+    const PCI_MSIX_IRQ_BASE: u32 = 0x8888;
+    const GENERIC_MSI_IRQS_SIZE: u32 = 0x16888;
+    const GENERIC_MSI_IRQS_BASE: u32 = 0x16688;
+    const MAC_ADDRESS: &str = "00:08:63:66:86:88";
+    const KVM_SLOT_ID: u32 = 0x0100;
+    const PCI_BAR_BASE: u64 = 0xe000_0000;
+    const PCI_BAR_SIZE: u64 = 0x1000_0000;
+
+    fn get_device_resource() -> DeviceResources {
+        let entry = Resource::PioAddressRange {
+            base: PIO_ADDRESS_BASE,
+            size: PIO_ADDRESS_SIZE,
+        };
+        let mut resource = DeviceResources::new();
+        resource.append(entry);
+        let entry = Resource::MmioAddressRange {
+            base: MMIO_ADDRESS_BASE,
+            size: MMIO_ADDRESS_SIZE,
+        };
+        resource.append(entry);
+        let entry = Resource::LegacyIrq(LEGACY_IRQ);
+        resource.append(entry);
+        let entry = Resource::MsiIrq {
+            ty: MsiIrqType::PciMsi,
+            base: PCI_MSI_IRQ_BASE,
+            size: PCI_MSI_IRQ_SIZE,
+        };
+        resource.append(entry);
+        let entry = Resource::MsiIrq {
+            ty: MsiIrqType::PciMsix,
+            base: PCI_MSIX_IRQ_BASE,
+            size: PCI_MSIX_IRQ_SIZE,
+        };
+        resource.append(entry);
+        let entry = Resource::MsiIrq {
+            ty: MsiIrqType::GenericMsi,
+            base: GENERIC_MSI_IRQS_BASE,
+            size: GENERIC_MSI_IRQS_SIZE,
+        };
+        resource.append(entry);
+        let entry = Resource::MacAddress(MAC_ADDRESS.parse().unwrap());
+        resource.append(entry);
+
+        resource.append(Resource::KvmMemSlot(KVM_SLOT_ID));
+
+        resource
+    }
+
+    #[test]
+    fn get_pio_address_ranges() {
+        let resources = get_device_resource();
+        assert!(
+            resources.get_pio_address_ranges()[0].0 == PIO_ADDRESS_BASE
+                && resources.get_pio_address_ranges()[0].1 == PIO_ADDRESS_SIZE
+        );
+    }
+
+    #[test]
+    fn test_get_mmio_address_ranges() {
+        let resources = get_device_resource();
+        assert!(
+            resources.get_mmio_address_ranges()[0].0 == MMIO_ADDRESS_BASE
+                && resources.get_mmio_address_ranges()[0].1 == MMIO_ADDRESS_SIZE
+        );
+    }
+
+    #[test]
+    fn test_get_legacy_irq() {
+        let resources = get_device_resource();
+        assert!(resources.get_legacy_irq().unwrap() == LEGACY_IRQ);
+    }
+
+    #[test]
+    fn test_get_pci_msi_irqs() {
+        let resources = get_device_resource();
+        assert!(
+            resources.get_pci_msi_irqs().unwrap().0 == PCI_MSI_IRQ_BASE
+                && resources.get_pci_msi_irqs().unwrap().1 == PCI_MSI_IRQ_SIZE
+        );
+    }
+
+    #[test]
+    fn test_pci_msix_irqs() {
+        let resources = get_device_resource();
+        assert!(
+            resources.get_pci_msix_irqs().unwrap().0 == PCI_MSIX_IRQ_BASE
+                && resources.get_pci_msix_irqs().unwrap().1 == PCI_MSIX_IRQ_SIZE
+        );
+    }
+
+    #[test]
+    fn test_get_generic_msi_irqs() {
+        let resources = get_device_resource();
+        assert!(
+            resources.get_generic_msi_irqs().unwrap().0 == GENERIC_MSI_IRQS_BASE
+                && resources.get_generic_msi_irqs().unwrap().1 == GENERIC_MSI_IRQS_SIZE
+        );
+    }
+
+    #[test]
+    fn test_get_mac_address() {
+        let resources = get_device_resource();
+        assert_eq!(
+            resources.get_mac_address().unwrap().to_string(),
+            MAC_ADDRESS
+        );
+    }
+
+    #[test]
+    fn test_get_kvm_slot() {
+        let resources = get_device_resource();
+        assert_eq!(resources.get_kvm_mem_slots(), vec![KVM_SLOT_ID]);
+    }
+
+    #[test]
+    fn test_get_all_resources() {
+        let resources = get_device_resource();
+        assert_eq!(resources.get_all_resources().len(), 8);
+    }
+
+    #[test]
+    fn test_get_ranges() {
+        let resources = get_device_resource();
+        assert_eq!(
+            resources.get_pio_ranges().unwrap(),
+            vec![PioRange::new(PioAddress(PIO_ADDRESS_BASE), PIO_ADDRESS_SIZE).unwrap()]
+        );
+        assert_eq!(
+            resources.get_mmio_ranges().unwrap(),
+            vec![MmioRange::new(MmioAddress(MMIO_ADDRESS_BASE), MMIO_ADDRESS_SIZE).unwrap()]
+        );
+
+        let mut resources = DeviceResources::new();
+        let invalid = Resource::MmioAddressRange {
+            base: u64::MAX,
+            size: 2,
+        };
+        resources.append(invalid.clone());
+        assert_eq!(resources.get_pio_ranges(), Ok(Vec::new()));
+        assert_eq!(
+            resources.get_mmio_ranges(),
+            Err(Error::InvalidRange(invalid))
+        );
+        let invalid = Resource::PioAddressRange { base: 0, size: 0 };
+        resources.append(invalid.clone());
+        assert_eq!(
+            resources.get_pio_ranges(),
+            Err(Error::InvalidRange(invalid))
+        );
+    }
+
+    #[test]
+    fn test_irq_iterators() {
+        let mut resources = get_device_resource();
+        resources.append(Resource::LegacyIrq(LEGACY_IRQ + 1));
+        assert_eq!(
+            resources.legacy_irqs().collect::<Vec<_>>(),
+            vec![LEGACY_IRQ, LEGACY_IRQ + 1]
+        );
+        assert_eq!(
+            resources.msi_irqs().collect::<Vec<_>>(),
+            vec![
+                (MsiIrqType::PciMsi, PCI_MSI_IRQ_BASE, PCI_MSI_IRQ_SIZE),
+                (MsiIrqType::PciMsix, PCI_MSIX_IRQ_BASE, PCI_MSIX_IRQ_SIZE),
+                (
+                    MsiIrqType::GenericMsi,
+                    GENERIC_MSI_IRQS_BASE,
+                    GENERIC_MSI_IRQS_SIZE
+                ),
+            ]
+        );
+        assert_eq!(DeviceResources::new().legacy_irqs().next(), None);
+    }
+
+    #[test]
+    fn test_update_resources() {
+        let mut resources = get_device_resource();
+        let irq = Resource::LegacyIrq(LEGACY_IRQ);
+        assert!(resources.remove(&irq));
+        assert!(!resources.remove(&irq));
+        assert_eq!(resources.get_legacy_irq(), None);
+        assert_eq!(resources.get_all_resources().len(), 7);
+
+        let old = Resource::KvmMemSlot(KVM_SLOT_ID);
+        let new = Resource::KvmMemSlot(KVM_SLOT_ID + 1);
+        let position = resources
+            .get_all_resources()
+            .iter()
+            .position(|res| *res == old);
+        assert!(resources.replace(&old, new.clone()));
+        assert!(!resources.replace(&old, new.clone()));
+        assert_eq!(
+            resources
+                .get_all_resources()
+                .iter()
+                .position(|res| *res == new),
+            position
+        );
+
+        resources.retain(|res| !matches!(res, Resource::MsiIrq { .. }));
+        assert_eq!(resources.msi_irqs().count(), 0);
+        assert_eq!(resources.get_all_resources().len(), 4);
+
+        // Merging skips the entries already present.
+        let mut other = DeviceResources::new();
+        other.append(new.clone());
+        other.append(irq.clone());
+        resources.merge(&other);
+        assert_eq!(resources.get_all_resources().len(), 5);
+        assert_eq!(resources.get_all_resources().last(), Some(&irq));
+        assert_eq!(resources.get_kvm_mem_slots(), vec![KVM_SLOT_ID + 1]);
+    }
+
+    #[test]
+    fn test_get_pci_bar() {
+        let mut resources = DeviceResources::new();
+        resources.append(Resource::MmioAddressRange {
+            base: MMIO_ADDRESS_BASE,
+            size: MMIO_ADDRESS_SIZE,
+        });
+        resources.append(Resource::PciBar {
+            index: 2,
+            base: PCI_BAR_BASE,
+            size: PCI_BAR_SIZE,
+            bar_type: PciBarType::Mmio64,
+            prefetchable: true,
+        });
+        assert_eq!(resources.get_pci_bar(2), Some((PCI_BAR_BASE, PCI_BAR_SIZE)));
+        assert_eq!(resources.get_pci_bar(0), None);
+        // BARs are not plain memory-mapped I/O ranges.
+        assert_eq!(
+            resources.get_mmio_address_ranges(),
+            vec![(MMIO_ADDRESS_BASE, MMIO_ADDRESS_SIZE)]
+        );
+    }
+
+    #[test]
+    fn test_resource_constraint() {
+        if let ResourceConstraint::PioAddress { range, align, size } =
+            ResourceConstraint::new_pio(2)
+        {
+            assert_eq!(range, None);
+            assert_eq!(align, 1);
+            assert_eq!(size, 2);
+        } else {
+            panic!("Pio resource constraint is invalid.");
+        }
+
+        if let ResourceConstraint::PioAddress { range, align, size } =
+            ResourceConstraint::pio_with_constraints(2, Some((15, 16)), 2)
+        {
+            assert_eq!(range, Some((15, 16)));
+            assert_eq!(align, 2);
+            assert_eq!(size, 2);
+        } else {
+            panic!("Pio resource constraint is invalid.");
+        }
+
+        if let ResourceConstraint::MmioAddress { range, align, size } =
+            ResourceConstraint::new_mmio(0x2000)
+        {
+            assert_eq!(range, None);
+            assert_eq!(align, 0x1000);
+            assert_eq!(size, 0x2000);
+        } else {
+            panic!("Mmio resource constraint is invalid.");
+        }
+
+        if let ResourceConstraint::MmioAddress { range, align, size } =
+            ResourceConstraint::mmio_with_constraints(0x2000, Some((0x0, 0x2000)), 0x2000)
+        {
+            assert_eq!(range, Some((0x0, 0x2000)));
+            assert_eq!(align, 0x2000);
+            assert_eq!(size, 0x2000);
+        } else {
+            panic!("Mmio resource constraint is invalid.");
+        }
+
+        if let ResourceConstraint::LegacyIrq { irq } =
+            ResourceConstraint::new_legacy_irq(Some(0x123))
+        {
+            assert_eq!(irq, Some(0x123));
+        } else {
+            panic!("IRQ resource constraint is invalid.");
+        }
+
+        if let ResourceConstraint::KvmMemSlot { slot, size } =
+            ResourceConstraint::new_kvm_mem_slot(0x1000, Some(0x2000))
+        {
+            assert_eq!(slot, Some(0x2000));
+            assert_eq!(size, 0x1000);
+        } else {
+            panic!("KVM slot resource constraint is invalid.");
+        }
+
+        let addr = MAC_ADDRESS.parse().unwrap();
+        assert_eq!(
+            ResourceConstraint::new_mac_address(Some(addr)),
+            ResourceConstraint::MacAddress { addr: Some(addr) }
+        );
+
+        assert_eq!(
+            ResourceConstraint::new_pci_bar(1, 0x1000, PciBarType::Mmio32, false),
+            ResourceConstraint::PciBar {
+                index: 1,
+                size: 0x1000,
+                bar_type: PciBarType::Mmio32,
+                prefetchable: false,
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let resources = get_device_resource();
+        let json = serde_json::to_string(&resources).unwrap();
+        assert_eq!(
+            serde_json::from_str::<DeviceResources>(&json).unwrap(),
+            resources
+        );
+
+        // The format is stable.
+        let mut resources = DeviceResources::new();
+        resources.append(Resource::PioAddressRange {
+            base: 0x3f8,
+            size: 8,
+        });
+        resources.append(Resource::MsiIrq {
+            ty: MsiIrqType::PciMsix,
+            base: 24,
+            size: 2,
+        });
+        resources.append(Resource::MacAddress(MAC_ADDRESS.parse().unwrap()));
+        let json = concat!(
+            r#"{"version":1,"resources":["#,
+            r#"{"PioAddressRange":{"base":1016,"size":8}},"#,
+            r#"{"MsiIrq":{"ty":"PciMsix","base":24,"size":2}},"#,
+            r#"{"MacAddress":"00:08:63:66:86:88"}]}"#
+        );
+        assert_eq!(serde_json::to_string(&resources).unwrap(), json);
+        assert_eq!(
+            serde_json::from_str::<DeviceResources>(json).unwrap(),
+            resources
+        );
+
+        // Unknown versions are rejected.
+        let err =
+            serde_json::from_str::<DeviceResources>(r#"{"version":2,"resources":[]}"#).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unsupported DeviceResources version 2"));
+
+        let constraints = vec![
+            ResourceConstraint::pio_with_constraints(8, Some((0x3f8, 0x3ff)), 1),
+            ResourceConstraint::new_mmio(0x1000),
+            ResourceConstraint::new_legacy_irq(None),
+            ResourceConstraint::PciMsiIrq { size: 1 },
+            ResourceConstraint::PciMsixIrq { size: 2 },
+            ResourceConstraint::GenericIrq { size: 3 },
+            ResourceConstraint::new_kvm_mem_slot(1, Some(2)),
+            ResourceConstraint::new_mac_address(None),
+            ResourceConstraint::new_pci_bar(0, 0x4000, PciBarType::Mmio64, true),
+        ];
+        let json = serde_json::to_string(&constraints).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<ResourceConstraint>>(&json).unwrap(),
+            constraints
+        );
+        assert_eq!(
+            serde_json::to_string(&constraints[0]).unwrap(),
+            r#"{"PioAddress":{"range":[1016,1023],"align":1,"size":8}}"#
+        );
+    }
+}
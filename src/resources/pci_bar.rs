@@ -0,0 +1,260 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::BTreeMap;
+
+use crate::resources::{Error, ReservedResources, Resource};
+
+/// Number of Base Address Registers in the header of a PCI endpoint.
+pub const PCI_BAR_COUNT: u8 = 6;
+
+// Smallest memory BAR, as the low 4 bits of the register hold its flags.
+const PCI_BAR_MIN_SIZE: u64 = 16;
+// Last address reachable by a 32-bit BAR.
+const MAX_32BIT_ADDRESS: u64 = 0xffff_ffff;
+
+/// Width of a PCI memory BAR.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PciBarType {
+    /// 32-bit BAR, which must be placed below 4 GiB.
+    Mmio32,
+    /// 64-bit BAR, which also uses the register following it.
+    Mmio64,
+}
+
+/// Allocator of PCI memory BARs, resolving
+/// [`ResourceConstraint::PciBar`](enum.ResourceConstraint.html#variant.PciBar) constraints
+/// within the windows of a PCI host bridge.
+///
+/// BARs are naturally aligned to their size. Prefetchable BARs are placed in the prefetchable
+/// window, or in the non-prefetchable one if they do not fit, which is always allowed.
+/// Non-prefetchable BARs are only placed in the non-prefetchable window, and 32-bit BARs only
+/// below 4 GiB. BARs are never placed in the reserved memory-mapped I/O ranges.
+#[derive(Clone, Debug)]
+pub struct PciBarAllocator {
+    mmio: (u64, u64),
+    prefetchable: Option<(u64, u64)>,
+    reserved: ReservedResources,
+    // Allocated BARs, mapping their base to their size.
+    allocated: BTreeMap<u64, u64>,
+}
+
+impl PciBarAllocator {
+    /// Create an allocator for the non-prefetchable window `mmio` and the optional
+    /// `prefetchable` window, both given as inclusive [`min`, `max`] ranges.
+    pub fn new(mmio: (u64, u64), prefetchable: Option<(u64, u64)>) -> Self {
+        PciBarAllocator {
+            mmio,
+            prefetchable,
+            reserved: ReservedResources::new(),
+            allocated: BTreeMap::new(),
+        }
+    }
+
+    /// Set the resources reserved for the platform, whose memory-mapped I/O ranges are
+    /// skipped by the following allocations.
+    pub fn set_reserved(&mut self, reserved: ReservedResources) {
+        self.reserved = reserved;
+    }
+
+    /// Allocate BAR `index` of a device, returning a
+    /// [`Resource::PciBar`](enum.Resource.html#variant.PciBar).
+    ///
+    /// Fails if the index does not fit in the header, if the size is not a power of two of at
+    /// least 16 bytes, or if no window has room for the BAR.
+    pub fn allocate(
+        &mut self,
+        index: u8,
+        size: u64,
+        bar_type: PciBarType,
+        prefetchable: bool,
+    ) -> Result<Resource, Error> {
+        let slots = match bar_type {
+            PciBarType::Mmio32 => 1,
+            PciBarType::Mmio64 => 2,
+        };
+        if index > PCI_BAR_COUNT - slots {
+            return Err(Error::InvalidPciBarIndex(index));
+        }
+        if size < PCI_BAR_MIN_SIZE || !size.is_power_of_two() {
+            return Err(Error::InvalidPciBarSize(size));
+        }
+
+        let mut windows = Vec::new();
+        if prefetchable {
+            windows.extend(self.prefetchable);
+        }
+        windows.push(self.mmio);
+        for (min, mut max) in windows {
+            if bar_type == PciBarType::Mmio32 {
+                max = max.min(MAX_32BIT_ADDRESS);
+            }
+            if let Some(base) = self.find_free(min, max, size) {
+                self.allocated.insert(base, size);
+                return Ok(Resource::PciBar {
+                    index,
+                    base,
+                    size,
+                    bar_type,
+                    prefetchable,
+                });
+            }
+        }
+        Err(Error::PciBarSpaceExhausted(index))
+    }
+
+    /// Free the BAR allocated at `base`, returning whether there was one.
+    pub fn free(&mut self, base: u64) -> bool {
+        self.allocated.remove(&base).is_some()
+    }
+
+    // Find the lowest base aligned to `size` such that the BAR lies within [`min`, `max`]
+    // without overlapping the allocated or reserved ones.
+    fn find_free(&self, min: u64, max: u64, size: u64) -> Option<u64> {
+        let mut used: Vec<(u64, u64)> = self
+            .allocated
+            .iter()
+            .map(|(&start, &len)| (start, len))
+            .chain(self.reserved.mmio_ranges().iter().copied())
+            .filter(|&(_, len)| len != 0)
+            .collect();
+        used.sort_unstable();
+
+        let mut base = align_up(min, size)?;
+        for (start, len) in used {
+            let last = base.checked_add(size - 1)?;
+            if last > max {
+                return None;
+            }
+            if last < start {
+                break;
+            }
+            if base <= start.saturating_add(len - 1) {
+                base = align_up(start.checked_add(len)?, size)?;
+            }
+        }
+        let last = base.checked_add(size - 1)?;
+        if last > max {
+            return None;
+        }
+        Some(base)
+    }
+}
+
+// Round `addr` up to a multiple of `align`, which must be a power of two.
+fn align_up(addr: u64, align: u64) -> Option<u64> {
+    Some(addr.checked_add(align - 1)? & !(align - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base(res: Resource) -> u64 {
+        match res {
+            Resource::PciBar { base, .. } => base,
+            _ => panic!("not a PCI BAR: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_pci_bar_alignment() {
+        let mut allocator = PciBarAllocator::new((0xc000_0000, 0xdfff_ffff), None);
+        assert_eq!(
+            allocator.allocate(0, 0x1000, PciBarType::Mmio32, false),
+            Ok(Resource::PciBar {
+                index: 0,
+                base: 0xc000_0000,
+                size: 0x1000,
+                bar_type: PciBarType::Mmio32,
+                prefetchable: false,
+            })
+        );
+        // The next BAR is aligned to its own size, and smaller ones fill the hole left.
+        let bar = allocator.allocate(1, 0x10_0000, PciBarType::Mmio32, false);
+        assert_eq!(base(bar.unwrap()), 0xc010_0000);
+        let bar = allocator.allocate(2, 0x1000, PciBarType::Mmio32, false);
+        assert_eq!(base(bar.unwrap()), 0xc000_1000);
+
+        assert!(allocator.free(0xc000_0000));
+        assert!(!allocator.free(0xc000_0000));
+        let bar = allocator.allocate(0, 0x10, PciBarType::Mmio32, false);
+        assert_eq!(base(bar.unwrap()), 0xc000_0000);
+
+        assert_eq!(
+            allocator.allocate(0, 0x1800, PciBarType::Mmio32, false),
+            Err(Error::InvalidPciBarSize(0x1800))
+        );
+        assert_eq!(
+            allocator.allocate(0, 8, PciBarType::Mmio32, false),
+            Err(Error::InvalidPciBarSize(8))
+        );
+        assert_eq!(
+            allocator.allocate(6, 0x1000, PciBarType::Mmio32, false),
+            Err(Error::InvalidPciBarIndex(6))
+        );
+        assert_eq!(
+            allocator.allocate(5, 0x1000, PciBarType::Mmio64, false),
+            Err(Error::InvalidPciBarIndex(5))
+        );
+        assert_eq!(
+            allocator.allocate(3, 0x4000_0000, PciBarType::Mmio32, false),
+            Err(Error::PciBarSpaceExhausted(3))
+        );
+    }
+
+    #[test]
+    fn test_pci_bar_windows() {
+        let mut allocator = PciBarAllocator::new(
+            (0xc000_0000, 0xc0ff_ffff),
+            Some((0xe000_0000, 0x1_ffff_ffff)),
+        );
+
+        // Non-prefetchable BARs stay in the non-prefetchable window.
+        let bar = allocator.allocate(0, 0x1000, PciBarType::Mmio64, false);
+        assert_eq!(base(bar.unwrap()), 0xc000_0000);
+        assert_eq!(
+            allocator.allocate(2, 0x1000_0000, PciBarType::Mmio64, false),
+            Err(Error::PciBarSpaceExhausted(2))
+        );
+
+        // Prefetchable BARs go to the prefetchable window, below 4 GiB if 32-bit.
+        let bar = allocator.allocate(2, 0x1000_0000, PciBarType::Mmio64, true);
+        assert_eq!(base(bar.unwrap()), 0xe000_0000);
+        let bar = allocator.allocate(4, 0x1000_0000, PciBarType::Mmio32, true);
+        assert_eq!(base(bar.unwrap()), 0xf000_0000);
+        let bar = allocator.allocate(0, 0x1_0000_0000, PciBarType::Mmio64, true);
+        assert_eq!(base(bar.unwrap()), 0x1_0000_0000);
+
+        // A 32-bit prefetchable BAR falls back to the non-prefetchable window.
+        let bar = allocator.allocate(1, 0x1000, PciBarType::Mmio32, true);
+        assert_eq!(base(bar.unwrap()), 0xc000_1000);
+
+        // Reserved ranges are skipped.
+        let mut allocator = PciBarAllocator::new((0xfe00_0000, 0xfeff_ffff), None);
+        let mut reserved = ReservedResources::new();
+        reserved.reserve_mmio(0xfe00_0800, 0x100);
+        reserved.reserve_mmio(0xfec0_0000, 0x40_0000);
+        allocator.set_reserved(reserved);
+        let bar = allocator.allocate(0, 0x1000, PciBarType::Mmio32, false);
+        assert_eq!(base(bar.unwrap()), 0xfe00_1000);
+        let bar = allocator.allocate(1, 0x40_0000, PciBarType::Mmio32, false);
+        assert_eq!(base(bar.unwrap()), 0xfe40_0000);
+        assert_eq!(
+            allocator.allocate(2, 0x80_0000, PciBarType::Mmio32, false),
+            Err(Error::PciBarSpaceExhausted(2))
+        );
+
+        // Windows reaching the end of the address space.
+        let mut allocator = PciBarAllocator::new((u64::MAX - 0xfff, u64::MAX), None);
+        let bar = allocator.allocate(0, 0x800, PciBarType::Mmio64, false);
+        assert_eq!(base(bar.unwrap()), u64::MAX - 0xfff);
+        let bar = allocator.allocate(2, 0x800, PciBarType::Mmio64, false);
+        assert_eq!(base(bar.unwrap()), u64::MAX - 0x7ff);
+        assert_eq!(
+            allocator.allocate(4, 0x10, PciBarType::Mmio64, false),
+            Err(Error::PciBarSpaceExhausted(4))
+        );
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::resources::{Error, Resource};
+
+/// Resources of the platform which must never be handed to devices, such as the IOAPIC and
+/// LAPIC windows, PCI holes, legacy ISA ports or the IRQs of platform devices.
+///
+/// The same set is passed to the allocators, which skip the reserved resources, and to
+/// [`IoManager::reserve_resources`](../device_manager/struct.IoManager.html#method.reserve_resources),
+/// which rejects devices claiming them.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReservedResources {
+    pio: Vec<(u16, u16)>,
+    mmio: Vec<(u64, u64)>,
+    irqs: Vec<u32>,
+}
+
+impl ReservedResources {
+    /// Create an empty set of reserved resources.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserve the port I/O range of `size` ports starting at `base`.
+    pub fn reserve_pio(&mut self, base: u16, size: u16) {
+        self.pio.push((base, size));
+    }
+
+    /// Reserve the memory-mapped I/O range of `size` bytes starting at `base`.
+    pub fn reserve_mmio(&mut self, base: u64, size: u64) {
+        self.mmio.push((base, size));
+    }
+
+    /// Reserve the interrupt line `irq`.
+    pub fn reserve_irq(&mut self, irq: u32) {
+        self.irqs.push(irq);
+    }
+
+    /// Return the reserved port I/O ranges, as (base, size) pairs.
+    pub fn pio_ranges(&self) -> &[(u16, u16)] {
+        &self.pio
+    }
+
+    /// Return the reserved memory-mapped I/O ranges, as (base, size) pairs.
+    pub fn mmio_ranges(&self) -> &[(u64, u64)] {
+        &self.mmio
+    }
+
+    /// Return the reserved interrupt lines.
+    pub fn irqs(&self) -> &[u32] {
+        &self.irqs
+    }
+
+    /// Check whether the port I/O range of `size` ports at `base` overlaps a reserved one.
+    pub fn is_pio_reserved(&self, base: u16, size: u16) -> bool {
+        self.pio
+            .iter()
+            .any(|&(b, s)| overlaps(base.into(), size.into(), b.into(), s.into()))
+    }
+
+    /// Check whether the memory-mapped I/O range of `size` bytes at `base` overlaps a
+    /// reserved one.
+    pub fn is_mmio_reserved(&self, base: u64, size: u64) -> bool {
+        self.mmio
+            .iter()
+            .any(|&(b, s)| overlaps(base.into(), size.into(), b.into(), s.into()))
+    }
+
+    /// Check whether any of the `size` interrupt lines starting at `base` is reserved.
+    pub fn is_irq_reserved(&self, base: u32, size: u32) -> bool {
+        self.irqs
+            .iter()
+            .any(|&irq| overlaps(base.into(), size.into(), irq.into(), 1))
+    }
+
+    /// Check that `res` does not claim any reserved resource, failing with
+    /// [`Error::ReservedResource`](enum.Error.html#variant.ReservedResource) otherwise.
+    pub fn check(&self, res: &Resource) -> Result<(), Error> {
+        let reserved = match *res {
+            Resource::PioAddressRange { base, size } => self.is_pio_reserved(base, size),
+            Resource::MmioAddressRange { base, size } | Resource::PciBar { base, size, .. } => {
+                self.is_mmio_reserved(base, size)
+            }
+            Resource::LegacyIrq(irq) => self.is_irq_reserved(irq, 1),
+            Resource::MsiIrq { base, size, .. } => self.is_irq_reserved(base, size),
+            Resource::MacAddress(_) | Resource::KvmMemSlot(_) => false,
+        };
+        if reserved {
+            return Err(Error::ReservedResource(res.clone()));
+        }
+        Ok(())
+    }
+}
+
+// Check whether the ranges [`base`, `base + size`) and [`other`, `other + other_size`) overlap.
+// The bounds are computed on 128 bits so ranges reaching the end of the address space
+// don't overflow.
+fn overlaps(base: u128, size: u128, other: u128, other_size: u128) -> bool {
+    size != 0 && other_size != 0 && base < other + other_size && other < base + size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::resources::{MsiIrqType, PciBarType};
+
+    #[test]
+    fn test_reserved_resources() {
+        let mut reserved = ReservedResources::new();
+        reserved.reserve_pio(0x20, 2);
+        reserved.reserve_mmio(0xfec0_0000, 0x1000);
+        reserved.reserve_mmio(u64::MAX - 0xfff, 0x1000);
+        reserved.reserve_irq(2);
+        assert_eq!(reserved.pio_ranges(), &[(0x20, 2)]);
+        assert_eq!(reserved.mmio_ranges().len(), 2);
+        assert_eq!(reserved.irqs(), &[2]);
+
+        assert!(reserved.is_pio_reserved(0x1f, 2));
+        assert!(!reserved.is_pio_reserved(0x22, 2));
+        assert!(!reserved.is_pio_reserved(0x20, 0));
+        assert!(reserved.is_mmio_reserved(0xfec0_0fff, 0x1000));
+        assert!(!reserved.is_mmio_reserved(0xfebf_f000, 0x1000));
+        assert!(reserved.is_mmio_reserved(u64::MAX, 1));
+        assert!(reserved.is_irq_reserved(0, 3));
+        assert!(!reserved.is_irq_reserved(3, 10));
+
+        let ok = [
+            Resource::PioAddressRange {
+                base: 0x3f8,
+                size: 8,
+            },
+            Resource::MmioAddressRange {
+                base: 0xd000_0000,
+                size: 0x1000,
+            },
+            Resource::LegacyIrq(4),
+            Resource::MsiIrq {
+                ty: MsiIrqType::PciMsix,
+                base: 24,
+                size: 8,
+            },
+            Resource::KvmMemSlot(2),
+        ];
+        for res in ok.iter() {
+            assert_eq!(reserved.check(res), Ok(()));
+        }
+
+        let claimed = [
+            Resource::PioAddressRange {
+                base: 0x21,
+                size: 1,
+            },
+            Resource::MmioAddressRange {
+                base: 0xfec0_0000,
+                size: 0x20,
+            },
+            Resource::PciBar {
+                index: 0,
+                base: 0xfe00_0000,
+                size: 0x100_0000,
+                bar_type: PciBarType::Mmio32,
+                prefetchable: false,
+            },
+            Resource::LegacyIrq(2),
+            Resource::MsiIrq {
+                ty: MsiIrqType::GenericMsi,
+                base: 0,
+                size: 4,
+            },
+        ];
+        for res in claimed.iter() {
+            assert_eq!(
+                reserved.check(res),
+                Err(Error::ReservedResource(res.clone()))
+            );
+        }
+    }
+}
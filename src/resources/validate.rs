@@ -0,0 +1,401 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::convert::TryFrom;
+
+use crate::resources::{
+    DeviceResources, Error, MsiIrqType, PciBarType, Resource, ResourceConstraint,
+};
+
+// A single resource needed to satisfy a constraint. Constraints asking for several KVM memory
+// slots are split into one requirement per slot, `unit` being the index of the slot.
+struct Requirement {
+    constraint: usize,
+    unit: u32,
+}
+
+impl DeviceResources {
+    /// Check that the resources satisfy `constraints`, such as the constraints of the device
+    /// they were allocated for.
+    ///
+    /// Every constraint must be matched to its own resources, with the requested size,
+    /// alignment and bounds, and every resource must be matched to a constraint. Otherwise
+    /// the method fails with
+    /// [`Error::ConstraintMismatch`](enum.Error.html#variant.ConstraintMismatch), listing the
+    /// constraints which could not be satisfied and the resources left over.
+    pub fn validate(&self, constraints: &[ResourceConstraint]) -> Result<(), Error> {
+        let resources = self.get_all_resources();
+        let mut requirements = Vec::new();
+        for (index, constraint) in constraints.iter().enumerate() {
+            let units = match *constraint {
+                // Asking for more slots than resources can't be satisfied anyway, so there is
+                // no point in creating more requirements than that.
+                ResourceConstraint::KvmMemSlot { size, .. } => size.min(
+                    u32::try_from(resources.len())
+                        .unwrap_or(u32::MAX)
+                        .saturating_add(1),
+                ),
+                _ => 1,
+            };
+            requirements.extend((0..units).map(|unit| Requirement {
+                constraint: index,
+                unit,
+            }));
+        }
+
+        // Match the requirements to the resources through augmenting paths, so a resource
+        // satisfying several constraints doesn't get taken by the wrong one.
+        let mut owners = vec![None; resources.len()];
+        // Indexes of the unsatisfied constraints, which are listed once even when several of
+        // their slots are missing.
+        let mut unmatched: Vec<usize> = Vec::new();
+        for req in 0..requirements.len() {
+            let mut visited = vec![false; resources.len()];
+            let matched = augment(
+                req,
+                &requirements,
+                constraints,
+                resources,
+                &mut owners,
+                &mut visited,
+            );
+            let constraint = requirements[req].constraint;
+            if !matched && !unmatched.contains(&constraint) {
+                unmatched.push(constraint);
+            }
+        }
+
+        let extra: Vec<Resource> = resources
+            .iter()
+            .zip(owners.iter())
+            .filter(|(_, owner)| owner.is_none())
+            .map(|(res, _)| res.clone())
+            .collect();
+        if !unmatched.is_empty() || !extra.is_empty() {
+            let unmatched = unmatched
+                .into_iter()
+                .map(|index| constraints[index].clone())
+                .collect();
+            return Err(Error::ConstraintMismatch { unmatched, extra });
+        }
+        Ok(())
+    }
+}
+
+// Try to match requirement `req` to a resource not visited yet, moving the requirement owning
+// it to another resource if needed.
+fn augment(
+    req: usize,
+    requirements: &[Requirement],
+    constraints: &[ResourceConstraint],
+    resources: &[Resource],
+    owners: &mut [Option<usize>],
+    visited: &mut [bool],
+) -> bool {
+    let requirement = &requirements[req];
+    let constraint = &constraints[requirement.constraint];
+    for (index, res) in resources.iter().enumerate() {
+        if visited[index] || !satisfies(constraint, requirement.unit, res) {
+            continue;
+        }
+        visited[index] = true;
+        let available = match owners[index] {
+            None => true,
+            Some(other) => augment(other, requirements, constraints, resources, owners, visited),
+        };
+        if available {
+            owners[index] = Some(req);
+            return true;
+        }
+    }
+    false
+}
+
+// Check whether `res` satisfies `constraint`, or the slot `unit` of it for KVM memory slots.
+fn satisfies(constraint: &ResourceConstraint, unit: u32, res: &Resource) -> bool {
+    match (constraint, res) {
+        (
+            &ResourceConstraint::PioAddress { range, align, size },
+            &Resource::PioAddressRange { base, size: len },
+        ) => {
+            len == size
+                && is_aligned(base.into(), align.into())
+                && is_within(
+                    base.into(),
+                    size.into(),
+                    range.map(|(min, max)| (min.into(), max.into())),
+                )
+        }
+        (
+            &ResourceConstraint::MmioAddress { range, align, size },
+            &Resource::MmioAddressRange { base, size: len },
+        ) => len == size && is_aligned(base, align) && is_within(base, size, range),
+        (&ResourceConstraint::LegacyIrq { irq }, &Resource::LegacyIrq(allocated)) => {
+            irq.is_none() || irq == Some(allocated)
+        }
+        (&ResourceConstraint::PciMsiIrq { size }, &Resource::MsiIrq { ty, size: len, .. }) => {
+            ty == MsiIrqType::PciMsi && len == size
+        }
+        (&ResourceConstraint::PciMsixIrq { size }, &Resource::MsiIrq { ty, size: len, .. }) => {
+            ty == MsiIrqType::PciMsix && len == size
+        }
+        (&ResourceConstraint::GenericIrq { size }, &Resource::MsiIrq { ty, size: len, .. }) => {
+            ty == MsiIrqType::GenericMsi && len == size
+        }
+        (&ResourceConstraint::KvmMemSlot { slot, .. }, &Resource::KvmMemSlot(allocated)) => {
+            match slot {
+                Some(slot) => slot.checked_add(unit) == Some(allocated),
+                None => true,
+            }
+        }
+        (&ResourceConstraint::MacAddress { addr }, &Resource::MacAddress(allocated)) => {
+            addr.is_none() || addr == Some(allocated)
+        }
+        (
+            &ResourceConstraint::PciBar {
+                index,
+                size,
+                bar_type,
+                prefetchable,
+            },
+            &Resource::PciBar {
+                index: allocated_index,
+                base,
+                size: len,
+                bar_type: allocated_type,
+                prefetchable: allocated_prefetchable,
+            },
+        ) => {
+            allocated_index == index
+                && len == size
+                && allocated_type == bar_type
+                && allocated_prefetchable == prefetchable
+                && is_aligned(base, size)
+                && (bar_type == PciBarType::Mmio64
+                    || is_within(base, size, Some((0, u32::MAX.into()))))
+        }
+        _ => false,
+    }
+}
+
+// Check whether `base` is a multiple of `align`, no alignment being required when it is 0.
+fn is_aligned(base: u64, align: u64) -> bool {
+    base.checked_rem(align).unwrap_or(0) == 0
+}
+
+// Check whether the range of `size` bytes at `base` lies within the inclusive `range`, if any.
+fn is_within(base: u64, size: u64, range: Option<(u64, u64)>) -> bool {
+    match range {
+        Some((min, max)) => {
+            base >= min && u128::from(base) + u128::from(size) <= u128::from(max) + 1
+        }
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device_resources(resources: &[Resource]) -> DeviceResources {
+        let mut device_resources = DeviceResources::new();
+        for res in resources {
+            device_resources.append(res.clone());
+        }
+        device_resources
+    }
+
+    fn constraints() -> Vec<ResourceConstraint> {
+        vec![
+            ResourceConstraint::pio_with_constraints(8, Some((0x3f8, 0x3ff)), 8),
+            ResourceConstraint::new_mmio(0x1000),
+            ResourceConstraint::mmio_with_constraints(
+                0x1000,
+                Some((0xd000_0000, 0xdfff_ffff)),
+                0x1000,
+            ),
+            ResourceConstraint::new_legacy_irq(Some(4)),
+            ResourceConstraint::PciMsixIrq { size: 2 },
+            ResourceConstraint::new_kvm_mem_slot(2, Some(3)),
+            ResourceConstraint::new_mac_address(None),
+            ResourceConstraint::new_pci_bar(0, 0x1000, PciBarType::Mmio32, false),
+        ]
+    }
+
+    // Resources satisfying `constraints()`.
+    fn resources() -> Vec<Resource> {
+        vec![
+            Resource::PioAddressRange {
+                base: 0x3f8,
+                size: 8,
+            },
+            // The range fitting both MMIO constraints must be handed to the bounded one, even
+            // though the unbounded one comes first.
+            Resource::MmioAddressRange {
+                base: 0xd000_0000,
+                size: 0x1000,
+            },
+            Resource::MmioAddressRange {
+                base: 0x1_0000_0000,
+                size: 0x1000,
+            },
+            Resource::LegacyIrq(4),
+            Resource::MsiIrq {
+                ty: MsiIrqType::PciMsix,
+                base: 24,
+                size: 2,
+            },
+            Resource::KvmMemSlot(4),
+            Resource::KvmMemSlot(3),
+            Resource::MacAddress("52:54:00:12:34:56".parse().unwrap()),
+            Resource::PciBar {
+                index: 0,
+                base: 0xc000_0000,
+                size: 0x1000,
+                bar_type: PciBarType::Mmio32,
+                prefetchable: false,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_validate() {
+        let constraints = constraints();
+        let resources = resources();
+        assert_eq!(device_resources(&resources).validate(&constraints), Ok(()));
+        let mut swapped = resources.clone();
+        swapped.swap(1, 2);
+        assert_eq!(device_resources(&swapped).validate(&constraints), Ok(()));
+        assert_eq!(DeviceResources::new().validate(&[]), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_mismatch() {
+        let constraints = constraints();
+        let extra = Resource::LegacyIrq(5);
+        let mut wrong = resources();
+        wrong.remove(0);
+        wrong.push(extra.clone());
+        assert_eq!(
+            device_resources(&wrong).validate(&constraints),
+            Err(Error::ConstraintMismatch {
+                unmatched: vec![constraints[0].clone()],
+                extra: vec![extra],
+            })
+        );
+
+        // Equal constraints are listed as many times as they are unsatisfied.
+        let irqs = [
+            ResourceConstraint::new_legacy_irq(None),
+            ResourceConstraint::new_legacy_irq(None),
+            ResourceConstraint::new_legacy_irq(None),
+        ];
+        assert_eq!(
+            device_resources(&[Resource::LegacyIrq(4)]).validate(&irqs),
+            Err(Error::ConstraintMismatch {
+                unmatched: irqs[1..].to_vec(),
+                extra: Vec::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_satisfies() {
+        let constraints = constraints();
+        // Resources breaking the bounds, alignment or size of their constraint.
+        let checks = [
+            (
+                0,
+                Resource::PioAddressRange {
+                    base: 0x3f0,
+                    size: 8,
+                },
+            ),
+            (
+                2,
+                Resource::MmioAddressRange {
+                    base: 0xd000_0800,
+                    size: 0x1000,
+                },
+            ),
+            (
+                2,
+                Resource::MmioAddressRange {
+                    base: 0xdfff_f000,
+                    size: 0x2000,
+                },
+            ),
+            (3, Resource::LegacyIrq(5)),
+            (
+                4,
+                Resource::MsiIrq {
+                    ty: MsiIrqType::PciMsi,
+                    base: 24,
+                    size: 2,
+                },
+            ),
+            (
+                7,
+                Resource::PciBar {
+                    index: 0,
+                    base: 0xc000_0800,
+                    size: 0x1000,
+                    bar_type: PciBarType::Mmio32,
+                    prefetchable: false,
+                },
+            ),
+            (
+                7,
+                Resource::PciBar {
+                    index: 0,
+                    base: 0x1_0000_0000,
+                    size: 0x1000,
+                    bar_type: PciBarType::Mmio32,
+                    prefetchable: false,
+                },
+            ),
+            (
+                7,
+                Resource::PciBar {
+                    index: 1,
+                    base: 0xc000_0000,
+                    size: 0x1000,
+                    bar_type: PciBarType::Mmio32,
+                    prefetchable: false,
+                },
+            ),
+        ];
+        for (constraint, res) in checks.iter() {
+            assert!(!satisfies(&constraints[*constraint], 0, res), "{:?}", res);
+        }
+    }
+
+    #[test]
+    fn test_validate_kvm_mem_slots() {
+        let slots = [ResourceConstraint::new_kvm_mem_slot(2, Some(3))];
+        assert!(
+            device_resources(&[Resource::KvmMemSlot(3), Resource::KvmMemSlot(5)])
+                .validate(&slots)
+                .is_err()
+        );
+        assert_eq!(
+            device_resources(&[Resource::KvmMemSlot(3)]).validate(&slots),
+            Err(Error::ConstraintMismatch {
+                unmatched: slots.to_vec(),
+                extra: Vec::new(),
+            })
+        );
+        // A constraint missing several slots is listed once.
+        assert_eq!(
+            DeviceResources::new().validate(&slots),
+            Err(Error::ConstraintMismatch {
+                unmatched: slots.to_vec(),
+                extra: Vec::new(),
+            })
+        );
+        let slots = [ResourceConstraint::new_kvm_mem_slot(u32::MAX, None)];
+        assert!(device_resources(&[Resource::KvmMemSlot(0)])
+            .validate(&slots)
+            .is_err());
+    }
+}
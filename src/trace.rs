@@ -0,0 +1,299 @@
+// Copyright 2026 The rust-vmm Authors. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! Tracing of I/O accesses.
+//!
+//! [`TracedDevice`] wraps any [`DevicePio`] or [`DeviceMmio`] and reports every
+//! access that reaches the inner device to a [`TraceSink`], which decides what to do with the
+//! resulting [`IoAccess`] records (collect them, print them, write them to a file, etc).
+//!
+//! A device only knows the range it is registered with, so [`TracedDevice`] reports accesses
+//! redirected by an alias window at their canonical address. Setting a sink on the
+//! [`IoManager`](../device_manager/struct.IoManager.html) with
+//! [`set_trace_sink`](../device_manager/struct.IoManager.html#method.set_trace_sink) traces
+//! accesses as they are dispatched instead: every access is then reported at the address used
+//! by the guest, along with its result, whether or not it reaches a device.
+//!
+//! # Example
+//!
+//! ```
+//! # use std::sync::{Arc, Mutex};
+//! # use vm_device::bus::{Error, PioAddress, PioAddressOffset, PioRange};
+//! # use vm_device::device_manager::{IoManager, PioManager};
+//! # use vm_device::trace::{IoAccess, IoDirection};
+//! # use vm_device::DevicePio;
+//! struct NoopDevice {}
+//!
+//! impl DevicePio for NoopDevice {
+//!     fn pio_read(&self, base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {}
+//!     fn pio_write(&self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {}
+//! }
+//!
+//! let mut manager = IoManager::new();
+//! let range = PioRange::new(PioAddress(0x3f8), 8).unwrap();
+//! manager.register_pio(range, Arc::new(NoopDevice {})).unwrap();
+//!
+//! // Every access dispatched from now on is traced into `sink`.
+//! let sink = Arc::new(Mutex::new(Vec::<IoAccess>::new()));
+//! manager.set_trace_sink(Some(sink.clone()));
+//! manager.pio_write(PioAddress(0x3f9), &[0x1]).unwrap();
+//! assert!(manager.pio_write(PioAddress(0x400), &[0x1]).is_err());
+//!
+//! let accesses = sink.lock().unwrap();
+//! assert_eq!(accesses.len(), 2);
+//! assert_eq!(accesses[0].address, 0x3f9);
+//! assert_eq!(accesses[0].offset, Some(1));
+//! assert_eq!(accesses[0].direction, IoDirection::Write);
+//! assert_eq!(accesses[1].result, Err(Error::DeviceNotFound));
+//! ```
+
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+pub use crate::bus::IoDirection;
+use crate::bus::{self, MmioAddress, MmioAddressOffset, PioAddress, PioAddressOffset};
+use crate::{DeviceMmio, DevicePio};
+
+/// The kind of bus an access was issued on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IoBus {
+    /// Port I/O bus.
+    Pio,
+    /// Memory-mapped I/O bus.
+    Mmio,
+}
+
+/// Describes a single traced access.
+#[derive(Clone, Debug)]
+pub struct IoAccess {
+    /// Bus the access was issued on.
+    pub bus: IoBus,
+    /// Absolute bus address of the access.
+    pub address: u64,
+    /// Offset of the access relative to the base of the device range, or `None` when the
+    /// access did not reach a device.
+    pub offset: Option<u64>,
+    /// Direction of the access.
+    pub direction: IoDirection,
+    /// Data written to the device, or returned by the device for reads.
+    pub data: Vec<u8>,
+    /// Result of the access, which always succeeds once it reaches a device.
+    pub result: Result<(), bus::Error>,
+    /// Moment the access completed.
+    pub timestamp: Instant,
+}
+
+/// Destination for the records produced by a [`TracedDevice`].
+pub trait TraceSink {
+    /// Consume the record of an access.
+    fn record(&self, access: IoAccess);
+}
+
+impl<T: TraceSink + ?Sized> TraceSink for Arc<T> {
+    fn record(&self, access: IoAccess) {
+        self.deref().record(access)
+    }
+}
+
+impl TraceSink for Mutex<Vec<IoAccess>> {
+    fn record(&self, access: IoAccess) {
+        self.lock().unwrap().push(access)
+    }
+}
+
+/// Wrapper which reports every access reaching `T` to a [`TraceSink`].
+///
+/// The reported address is the base of the range the access was dispatched to plus the offset
+/// of the access, which is the canonical address for accesses redirected by alias windows.
+///
+/// String accesses are handed to `T` as a whole, so it can use its bulk handlers, but they are
+/// reported as one [`IoAccess`] per element, as if the elements had been accessed one by one.
+pub struct TracedDevice<T, S> {
+    inner: T,
+    sink: S,
+}
+
+impl<T, S: TraceSink> TracedDevice<T, S> {
+    /// Wrap `inner` so that its accesses are reported to `sink`.
+    pub fn new(inner: T, sink: S) -> Self {
+        TracedDevice { inner, sink }
+    }
+
+    /// Return a reference to the wrapped device.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Consume the wrapper and return the wrapped device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn trace(&self, bus: IoBus, base: u64, offset: u64, direction: IoDirection, data: &[u8]) {
+        self.sink.record(IoAccess {
+            bus,
+            address: base.wrapping_add(offset),
+            offset: Some(offset),
+            direction,
+            data: data.to_vec(),
+            result: Ok(()),
+            timestamp: Instant::now(),
+        });
+    }
+}
+
+impl<T: DevicePio, S: TraceSink> DevicePio for TracedDevice<T, S> {
+    fn pio_read(&self, base: PioAddress, offset: PioAddressOffset, data: &mut [u8]) {
+        self.inner.pio_read(base, offset, data);
+        self.trace(
+            IoBus::Pio,
+            base.0.into(),
+            offset.into(),
+            IoDirection::Read,
+            data,
+        );
+    }
+
+    fn pio_write(&self, base: PioAddress, offset: PioAddressOffset, data: &[u8]) {
+        self.inner.pio_write(base, offset, data);
+        self.trace(
+            IoBus::Pio,
+            base.0.into(),
+            offset.into(),
+            IoDirection::Write,
+            data,
+        );
+    }
+
+    fn pio_read_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &mut [u8],
+    ) {
+        self.inner.pio_read_string(base, offset, size, data);
+        for element in data.chunks(size) {
+            self.trace(
+                IoBus::Pio,
+                base.0.into(),
+                offset.into(),
+                IoDirection::Read,
+                element,
+            );
+        }
+    }
+
+    fn pio_write_string(
+        &self,
+        base: PioAddress,
+        offset: PioAddressOffset,
+        size: usize,
+        data: &[u8],
+    ) {
+        self.inner.pio_write_string(base, offset, size, data);
+        for element in data.chunks(size) {
+            self.trace(
+                IoBus::Pio,
+                base.0.into(),
+                offset.into(),
+                IoDirection::Write,
+                element,
+            );
+        }
+    }
+
+    fn pio_poisoned(&self) -> bool {
+        self.inner.pio_poisoned()
+    }
+}
+
+impl<T: DeviceMmio, S: TraceSink> DeviceMmio for TracedDevice<T, S> {
+    fn mmio_read(&self, base: MmioAddress, offset: MmioAddressOffset, data: &mut [u8]) {
+        self.inner.mmio_read(base, offset, data);
+        self.trace(IoBus::Mmio, base.0, offset, IoDirection::Read, data);
+    }
+
+    fn mmio_write(&self, base: MmioAddress, offset: MmioAddressOffset, data: &[u8]) {
+        self.inner.mmio_write(base, offset, data);
+        self.trace(IoBus::Mmio, base.0, offset, IoDirection::Write, data);
+    }
+
+    fn mmio_poisoned(&self) -> bool {
+        self.inner.mmio_poisoned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DummyDevice {
+        config: Mutex<u8>,
+    }
+
+    impl DevicePio for DummyDevice {
+        fn pio_read(&self, _base: PioAddress, _offset: PioAddressOffset, data: &mut [u8]) {
+            data[0] = *self.config.lock().unwrap();
+        }
+
+        fn pio_write(&self, _base: PioAddress, _offset: PioAddressOffset, data: &[u8]) {
+            *self.config.lock().unwrap() = data[0];
+        }
+    }
+
+    impl DeviceMmio for DummyDevice {
+        fn mmio_read(&self, _base: MmioAddress, _offset: MmioAddressOffset, data: &mut [u8]) {
+            data[0] = *self.config.lock().unwrap();
+        }
+
+        fn mmio_write(&self, _base: MmioAddress, _offset: MmioAddressOffset, data: &[u8]) {
+            *self.config.lock().unwrap() = data[0];
+        }
+    }
+
+    #[test]
+    fn test_traced_device() {
+        let sink = Arc::new(Mutex::new(Vec::new()));
+        let device = TracedDevice::new(
+            DummyDevice {
+                config: Mutex::new(0),
+            },
+            sink.clone(),
+        );
+
+        device.pio_write(PioAddress(0x10), 2, &[0x12]);
+        let mut data = [0; 1];
+        device.pio_read(PioAddress(0x10), 3, &mut data);
+        assert_eq!(data, [0x12]);
+
+        device.mmio_write(MmioAddress(0x1000), 0x20, &[0x34]);
+        device.mmio_read(MmioAddress(0x1000), 0x24, &mut data);
+        assert_eq!(data, [0x34]);
+
+        let accesses = sink.lock().unwrap();
+        assert_eq!(accesses.len(), 4);
+
+        assert_eq!(accesses[0].bus, IoBus::Pio);
+        assert_eq!(accesses[0].address, 0x12);
+        assert_eq!(accesses[0].offset, Some(2));
+        assert_eq!(accesses[0].result, Ok(()));
+        assert_eq!(accesses[0].direction, IoDirection::Write);
+        assert_eq!(accesses[0].data, vec![0x12]);
+
+        // Reads record the data returned by the inner device.
+        assert_eq!(accesses[1].address, 0x13);
+        assert_eq!(accesses[1].direction, IoDirection::Read);
+        assert_eq!(accesses[1].data, vec![0x12]);
+
+        assert_eq!(accesses[2].bus, IoBus::Mmio);
+        assert_eq!(accesses[2].address, 0x1020);
+        assert_eq!(accesses[3].address, 0x1024);
+        assert_eq!(accesses[3].data, vec![0x34]);
+        assert!(accesses[0].timestamp <= accesses[3].timestamp);
+
+        drop(accesses);
+        assert_eq!(*device.into_inner().config.lock().unwrap(), 0x34);
+    }
+}